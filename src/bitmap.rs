@@ -1,10 +1,14 @@
 #[allow(dead_code)]
 
 use std::io::Write;
+use crate::color::LinearColor;
+use crate::palette::{Dithering, IndexedImage, Palette, dither, quantize};
+use crate::resample::{Filter, resample, upscale};
+use crate::view::{BitmapView, BitmapViewMut};
 use windows::Win32::{
     Foundation::RECT,
     Graphics::Gdi::{
-        BITMAPINFOHEADER, BI_RGB, BITMAPINFO, HDC, StretchDIBits, DIB_RGB_COLORS, SRCCOPY
+        BITMAPINFOHEADER, BI_RGB, BITMAPINFO, HDC, PatBlt, StretchDIBits, BLACKNESS, DIB_RGB_COLORS, SRCCOPY
     }
 };
 
@@ -53,12 +57,144 @@ impl Pixel {
         }
     }
 
+    /// Returns copy of the Pixel with specified alfa.
+    pub const fn with_alfa(self, alfa: u8) -> Self {
+        Self { alfa, ..self }
+    }
+
+    /// Composites the Pixel over `background` using its alfa as opacity (Porter-Duff source over).
+    pub fn over(&self, background: &Pixel) -> Pixel {
+        let alfa = self.alfa as u32;
+        let inverse = 255 - alfa;
+        let mix = |source: u8, destination: u8| ((source as u32 * alfa + destination as u32 * inverse + 127) / 255) as u8;
+        Pixel {
+            red: mix(self.red, background.red),
+            green: mix(self.green, background.green),
+            blue: mix(self.blue, background.blue),
+            alfa: (alfa + (background.alfa as u32 * inverse + 127) / 255) as u8,
+        }
+    }
+
+    /// Composites the Pixel over `background` like `over`, but mixes the colors in linear space.
+    ///
+    /// Mixing sRGB encoded values directly makes translucent and anti-aliased edges look too dark.
+    pub fn over_linear(&self, background: &Pixel) -> Pixel {
+        match self.alfa {
+            255 => *self,
+            0 => *background,
+            _ => LinearColor::from_srgb(*self).over(&LinearColor::from_srgb(*background)).to_srgb(),
+        }
+    }
+
+    /// Returns copy of the Pixel with color channels multiplied by its alfa.
+    pub fn premultiplied(&self) -> Pixel {
+        let alfa = self.alfa as u32;
+        Pixel {
+            red: multiply(self.red as u32, alfa) as u8,
+            green: multiply(self.green as u32, alfa) as u8,
+            blue: multiply(self.blue as u32, alfa) as u8,
+            alfa: self.alfa,
+        }
+    }
+
+    /// Returns copy of the premultiplied Pixel with color channels divided by its alfa.
+    pub fn unpremultiplied(&self) -> Pixel {
+        if self.alfa == 0 {
+            return Pixel::new(0, 0, 0).with_alfa(0);
+        }
+        let alfa = self.alfa as u32;
+        let divide = |channel: u8| ((channel as u32 * 255 + alfa / 2) / alfa).min(255) as u8;
+        Pixel { red: divide(self.red), green: divide(self.green), blue: divide(self.blue), alfa: self.alfa }
+    }
+
+    /// Composites the Pixel onto `background` using given blend mode, with its alfa as opacity.
+    ///
+    /// Colors of the background are used as they are, which is exact for opaque backgrounds
+    /// and backgrounds with premultiplied alfa. Resulting alfa is always the source over alfa.
+    /// Like `over` it mixes sRGB encoded values in integer arithmetic, drawing uses `blend_linear`.
+    pub fn blend(&self, background: &Pixel, mode: BlendMode, alfa_mode: AlfaMode) -> Pixel {
+        let source = match alfa_mode {
+            AlfaMode::Straight => self.premultiplied(),
+            AlfaMode::Premultiplied => *self,
+        };
+        let inverse = 255 - source.alfa as u32;
+        let channel = |source: u8, destination: u8| {
+            let (source, destination) = (source as u32, destination as u32);
+            let value = match mode {
+                BlendMode::SourceOver => source + multiply(destination, inverse),
+                BlendMode::Additive => source + destination,
+                BlendMode::Multiply => multiply(destination, inverse) + multiply(source, destination),
+                BlendMode::Screen => source + destination - multiply(source, destination),
+            };
+            value.min(255) as u8
+        };
+        Pixel {
+            red: channel(source.red, background.red),
+            green: channel(source.green, background.green),
+            blue: channel(source.blue, background.blue),
+            alfa: (source.alfa as u32 + multiply(background.alfa as u32, inverse)).min(255) as u8,
+        }
+    }
+
+    /// Composites the Pixel onto `background` like `blend`, but mixes the colors in linear space.
+    ///
+    /// Premultiplied pixels are unpremultiplied before decoding, as their channels hold sRGB encoded values.
+    pub fn blend_linear(&self, background: &Pixel, mode: BlendMode, alfa_mode: AlfaMode) -> Pixel {
+        let source = match alfa_mode {
+            AlfaMode::Straight => *self,
+            AlfaMode::Premultiplied => self.unpremultiplied(),
+        };
+        let (source, background) = (LinearColor::from_srgb(source), LinearColor::from_srgb(*background));
+        let (alfa, inverse) = (source.alfa, 1.0 - source.alfa);
+        let channel = |source: f32, destination: f32| {
+            let source = source * alfa;
+            let value = match mode {
+                BlendMode::SourceOver => source + destination * inverse,
+                BlendMode::Additive => source + destination,
+                BlendMode::Multiply => destination * inverse + source * destination,
+                BlendMode::Screen => source + destination - source * destination,
+            };
+            value.min(1.0)
+        };
+        LinearColor {
+            red: channel(source.red, background.red),
+            green: channel(source.green, background.green),
+            blue: channel(source.blue, background.blue),
+            alfa: (alfa + background.alfa * inverse).min(1.0),
+        }.to_srgb()
+    }
+
     /// Returns Pixel representation used in primitive plain ppm format.
     pub fn plain_ppm_pixel_format(&self) -> String {
         format!(" {} {} {} ", self.red, self.green, self.blue)
     }
 }
 
+/// Product of two values in 0 - 255 range, scaled back to that range.
+fn multiply(a: u32, b: u32) -> u32 {
+    (a * b + 127) / 255
+}
+
+/// How colors of a drawn pixel are combined with the pixel below it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Porter-Duff source over, the source covers the destination according to its opacity.
+    SourceOver,
+    /// Colors are added, useful for light and particle effects.
+    Additive,
+    /// Colors are multiplied, the result is never brighter than either of them.
+    Multiply,
+    /// Inverted colors are multiplied, the result is never darker than either of them.
+    Screen,
+}
+
+/// Whether color channels of a pixel are already multiplied by its alfa.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AlfaMode {
+    Straight,
+    Premultiplied,
+}
+
 impl Default for Pixel {
     fn default() -> Self {
         Self::new(BG.0, BG.1, BG.2)
@@ -98,8 +234,159 @@ mod test_pixel {
     fn pixel_too_many_numbers() {
         Pixel::from_string("0 0 0 0");
     }
+
+    /// Test Pixel::over
+    #[test]
+    fn pixel_over() {
+        let background = Pixel::new(0, 0, 0);
+        assert_eq!(Pixel::new(255, 255, 255).over(&background), Pixel::new(255, 255, 255));
+        assert_eq!(Pixel::new(255, 255, 255).with_alfa(0).over(&background), background);
+        assert_eq!(Pixel::new(200, 100, 0).with_alfa(128).over(&background), Pixel::new(100, 50, 0));
+    }
+
+    /// Test Pixel::over_linear
+    #[test]
+    fn pixel_over_linear() {
+        let background = Pixel::new(0, 0, 0);
+        assert_eq!(Pixel::new(255, 255, 255).with_alfa(128).over_linear(&background), Pixel::new(188, 188, 188));
+        assert_eq!(Pixel::new(10, 20, 30).with_alfa(0).over_linear(&background), background);
+        assert_eq!(Pixel::new(10, 20, 30).over_linear(&background), Pixel::new(10, 20, 30));
+    }
+
+    /// Test Pixel::blend
+    #[test]
+    fn pixel_blend() {
+        let background = Pixel::new(100, 200, 0);
+        let source = Pixel::new(200, 100, 255);
+        let half = source.with_alfa(128);
+        use BlendMode::*;
+        use AlfaMode::*;
+
+        assert_eq!(source.blend(&background, SourceOver, Straight), source);
+        assert_eq!(half.blend(&background, SourceOver, Straight), Pixel::new(150, 150, 128));
+        assert_eq!(source.blend(&background, Additive, Straight), Pixel::new(255, 255, 255));
+        assert_eq!(source.blend(&background, Multiply, Straight), Pixel::new(78, 78, 0));
+        assert_eq!(source.blend(&background, Screen, Straight), Pixel::new(222, 222, 255));
+        assert_eq!(half.blend(&background, Multiply, Straight), Pixel::new(89, 139, 0));
+        assert_eq!(half.premultiplied().blend(&background, Screen, Premultiplied), half.blend(&background, Screen, Straight));
+    }
+
+    /// Test Pixel::premultiplied and Pixel::unpremultiplied
+    #[test]
+    fn pixel_premultiplied() {
+        let pixel = Pixel::new(200, 100, 50).with_alfa(128);
+        assert_eq!(pixel.premultiplied(), Pixel::new(100, 50, 25).with_alfa(128));
+        assert_eq!(pixel.premultiplied().unpremultiplied(), Pixel::new(199, 100, 50).with_alfa(128));
+        assert_eq!(pixel.with_alfa(0).premultiplied().unpremultiplied(), Pixel::new(0, 0, 0).with_alfa(0));
+    }
+}
+
+/// Axis aligned rectangle of pixels.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+
+impl Rect {
+    pub const fn new(x: i32, y: i32, width: i32, height: i32) -> Self {
+        Self { x, y, width, height }
+    }
+
+    /// Coordinate one past the last column, saturated at `i32::MAX`.
+    pub const fn right(&self) -> i32 {
+        self.x.saturating_add(self.width)
+    }
+
+    /// Coordinate one past the last row, saturated at `i32::MAX`.
+    pub const fn bottom(&self) -> i32 {
+        self.y.saturating_add(self.height)
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.width <= 0 || self.height <= 0
+    }
+
+    pub const fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && y >= self.y && x < self.right() && y < self.bottom()
+    }
+
+    /// Returns the common part of two rectangles, empty rectangles have zero size.
+    pub fn intersection(&self, other: &Rect) -> Rect {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = self.right().min(other.right());
+        let bottom = self.bottom().min(other.bottom());
+        Rect::new(x, y, right.saturating_sub(x).max(0), bottom.saturating_sub(y).max(0))
+    }
+}
+
+
+/// Which neighbours of a pixel belong to the same region.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Connectivity {
+    /// Pixels sharing an edge.
+    Four,
+    /// Pixels sharing an edge or a corner.
+    Eight,
+}
+
+
+/// How the Bitmap is fitted into the client area of the window.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ResizePolicy {
+    /// Bitmap follows the size of the client area, its pixels map one to one onto the screen.
+    Native,
+    /// Bitmap has fixed resolution and is stretched over the whole client area.
+    Stretch { width: usize, height: usize },
+    /// Bitmap has fixed resolution and is scaled to the largest size with unchanged aspect ratio fitting the client area.
+    Letterbox { width: usize, height: usize },
+    /// Bitmap has fixed resolution and is scaled by the largest integer factor fitting the client area, which keeps its pixels square.
+    IntegerScale { width: usize, height: usize },
+}
+
+
+impl ResizePolicy {
+    /// Returns dimensions of the Bitmap for client area of given size.
+    pub fn bitmap_size(&self, client_width: usize, client_height: usize) -> (usize, usize) {
+        match *self {
+            ResizePolicy::Native => (client_width, client_height),
+            ResizePolicy::Stretch { width, height }
+            | ResizePolicy::Letterbox { width, height }
+            | ResizePolicy::IntegerScale { width, height } => (width, height),
+        }
+    }
+
+    /// Returns the part of the client area covered by the Bitmap of given dimensions.
+    pub fn destination(&self, width: usize, height: usize, client: &Rect) -> Rect {
+        let (width, height) = (width.max(1) as i32, height.max(1) as i32);
+        let (scaled_width, scaled_height) = match self {
+            ResizePolicy::Native | ResizePolicy::Stretch { .. } => return *client,
+            ResizePolicy::Letterbox { .. } => {
+                let scale = (client.width as f32 / width as f32).min(client.height as f32 / height as f32);
+                ((width as f32 * scale).round() as i32, (height as f32 * scale).round() as i32)
+            },
+            ResizePolicy::IntegerScale { .. } => {
+                let scale = (client.width / width).min(client.height / height).max(1);
+                (width * scale, height * scale)
+            },
+        };
+        Rect::new(client.x + (client.width - scaled_width) / 2, client.y + (client.height - scaled_height) / 2, scaled_width, scaled_height)
+    }
+
+    /// Aspect ratio the image should be rendered with to appear undistorted in the client area.
+    pub fn aspect_ratio(&self, bitmap: &Bitmap, client: &Rect) -> f32 {
+        match self {
+            ResizePolicy::Stretch { .. } => client.width as f32 / client.height.max(1) as f32,
+            _ => bitmap.aspect_ratio(),
+        }
+    }
 }
 
+
 /// 2D RGBA Bitmap.
 pub struct Bitmap {
     pub width:  usize,
@@ -133,21 +420,47 @@ impl Bitmap {
         }
     }
 
+    /// Changes dimensions of the Bitmap, contents are cleared when the size changes and kept otherwise.
     pub fn resize(&mut self, width: usize, height: usize) {
-        self.buffer.resize(width * height, Pixel::default());
+        if (width, height) == (self.width, self.height) {
+            return;
+        }
+        self.width = width;
+        self.height = height;
+        self.buffer = vec![Pixel::default(); width * height];
+        self.info.bmiHeader.biWidth = width as i32;
+        self.info.bmiHeader.biHeight = -(height as i32);
     }
 
-    pub fn blit(&mut self, device_context: &HDC, client_rect: &RECT) {
-        let client_width = client_rect.right - client_rect.left;
-        let client_height = client_rect.bottom - client_rect.top;
+    /// Ratio of width to height.
+    pub fn aspect_ratio(&self) -> f32 {
+        self.width as f32 / self.height.max(1) as f32
+    }
+
+    /// Copies the Bitmap into the client area, placed according to the resize policy.
+    ///
+    /// Parts of the client area not covered by the Bitmap are filled with black.
+    pub fn blit(&mut self, device_context: &HDC, client_rect: &RECT, policy: &ResizePolicy) {
+        let client = Rect::new(client_rect.left, client_rect.top, client_rect.right - client_rect.left, client_rect.bottom - client_rect.top);
+        let destination = policy.destination(self.width, self.height, &client);
+
+        let bars = [
+            Rect::new(client.x, client.y, client.width, destination.y - client.y),
+            Rect::new(client.x, destination.bottom(), client.width, client.bottom() - destination.bottom()),
+            Rect::new(client.x, destination.y, destination.x - client.x, destination.height),
+            Rect::new(destination.right(), destination.y, client.right() - destination.right(), destination.height),
+        ];
 
         unsafe {
+            for bar in bars.iter().filter(|bar| !bar.is_empty()) {
+                PatBlt(device_context, bar.x, bar.y, bar.width, bar.height, BLACKNESS);
+            }
             StretchDIBits(
                 device_context,
-                0,
-                0,
-                client_width,
-                client_height,
+                destination.x,
+                destination.y,
+                destination.width,
+                destination.height,
                 0,
                 0,
                 self.width as i32,
@@ -160,6 +473,11 @@ impl Bitmap {
         };
     }
 
+    /// Rectangle covering the whole bitmap.
+    pub fn rect(&self) -> Rect {
+        Rect::new(0, 0, self.width as i32, self.height as i32)
+    }
+
     fn calculate_index(&self, x: usize, y: usize) -> usize {
         x + y * self.width
     }
@@ -188,6 +506,111 @@ impl Bitmap {
         self.buffer[index] = pixel;
     }
 
+    /// Returns pixel at given coordinates, `None` outside of the bitmap.
+    pub fn get_pixel(&self, x: usize, y: usize) -> Option<Pixel> {
+        self.checked_index(x, y).map(|index| self.buffer[index])
+    }
+
+    /// Returns mutable reference to pixel at given coordinates, `None` outside of the bitmap.
+    pub fn pixel_mut(&mut self, x: usize, y: usize) -> Option<&mut Pixel> {
+        self.checked_index(x, y).map(move |index| &mut self.buffer[index])
+    }
+
+    /// Sets pixel if the coordinates lie inside of the bitmap, returns whether it was set.
+    pub fn try_set_pixel(&mut self, x: usize, y: usize, pixel: Pixel) -> bool {
+        match self.pixel_mut(x, y) {
+            Some(destination) => {
+                *destination = pixel;
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn checked_index(&self, x: usize, y: usize) -> Option<usize> {
+        (x < self.width && y < self.height).then(|| self.calculate_index(x, y))
+    }
+
+    /// All pixels row by row.
+    pub fn pixels(&self) -> &[Pixel] {
+        &self.buffer
+    }
+
+    /// All pixels row by row.
+    pub fn pixels_mut(&mut self) -> &mut [Pixel] {
+        &mut self.buffer
+    }
+
+    /// Returns pixels of a single row, `None` outside of the bitmap.
+    pub fn row(&self, y: usize) -> Option<&[Pixel]> {
+        (y < self.height).then(|| &self.buffer[y * self.width..(y + 1) * self.width])
+    }
+
+    /// Returns pixels of a single row, `None` outside of the bitmap.
+    pub fn row_mut(&mut self, y: usize) -> Option<&mut [Pixel]> {
+        let width = self.width;
+        (y < self.height).then(move || &mut self.buffer[y * width..(y + 1) * width])
+    }
+
+    /// Iterator over rows from top to bottom.
+    pub fn rows(&self) -> impl Iterator<Item = &[Pixel]> {
+        self.buffer.chunks_exact(self.width.max(1))
+    }
+
+    /// Iterator over rows from top to bottom.
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [Pixel]> {
+        self.buffer.chunks_exact_mut(self.width.max(1))
+    }
+
+    /// Iterator over all pixels with their coordinates, row by row.
+    pub fn enumerate_pixels(&self) -> impl Iterator<Item = (usize, usize, &Pixel)> {
+        let width = self.width;
+        self.buffer.iter().enumerate().map(move |(index, pixel)| (index % width, index / width, pixel))
+    }
+
+    /// Iterator over all pixels with their coordinates, row by row.
+    pub fn enumerate_pixels_mut(&mut self) -> impl Iterator<Item = (usize, usize, &mut Pixel)> {
+        let width = self.width;
+        self.buffer.iter_mut().enumerate().map(move |(index, pixel)| (index % width, index / width, pixel))
+    }
+
+    /// Read only view of the part of the Bitmap inside of `rect`.
+    pub fn view(&self, rect: Rect) -> BitmapView<'_> {
+        BitmapView::new(self, rect)
+    }
+
+    /// View of the part of the Bitmap inside of `rect`, which can be drawn into like a separate Bitmap.
+    pub fn view_mut(&mut self, rect: Rect) -> BitmapViewMut<'_> {
+        BitmapViewMut::new(self, rect)
+    }
+
+    /// Calls `function` with the index and pixels of every row, rows are split between all available threads.
+    pub fn par_rows_mut<F: Fn(usize, &mut [Pixel]) + Sync>(&mut self, function: F) {
+        let width = self.width.max(1);
+        let threads = std::thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1);
+        let rows_per_thread = self.height.div_ceil(threads).max(1);
+        let function = &function;
+
+        std::thread::scope(|scope| {
+            for (chunk, pixels) in self.buffer.chunks_mut(rows_per_thread * width).enumerate() {
+                scope.spawn(move || {
+                    for (offset, row) in pixels.chunks_exact_mut(width).enumerate() {
+                        function(chunk * rows_per_thread + offset, row);
+                    }
+                });
+            }
+        });
+    }
+
+    /// Blends pixel into the bitmap using its alfa channel as opacity, colors are mixed in linear space.
+    ///
+    /// Pixels outside of the bitmap are skipped.
+    pub fn blend_pixel(&mut self, x: usize, y: usize, pixel: Pixel) {
+        if let Some(index) = self.checked_index(x, y) {
+            self.buffer[index] = pixel.over_linear(&self.buffer[index]);
+        }
+    }
+
     /// Returns Bitmap representation in plain ppm format.
     pub fn plain_ppm_format(&self) -> String {
         let pixel_repr_len =
@@ -228,6 +651,127 @@ impl Bitmap {
         write!(file_handle, "{}", &self.plain_ppm_format()[..])?;
         Ok(())
     }
+
+    /// Returns copy of the Bitmap scaled to given dimensions with the filter.
+    pub fn resampled(&self, width: usize, height: usize, filter: Filter) -> Bitmap {
+        let mut result = Bitmap::new(width, height);
+        self.resample_into(&mut result, filter);
+        result
+    }
+
+    /// Scales the Bitmap to fill the whole `destination`, for example when presenting low resolution render targets.
+    pub fn resample_into(&self, destination: &mut Bitmap, filter: Filter) {
+        destination.buffer = resample(&self.buffer, self.width, self.height, destination.width, destination.height, filter);
+    }
+
+    /// Returns copy of the Bitmap enlarged by an integer factor, every pixel becomes a square of `factor` x `factor` pixels.
+    pub fn upscaled(&self, factor: usize) -> Bitmap {
+        let mut result = Bitmap::new(self.width * factor, self.height * factor);
+        result.buffer = upscale(&self.buffer, self.width, self.height, factor);
+        result
+    }
+
+    /// Returns the Bitmap reduced to colors of the palette, for example before exporting it.
+    pub fn quantized(&self, palette: &Palette, dithering: Dithering) -> IndexedImage {
+        IndexedImage {
+            width: self.width,
+            height: self.height,
+            palette: palette.clone(),
+            indices: quantize(&self.buffer, self.width, self.height, palette, dithering),
+        }
+    }
+
+    /// Replaces colors of the Bitmap with the closest colors of the palette, alfa is kept.
+    pub fn dither(&mut self, palette: &Palette, dithering: Dithering) {
+        dither(&mut self.buffer, self.width, self.height, palette, dithering);
+    }
+
+    /// Composites `source_rect` part of `source` onto the bitmap with its top left corner at (x, y).
+    ///
+    /// Colors are mixed in linear space like in `blend_pixel`. Parts of the source rectangle outside of either bitmap are skipped.
+    pub fn draw_bitmap(&mut self, source: &Bitmap, source_rect: Rect, x: i32, y: i32, mode: BlendMode, alfa_mode: AlfaMode) {
+        let source_rect = source_rect.intersection(&source.rect());
+        // Offsets may not fit into i32 when the destination lies far away.
+        let (offset_x, offset_y) = (x as i64 - source_rect.x as i64, y as i64 - source_rect.y as i64);
+        let destination = Rect::new(x, y, source_rect.width, source_rect.height).intersection(&self.rect());
+
+        for y in destination.y..destination.bottom() {
+            for x in destination.x..destination.right() {
+                let source_pixel = source.buffer[source.calculate_index((x as i64 - offset_x) as usize, (y as i64 - offset_y) as usize)];
+                let index = self.calculate_index(x as usize, y as usize);
+                self.buffer[index] = source_pixel.blend_linear(&self.buffer[index], mode, alfa_mode);
+            }
+        }
+    }
+
+    /// Fills region of similar colors containing pixel (x, y), returns number of filled pixels.
+    ///
+    /// Pixel belongs to the region when none of its color channels differs from the starting pixel
+    /// by more than `tolerance`, with tolerance of 0 only exact matches are filled.
+    pub fn flood_fill(&mut self, x: usize, y: usize, color: &Rgb, connectivity: Connectivity, tolerance: u8) -> usize {
+        if x >= self.width || y >= self.height {
+            return 0;
+        }
+        let seed = self.buffer[self.calculate_index(x, y)];
+        let difference = |a: u8, b: u8| a.abs_diff(b) <= tolerance;
+        self.flood_fill_with(x, y, Pixel::from_rgb_tuple(*color), connectivity, |pixel| {
+            difference(pixel.red, seed.red) && difference(pixel.green, seed.green) && difference(pixel.blue, seed.blue)
+        })
+    }
+
+    /// Replaces pixels of the region containing pixel (x, y) for which `inside` holds, returns number of replaced pixels.
+    ///
+    /// Scanline flood fill, whole horizontal runs are filled at once and only a single seed per run
+    /// of the neighbouring rows is remembered. Seeds are kept on the heap, so large regions do not overflow the stack.
+    /// Every pixel is replaced at most once and never tested again afterwards, so `inside` may also accept the replacement.
+    /// Pixels left unfilled may be tested several times, once for every run next to them.
+    pub fn flood_fill_with<F: FnMut(&Pixel) -> bool>(&mut self, x: usize, y: usize, replacement: Pixel, connectivity: Connectivity, mut inside: F) -> usize {
+        if x >= self.width || y >= self.height {
+            return 0;
+        }
+        let mut filled = vec![false; self.buffer.len()];
+        let mut count = 0;
+        let mut seeds = vec![(x, y)];
+
+        while let Some((x, y)) = seeds.pop() {
+            let index = self.calculate_index(x, y);
+            if filled[index] || !inside(&self.buffer[index]) {
+                continue;
+            }
+
+            // Extend the run to both sides of the seed.
+            let row = y * self.width;
+            let (mut left, mut right) = (x, x);
+            while left > 0 && !filled[row + left - 1] && inside(&self.buffer[row + left - 1]) {
+                left -= 1;
+            }
+            while right + 1 < self.width && !filled[row + right + 1] && inside(&self.buffer[row + right + 1]) {
+                right += 1;
+            }
+            self.buffer[row + left..=row + right].fill(replacement);
+            filled[row + left..=row + right].fill(true);
+            count += right - left + 1;
+
+            // Diagonal neighbours extend the range of the adjacent rows by one pixel.
+            let (start, end) = match connectivity {
+                Connectivity::Four => (left, right),
+                Connectivity::Eight => (left.saturating_sub(1), (right + 1).min(self.width - 1)),
+            };
+            let rows = [y.checked_sub(1), Some(y + 1).filter(|&y| y < self.height)];
+            for y in rows.into_iter().flatten() {
+                let row = y * self.width;
+                let mut in_run = false;
+                for x in start..=end {
+                    let fillable = !filled[row + x] && inside(&self.buffer[row + x]);
+                    if fillable && !in_run {
+                        seeds.push((x, y));
+                    }
+                    in_run = fillable;
+                }
+            }
+        }
+        count
+    }
 }
 
 impl Default for Bitmap {
@@ -235,3 +779,239 @@ impl Default for Bitmap {
         Self::new(960, 540)
     }
 }
+
+/// Test suite for pixel access.
+#[cfg(test)]
+mod test_access {
+    use super::*;
+    use crate::view::RenderTarget;
+
+    #[test]
+    fn bounds_checked_access() {
+        let mut bitmap = Bitmap::new(3, 2);
+        assert!(bitmap.try_set_pixel(2, 1, Pixel::new(1, 2, 3)));
+        assert!(!bitmap.try_set_pixel(3, 1, Pixel::new(1, 2, 3)));
+        assert!(!bitmap.try_set_pixel(0, 2, Pixel::new(1, 2, 3)));
+
+        assert_eq!(bitmap.get_pixel(2, 1), Some(Pixel::new(1, 2, 3)));
+        assert_eq!(bitmap.get_pixel(3, 0), None);
+        assert_eq!(bitmap.pixels()[5], Pixel::new(1, 2, 3));
+
+        bitmap.pixel_mut(0, 0).unwrap().red = 7;
+        assert_eq!(bitmap.row(0).unwrap()[0].red, 7);
+        assert!(bitmap.row(2).is_none());
+
+        // Blending outside of the bitmap neither wraps into the next row nor panics, like for every other render target.
+        let before = bitmap.pixels().to_vec();
+        for (x, y) in [(3, 0), (0, 2), (usize::MAX, usize::MAX)] {
+            bitmap.blend_pixel(x, y, Pixel::new(255, 255, 255));
+            RenderTarget::blend_pixel(&mut bitmap, x, y, Pixel::new(255, 255, 255));
+        }
+        assert_eq!(bitmap.pixels(), &before[..]);
+    }
+
+    #[test]
+    fn iterators() {
+        let mut bitmap = Bitmap::new(4, 3);
+        for (x, y, pixel) in bitmap.enumerate_pixels_mut() {
+            *pixel = Pixel::new(x as u8, y as u8, 0);
+        }
+        assert_eq!(bitmap.rows().count(), 3);
+        assert!(bitmap.rows().enumerate().all(|(y, row)| row.iter().enumerate().all(|(x, pixel)| *pixel == Pixel::new(x as u8, y as u8, 0))));
+        assert_eq!(bitmap.enumerate_pixels().last().map(|(x, y, _)| (x, y)), Some((3, 2)));
+    }
+
+    #[test]
+    fn parallel_rows() {
+        let mut bitmap = Bitmap::new(5, 37);
+        bitmap.par_rows_mut(|y, row| row.fill(Pixel::new(y as u8, 0, 0)));
+        assert!(bitmap.enumerate_pixels().all(|(_, y, pixel)| pixel.red == y as u8));
+    }
+}
+
+
+/// Test suite for resizing.
+#[cfg(test)]
+mod test_resize {
+    use super::*;
+
+    #[test]
+    fn resize() {
+        let mut bitmap = Bitmap::new(4, 3);
+        bitmap.resize(6, 5);
+        assert_eq!((bitmap.width, bitmap.height, bitmap.buffer.len()), (6, 5, 30));
+        assert_eq!((bitmap.info.bmiHeader.biWidth, bitmap.info.bmiHeader.biHeight), (6, -5));
+        bitmap.set_pixel(5, 4, Pixel::new(0, 0, 0));
+
+        // Contents are kept when the size does not change and cleared when it does.
+        bitmap.resize(6, 5);
+        assert_eq!(bitmap.buffer[bitmap.calculate_index(5, 4)], Pixel::new(0, 0, 0));
+        bitmap.resize(5, 6);
+        assert!(bitmap.buffer.iter().all(|pixel| *pixel == Pixel::default()));
+    }
+
+    #[test]
+    fn destination() {
+        let client = Rect::new(0, 0, 1000, 500);
+        let fixed = (320, 180);
+        assert_eq!(ResizePolicy::Native.bitmap_size(1000, 500), (1000, 500));
+        assert_eq!(ResizePolicy::Letterbox { width: fixed.0, height: fixed.1 }.bitmap_size(1000, 500), fixed);
+
+        assert_eq!(ResizePolicy::Native.destination(1000, 500, &client), client);
+        assert_eq!(ResizePolicy::Stretch { width: fixed.0, height: fixed.1 }.destination(fixed.0, fixed.1, &client), client);
+        assert_eq!(ResizePolicy::Letterbox { width: fixed.0, height: fixed.1 }.destination(fixed.0, fixed.1, &client), Rect::new(55, 0, 889, 500));
+        assert_eq!(ResizePolicy::IntegerScale { width: fixed.0, height: fixed.1 }.destination(fixed.0, fixed.1, &client), Rect::new(180, 70, 640, 360));
+    }
+}
+
+
+/// Test suite for Bitmap::draw_bitmap.
+#[cfg(test)]
+mod test_draw_bitmap {
+    use super::*;
+
+    fn sprite() -> Bitmap {
+        let mut sprite = Bitmap::new(4, 4);
+        sprite.fill(&(255, 0, 0));
+        sprite.set_pixel(0, 0, Pixel::new(0, 0, 255).with_alfa(0));
+        sprite
+    }
+
+    fn count(bitmap: &Bitmap, pixel: Pixel) -> usize {
+        bitmap.buffer.iter().filter(|&&other| other == pixel).count()
+    }
+
+    #[test]
+    fn transparent_pixels_keep_background() {
+        let mut bitmap = Bitmap::new(8, 8);
+        bitmap.fill(&(0, 0, 0));
+        bitmap.draw_bitmap(&sprite(), sprite().rect(), 2, 2, BlendMode::SourceOver, AlfaMode::Straight);
+
+        assert_eq!(count(&bitmap, Pixel::new(255, 0, 0)), 15);
+        assert_eq!(bitmap.buffer[bitmap.calculate_index(2, 2)], Pixel::new(0, 0, 0));
+    }
+
+    #[test]
+    fn clipping() {
+        let mut bitmap = Bitmap::new(8, 8);
+        bitmap.fill(&(0, 0, 0));
+        bitmap.draw_bitmap(&sprite(), Rect::new(1, 1, 10, 10), -1, 6, BlendMode::SourceOver, AlfaMode::Straight);
+        assert_eq!(count(&bitmap, Pixel::new(255, 0, 0)), 4);
+        assert_eq!(bitmap.buffer[bitmap.calculate_index(0, 6)], Pixel::new(255, 0, 0));
+
+        bitmap.draw_bitmap(&sprite(), sprite().rect(), 8, -100, BlendMode::SourceOver, AlfaMode::Straight);
+        assert_eq!(count(&bitmap, Pixel::new(255, 0, 0)), 4);
+
+        // Far away destinations neither overflow nor draw anything.
+        for (x, y) in [(i32::MAX - 1, 0), (0, i32::MAX), (i32::MIN, i32::MIN), (i32::MIN, 0)] {
+            bitmap.draw_bitmap(&sprite(), Rect::new(1, 1, i32::MAX, i32::MAX), x, y, BlendMode::SourceOver, AlfaMode::Straight);
+        }
+        assert_eq!(count(&bitmap, Pixel::new(255, 0, 0)), 4);
+        assert_eq!(Rect::new(i32::MAX - 1, 0, 10, 10).right(), i32::MAX);
+        assert!(Rect::new(i32::MAX - 1, 0, 10, 10).intersection(&Rect::new(i32::MIN, 0, 1, 1)).is_empty());
+    }
+
+    #[test]
+    fn matches_blend_pixel() {
+        let sprite = |premultiplied: bool| {
+            let mut sprite = Bitmap::new(16, 16);
+            for (x, y, pixel) in sprite.enumerate_pixels_mut() {
+                *pixel = Pixel::new((x * 16) as u8, (y * 16) as u8, 200).with_alfa((x * 8 + y * 8) as u8);
+                if premultiplied {
+                    *pixel = pixel.premultiplied();
+                }
+            }
+            sprite
+        };
+        let background = || {
+            let mut background = Bitmap::new(16, 16);
+            for (x, y, pixel) in background.enumerate_pixels_mut() {
+                *pixel = Pixel::new(255 - (y * 16) as u8, 30, (x * 16) as u8);
+            }
+            background
+        };
+
+        let mut expected = background();
+        for (x, y, pixel) in sprite(false).enumerate_pixels() {
+            expected.blend_pixel(x, y, *pixel);
+        }
+        let mut straight = background();
+        straight.draw_bitmap(&sprite(false), Rect::new(0, 0, 16, 16), 0, 0, BlendMode::SourceOver, AlfaMode::Straight);
+        assert_eq!(straight.buffer, expected.buffer);
+
+        // Premultiplying to 8 bits loses some precision of almost transparent pixels.
+        let mut premultiplied = background();
+        premultiplied.draw_bitmap(&sprite(true), Rect::new(0, 0, 16, 16), 0, 0, BlendMode::SourceOver, AlfaMode::Premultiplied);
+        for (pixel, expected) in premultiplied.buffer.iter().zip(&expected.buffer) {
+            let close = |a: u8, b: u8| a.abs_diff(b) <= 3;
+            assert!(close(pixel.red, expected.red) && close(pixel.green, expected.green) && close(pixel.blue, expected.blue), "{:?} {:?}", pixel, expected);
+        }
+    }
+}
+
+
+/// Test suite for flood fill.
+#[cfg(test)]
+mod test_flood_fill {
+    use super::*;
+
+    const WHITE: Rgb = (255, 255, 255);
+
+    fn color_at(bitmap: &Bitmap, x: usize, y: usize) -> Rgb {
+        let pixel = bitmap.buffer[bitmap.calculate_index(x, y)];
+        (pixel.red, pixel.green, pixel.blue)
+    }
+
+    /// Bitmap split in two by a diagonal line of white pixels.
+    fn diagonal() -> Bitmap {
+        let mut bitmap = Bitmap::new(8, 8);
+        bitmap.fill(&(0, 0, 0));
+        for i in 0..8 {
+            bitmap.set_pixel(i, 7 - i, Pixel::from_rgb_tuple(WHITE));
+        }
+        bitmap
+    }
+
+    #[test]
+    fn connectivity() {
+        let mut bitmap = diagonal();
+        assert_eq!(bitmap.flood_fill(0, 0, &(255, 0, 0), Connectivity::Four, 0), 28);
+        assert_eq!(color_at(&bitmap, 7, 7), (0, 0, 0));
+
+        let mut bitmap = diagonal();
+        assert_eq!(bitmap.flood_fill(0, 0, &(255, 0, 0), Connectivity::Eight, 0), 56);
+        assert_eq!(color_at(&bitmap, 7, 7), (255, 0, 0));
+    }
+
+    #[test]
+    fn tolerance() {
+        let mut bitmap = Bitmap::new(4, 1);
+        for (x, value) in [0, 10, 20, 30].into_iter().enumerate() {
+            bitmap.set_pixel(x, 0, Pixel::new(value, value, value));
+        }
+        assert_eq!(bitmap.flood_fill(0, 0, &WHITE, Connectivity::Four, 0), 1);
+        assert_eq!(bitmap.flood_fill(3, 0, &WHITE, Connectivity::Four, 15), 2);
+    }
+
+    #[test]
+    fn replacement_matching_predicate() {
+        let mut bitmap = Bitmap::new(16, 16);
+        assert_eq!(bitmap.flood_fill_with(8, 8, Pixel::default(), Connectivity::Eight, |_| true), 16 * 16);
+    }
+
+    #[test]
+    fn large_region() {
+        let mut bitmap = Bitmap::default();
+        let (width, height) = (bitmap.width, bitmap.height);
+        // Comb shaped walls make the region one long winding corridor.
+        for x in (1..width).step_by(2) {
+            let gap = if x % 4 == 1 { height - 1 } else { 0 };
+            for y in (0..height).filter(|&y| y != gap) {
+                bitmap.set_pixel(x, y, Pixel::from_rgb_tuple(WHITE));
+            }
+        }
+        let filled = bitmap.flood_fill(0, 0, &(255, 0, 0), Connectivity::Four, 0);
+        assert_eq!(filled, width * height - width / 2 * (height - 1));
+        assert_eq!(color_at(&bitmap, width - 2, height / 2), (255, 0, 0));
+    }
+}
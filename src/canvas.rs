@@ -0,0 +1,584 @@
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+use crate::{Bitmap, Pixel};
+use crate::bitmap::Rect;
+use crate::line::{LineCap, Stroke, clip_segment, rasterize_line_aa, rasterize_thick_line, walk_line};
+use crate::path::{Path, flatten_cubic, flatten_quadratic};
+use crate::polygon::{FillRule, pixel_range, rasterize_polygon};
+use crate::text::{Font, TextStyle, draw_text_clipped};
+use crate::view::RenderTarget;
+
+type Rgb = (u8, u8, u8);
+type Point = (f32, f32);
+
+/// Maximal distance in pixels between a curve and the polyline approximating it.
+const FLATNESS: f32 = 0.25;
+
+
+/// Rectangle in canvas coordinates, unlike `Rect` its edges can lie anywhere between pixels.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct RectF {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+
+impl RectF {
+    pub const fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self { x, y, width, height }
+    }
+
+    /// Corners in clockwise order on screen, starting with the top left one.
+    fn corners(&self) -> [Point; 4] {
+        let (right, bottom) = (self.x + self.width, self.y + self.height);
+        [(self.x, self.y), (right, self.y), (right, bottom), (self.x, bottom)]
+    }
+}
+
+
+/// Ellipse with semi-axes `radius_x` and `radius_y` rotated clockwise by `rotation`, or the part of its outline
+/// from `start_angle` to `end_angle`.
+///
+/// Angles are parametric, point at angle `t` is `(radius_x * cos(t), radius_y * sin(t))` before rotation,
+/// which for circles is the same as the polar angle.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct EllipseArc {
+    pub center: Point,
+    pub radius_x: f32,
+    pub radius_y: f32,
+    pub rotation: f32,
+    pub start_angle: f32,
+    pub end_angle: f32,
+}
+
+
+impl EllipseArc {
+    /// Creates the whole ellipse.
+    pub fn new(center: Point, radius_x: f32, radius_y: f32, rotation: f32) -> Self {
+        Self { center, radius_x, radius_y, rotation, start_angle: 0.0, end_angle: TAU }
+    }
+
+    pub fn with_angles(self, start_angle: f32, end_angle: f32) -> Self {
+        Self { start_angle, end_angle, ..self }
+    }
+}
+
+
+/// Coverage of pixels inside a rectangular area.
+///
+/// Used to blend pixels shared by several parts of a shape, like segments of a polyline, only once.
+struct CoverageMask {
+    area: Rect,
+    coverage: Vec<f32>,
+}
+
+
+impl CoverageMask {
+    fn new(area: Rect) -> Self {
+        Self { area, coverage: vec![0.0; (area.width * area.height) as usize] }
+    }
+
+    fn add(&mut self, x: i32, y: i32, coverage: f32) {
+        if self.area.contains(x, y) {
+            let index = ((y - self.area.y) * self.area.width + (x - self.area.x)) as usize;
+            self.coverage[index] = self.coverage[index].max(coverage);
+        }
+    }
+}
+
+
+/// 2D drawing surface over a Bitmap or any other render target, like a view of a part of a Bitmap.
+///
+/// Coordinates are in pixels with pixel centers at half integer coordinates, angles are in radians
+/// measured from the positive x axis towards the positive y axis, which is clockwise on screen.
+/// Everything is clipped to the clipping rectangle, which never extends past the bitmap.
+pub struct Canvas<'a, T: RenderTarget = Bitmap> {
+    bitmap: &'a mut T,
+    clip: Rect,
+}
+
+
+impl<'a, T: RenderTarget> Canvas<'a, T> {
+    pub fn new(bitmap: &'a mut T) -> Self {
+        let clip = bitmap.rect();
+        Self { bitmap, clip }
+    }
+
+    pub fn bitmap(&mut self) -> &mut T {
+        self.bitmap
+    }
+
+    pub fn clip(&self) -> Rect {
+        self.clip
+    }
+
+    /// Restricts drawing to the rectangle, the part of it outside of the bitmap is ignored.
+    pub fn set_clip(&mut self, clip: Rect) {
+        self.clip = clip.intersection(&self.bitmap.rect());
+    }
+
+    /// Allows drawing on the whole bitmap again.
+    pub fn reset_clip(&mut self) {
+        self.clip = self.bitmap.rect();
+    }
+
+    /// Sets single pixel.
+    pub fn point(&mut self, x: i32, y: i32, color: &Rgb) {
+        if self.clip.contains(x, y) {
+            self.bitmap.set_pixel(x as usize, y as usize, Pixel::from_rgb_tuple(*color));
+        }
+    }
+
+    /// Draws line between points specified.
+    pub fn line(&mut self, x0: f32, y0: f32, x1: f32, y1: f32, stroke: &Stroke, color: &Rgb) {
+        self.stroke_path(&[(x0, y0), (x1, y1)], false, stroke, color);
+    }
+
+    /// Draws connected line segments, stroke's cap is applied only at the ends and segments are joined with round joins.
+    pub fn polyline(&mut self, points: &[Point], stroke: &Stroke, color: &Rgb) {
+        self.stroke_path(points, false, stroke, color);
+    }
+
+    /// Draws outline of a closed polygon.
+    pub fn polygon(&mut self, points: &[Point], stroke: &Stroke, color: &Rgb) {
+        self.stroke_path(points, true, stroke, color);
+    }
+
+    /// Fills closed polygon using even-odd rule.
+    pub fn fill_polygon(&mut self, points: &[Point], color: &Rgb) {
+        self.fill_points(points, color);
+    }
+
+    /// Fills polygon made of several closed contours, which can intersect and form holes, using given fill rule.
+    pub fn fill_contours(&mut self, contours: &[&[Point]], rule: FillRule, color: &Rgb) {
+        let (bitmap, clip) = (&mut *self.bitmap, self.clip);
+        let pixel = Pixel::from_rgb_tuple(*color);
+        rasterize_polygon(contours, rule, &clip, |y, range| {
+            for x in range {
+                bitmap.set_pixel(x as usize, y as usize, pixel);
+            }
+        });
+    }
+
+    pub fn rect(&mut self, rect: &RectF, stroke: &Stroke, color: &Rgb) {
+        self.polygon(&rect.corners(), stroke, color);
+    }
+
+    /// Fills every pixel whose center lies inside the rectangle.
+    pub fn fill_rect(&mut self, rect: &RectF, color: &Rgb) {
+        for row in pixel_range(rect.y, rect.y + rect.height, self.clip.y, self.clip.bottom()) {
+            self.span(row, rect.x, rect.x + rect.width, color);
+        }
+    }
+
+    /// Draws outline of a rectangle with corners rounded with given radius.
+    pub fn rounded_rect(&mut self, rect: &RectF, radius: f32, stroke: &Stroke, color: &Rgb) {
+        self.polygon(&rounded_rect_points(rect, radius), stroke, color);
+    }
+
+    pub fn fill_rounded_rect(&mut self, rect: &RectF, radius: f32, color: &Rgb) {
+        self.fill_points(&rounded_rect_points(rect, radius), color);
+    }
+
+    pub fn circle(&mut self, center: Point, radius: f32, stroke: &Stroke, color: &Rgb) {
+        self.polygon(&arc_points(center, radius, 0.0, TAU), stroke, color);
+    }
+
+    pub fn fill_circle(&mut self, center: Point, radius: f32, color: &Rgb) {
+        self.fill_points(&arc_points(center, radius, 0.0, TAU), color);
+    }
+
+    /// Draws part of a circle outline from `start_angle` to `end_angle`.
+    pub fn arc(&mut self, center: Point, radius: f32, start_angle: f32, end_angle: f32, stroke: &Stroke, color: &Rgb) {
+        self.polyline(&arc_points(center, radius, start_angle, end_angle), stroke, color);
+    }
+
+    /// Draws outline of a pie slice, an arc connected with the center of the circle.
+    pub fn pie(&mut self, center: Point, radius: f32, start_angle: f32, end_angle: f32, stroke: &Stroke, color: &Rgb) {
+        self.polygon(&pie_points(center, radius, start_angle, end_angle), stroke, color);
+    }
+
+    pub fn fill_pie(&mut self, center: Point, radius: f32, start_angle: f32, end_angle: f32, color: &Rgb) {
+        self.fill_points(&pie_points(center, radius, start_angle, end_angle), color);
+    }
+
+    /// Draws outline of an ellipse with semi-axes `radius_x` and `radius_y` rotated clockwise by `rotation`.
+    pub fn ellipse(&mut self, center: Point, radius_x: f32, radius_y: f32, rotation: f32, stroke: &Stroke, color: &Rgb) {
+        self.polygon(&ellipse_arc_points(&EllipseArc::new(center, radius_x, radius_y, rotation)), stroke, color);
+    }
+
+    pub fn fill_ellipse(&mut self, center: Point, radius_x: f32, radius_y: f32, rotation: f32, color: &Rgb) {
+        self.fill_points(&ellipse_arc_points(&EllipseArc::new(center, radius_x, radius_y, rotation)), color);
+    }
+
+    /// Draws part of an ellipse outline.
+    pub fn elliptical_arc(&mut self, arc: &EllipseArc, stroke: &Stroke, color: &Rgb) {
+        self.polyline(&ellipse_arc_points(arc), stroke, color);
+    }
+
+    /// Draws quadratic Bézier curve from `start` to `end` with one control point.
+    pub fn quadratic_bezier(&mut self, start: Point, control: Point, end: Point, stroke: &Stroke, color: &Rgb) {
+        let mut points = vec![start];
+        flatten_quadratic(start, control, end, FLATNESS, &mut points);
+        self.polyline(&points, stroke, color);
+    }
+
+    /// Draws cubic Bézier curve from `start` to `end` with two control points.
+    pub fn cubic_bezier(&mut self, start: Point, control1: Point, control2: Point, end: Point, stroke: &Stroke, color: &Rgb) {
+        let mut points = vec![start];
+        flatten_cubic(start, control1, control2, end, FLATNESS, &mut points);
+        self.polyline(&points, stroke, color);
+    }
+
+    /// Draws outline of every contour of the path.
+    pub fn path(&mut self, path: &Path, stroke: &Stroke, color: &Rgb) {
+        for contour in path.flatten(FLATNESS) {
+            self.stroke_path(&contour.points, contour.closed, stroke, color);
+        }
+    }
+
+    /// Fills the path using given fill rule, open contours are treated as closed.
+    pub fn fill_path(&mut self, path: &Path, rule: FillRule, color: &Rgb) {
+        let contours = path.flatten(FLATNESS);
+        let contours: Vec<&[Point]> = contours.iter().map(|contour| contour.points.as_slice()).collect();
+        self.fill_contours(&contours, rule, color);
+    }
+
+    /// Draws text in color of the style with its first line starting at row `y`, see `text::rasterize_text` for the layout.
+    pub fn text(&mut self, font: &Font, text: &str, x: i32, y: i32, style: &TextStyle) {
+        draw_text_clipped(self.bitmap, font, text, x, y, style, &self.clip);
+    }
+
+    /// Sets pixels of the row whose centers lie between `x0` and `x1`.
+    fn span(&mut self, y: i32, x0: f32, x1: f32, color: &Rgb) {
+        let pixel = Pixel::from_rgb_tuple(*color);
+        for x in pixel_range(x0, x1, self.clip.x, self.clip.right()) {
+            self.bitmap.set_pixel(x as usize, y as usize, pixel);
+        }
+    }
+
+    /// Blends color into the pixels covered by the mask.
+    fn blend_mask(&mut self, mask: &CoverageMask, color: &Rgb) {
+        for y in 0..mask.area.height {
+            for x in 0..mask.area.width {
+                let coverage = mask.coverage[(y * mask.area.width + x) as usize];
+                if coverage > 0.0 {
+                    let alfa = (coverage.min(1.0) * 255.0).round() as u8;
+                    let pixel = Pixel::from_rgb_tuple(*color).with_alfa(alfa);
+                    self.bitmap.blend_pixel((mask.area.x + x) as usize, (mask.area.y + y) as usize, pixel);
+                }
+            }
+        }
+    }
+
+    fn stroke_path(&mut self, points: &[Point], closed: bool, stroke: &Stroke, color: &Rgb) {
+        if points.is_empty() {
+            return;
+        }
+        let mut points = points.to_vec();
+        if closed && points.len() > 2 {
+            points.push(points[0]);
+        }
+
+        // Thin aliased strokes are drawn with Bresenham's algorithm, which gives the classic one pixel look.
+        if stroke.width <= 1.0 && !stroke.antialiased {
+            let (bitmap, clip) = (&mut *self.bitmap, self.clip);
+            let pixel = Pixel::from_rgb_tuple(*color);
+            // One pixel margin, which is skipped while drawing, keeps the clipped segments on the same pixels.
+            let (min, max) = ((clip.x as f64 - 1.0, clip.y as f64 - 1.0), (clip.right() as f64 + 1.0, clip.bottom() as f64 + 1.0));
+            for segment in points.windows(2) {
+                let [(x0, y0), (x1, y1)] = [segment[0], segment[1]].map(|(x, y)| (x as f64, y as f64));
+                let (t0, t1) = match clip_segment((x0, y0), (x1, y1), min, max) {
+                    Some(range) => range,
+                    None => continue,
+                };
+                let at = |t: f64| ((x0 + (x1 - x0) * t).floor() as i32, (y0 + (y1 - y0) * t).floor() as i32);
+                let ((x0, y0), (x1, y1)) = (at(t0), at(t1));
+                walk_line(x0, y0, x1, y1, &clip, |x, y, _| {
+                    bitmap.set_pixel(x as usize, y as usize, pixel);
+                });
+            }
+            return;
+        }
+
+        let half_width = stroke.width / 2.0;
+        if !closed && stroke.cap == LineCap::Square && points.len() > 1 {
+            let last = points.len() - 1;
+            points[0] = extend(points[1], points[0], half_width);
+            points[last] = extend(points[last - 1], points[last], half_width);
+        }
+
+        let area = bounding_rect(&points, half_width + 2.0).intersection(&self.clip);
+        if area.is_empty() {
+            return;
+        }
+        let mut mask = CoverageMask::new(area);
+        let thin = stroke.width <= 1.0;
+        let butt = Stroke { cap: LineCap::Butt, ..*stroke };
+        let round = Stroke { cap: LineCap::Round, ..*stroke };
+
+        for segment in points.windows(2) {
+            let [(x0, y0), (x1, y1)] = [segment[0], segment[1]];
+            if thin {
+                rasterize_line_aa(x0, y0, x1, y1, &area, |x, y, coverage| mask.add(x, y, coverage));
+            } else {
+                rasterize_thick_line(x0, y0, x1, y1, &butt, &area, |x, y, coverage| mask.add(x, y, coverage));
+            }
+        }
+
+        if !thin {
+            // Round joins between segments and round caps at the ends of open paths.
+            let round_ends = !closed && stroke.cap == LineCap::Round;
+            for (index, &(x, y)) in points.iter().enumerate() {
+                let is_end = !closed && (index == 0 || index == points.len() - 1);
+                if !is_end || round_ends {
+                    rasterize_thick_line(x, y, x, y, &round, &area, |x, y, coverage| mask.add(x, y, coverage));
+                }
+            }
+        }
+
+        self.blend_mask(&mask, color);
+    }
+
+    /// Fills closed polygon using even-odd rule, sampling at pixel centers.
+    fn fill_points(&mut self, points: &[Point], color: &Rgb) {
+        self.fill_contours(&[points], FillRule::EvenOdd, color);
+    }
+}
+
+
+/// Returns smallest rectangle of pixels containing all of the points, grown by margin.
+fn bounding_rect(points: &[Point], margin: f32) -> Rect {
+    let (mut x_min, mut y_min) = (f32::INFINITY, f32::INFINITY);
+    let (mut x_max, mut y_max) = (f32::NEG_INFINITY, f32::NEG_INFINITY);
+    for &(x, y) in points {
+        x_min = x_min.min(x);
+        y_min = y_min.min(y);
+        x_max = x_max.max(x);
+        y_max = y_max.max(y);
+    }
+    // NOTE: float to int casts saturate, so points far outside of the bitmap do not overflow.
+    let (left, top) = ((x_min - margin).floor() as i32, (y_min - margin).floor() as i32);
+    let (right, bottom) = ((x_max + margin).ceil() as i32, (y_max + margin).ceil() as i32);
+    Rect::new(left, top, right.saturating_sub(left), bottom.saturating_sub(top))
+}
+
+
+/// Moves `to` further away from `from` by given distance.
+fn extend(from: Point, to: Point, distance: f32) -> Point {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length = dx.hypot(dy);
+    if length == 0.0 {
+        return to;
+    }
+    (to.0 + dx / length * distance, to.1 + dy / length * distance)
+}
+
+
+/// Number of segments needed to approximate arc of given radius and angle within `FLATNESS`.
+pub fn arc_segments(radius: f32, sweep: f32) -> usize {
+    let step = if radius > FLATNESS { 2.0 * (1.0 - FLATNESS / radius).acos() } else { FRAC_PI_2 };
+    ((sweep.abs() / step).ceil() as usize).clamp(1, 1024)
+}
+
+
+/// Approximates arc of a circle with a polyline, both end points included.
+pub fn arc_points(center: Point, radius: f32, start_angle: f32, end_angle: f32) -> Vec<Point> {
+    ellipse_arc_points(&EllipseArc::new(center, radius, radius, 0.0).with_angles(start_angle, end_angle))
+}
+
+
+/// Approximates arc of an ellipse with a polyline, both end points included.
+pub fn ellipse_arc_points(arc: &EllipseArc) -> Vec<Point> {
+    let sweep = (arc.end_angle - arc.start_angle).clamp(-TAU, TAU);
+    let segments = arc_segments(arc.radius_x.abs().max(arc.radius_y.abs()), sweep);
+    let (sin, cos) = arc.rotation.sin_cos();
+    (0..=segments)
+        .map(|index| {
+            let angle = arc.start_angle + sweep * index as f32 / segments as f32;
+            let (x, y) = (arc.radius_x * angle.cos(), arc.radius_y * angle.sin());
+            (arc.center.0 + x * cos - y * sin, arc.center.1 + x * sin + y * cos)
+        })
+        .collect()
+}
+
+
+fn pie_points(center: Point, radius: f32, start_angle: f32, end_angle: f32) -> Vec<Point> {
+    let mut points = arc_points(center, radius, start_angle, end_angle);
+    if (end_angle - start_angle).abs() < TAU {
+        points.push(center);
+    }
+    points
+}
+
+
+fn rounded_rect_points(rect: &RectF, radius: f32) -> Vec<Point> {
+    let radius = radius.clamp(0.0, rect.width.abs().min(rect.height.abs()) / 2.0);
+    let (left, top) = (rect.x + radius, rect.y + radius);
+    let (right, bottom) = (rect.x + rect.width - radius, rect.y + rect.height - radius);
+    [
+        (left, top, PI),
+        (right, top, PI + FRAC_PI_2),
+        (right, bottom, 0.0),
+        (left, bottom, FRAC_PI_2),
+    ]
+        .into_iter()
+        .flat_map(|(center_x, center_y, start)| arc_points((center_x, center_y), radius, start, start + FRAC_PI_2))
+        .collect()
+}
+
+
+/// Test suite for the canvas shapes.
+#[cfg(test)]
+mod test_canvas {
+    use super::*;
+
+    const WHITE: Rgb = (255, 255, 255);
+
+    /// Returns coordinates of every pixel which differs from the background.
+    fn drawn(bitmap: &Bitmap) -> Vec<(i32, i32)> {
+        bitmap.enumerate_pixels()
+            .filter(|(_, _, pixel)| **pixel != Pixel::default())
+            .map(|(x, y, _)| (x as i32, y as i32))
+            .collect()
+    }
+
+    #[test]
+    fn clipping() {
+        let clip = Rect::new(2, 3, 6, 5);
+        let shapes: [&dyn Fn(&mut Canvas); 5] = [
+            &|canvas| canvas.fill_rect(&RectF::new(-5.0, -5.0, 30.0, 30.0), &WHITE),
+            &|canvas| canvas.fill_circle((5.0, 5.0), 20.0, &WHITE),
+            &|canvas| canvas.rect(&RectF::new(2.5, 3.5, 5.0, 4.0), &Stroke::new(3.0, LineCap::Butt, true), &WHITE),
+            &|canvas| canvas.polygon(&[(1.0, 5.5), (5.0, 1.5), (9.0, 5.5), (5.0, 9.5)], &Stroke::default(), &WHITE),
+            &|canvas| canvas.polygon(&[(1.0, 5.5), (5.0, 1.5), (9.0, 5.5), (5.0, 9.5)], &Stroke::new(1.0, LineCap::Butt, true), &WHITE),
+        ];
+        for (index, shape) in shapes.iter().enumerate() {
+            let mut bitmap = Bitmap::new(10, 10);
+            let mut canvas = Canvas::new(&mut bitmap);
+            canvas.set_clip(clip);
+            shape(&mut canvas);
+
+            let drawn = drawn(&bitmap);
+            assert!(drawn.iter().all(|(x, y)| clip.contains(*x, *y)), "{}", index);
+            // Every edge of the clipping rectangle is reached.
+            assert!(drawn.iter().any(|(x, _)| *x == clip.x), "{}", index);
+            assert!(drawn.iter().any(|(x, _)| *x == clip.right() - 1), "{}", index);
+            assert!(drawn.iter().any(|(_, y)| *y == clip.y), "{}", index);
+            assert!(drawn.iter().any(|(_, y)| *y == clip.bottom() - 1), "{}", index);
+        }
+
+        // Clipping rectangle never extends past the bitmap.
+        let mut bitmap = Bitmap::new(10, 10);
+        let mut canvas = Canvas::new(&mut bitmap);
+        canvas.set_clip(Rect::new(-5, 5, 20, 20));
+        assert_eq!(canvas.clip(), Rect::new(0, 5, 10, 5));
+        canvas.reset_clip();
+        assert_eq!(canvas.clip(), Rect::new(0, 0, 10, 10));
+
+        // Far end points are clipped before rounding, the line keeps its direction.
+        canvas.line(-1e10, 4.0, 1e10, 4.0, &Stroke::default(), &WHITE);
+        canvas.line(-1e10, -1e10 + 0.5, 1e10, 1e10 + 0.5, &Stroke::default(), &WHITE);
+        let drawn = drawn(&bitmap);
+        assert_eq!(drawn.len(), 10 + 9);
+        assert!(drawn.iter().all(|(x, y)| *y == 4 || x == y), "{:?}", drawn);
+    }
+
+    #[test]
+    fn filled_and_outlined() {
+        // Filling covers pixels whose centers lie inside, here columns and rows 2 to 5.
+        let mut bitmap = Bitmap::new(10, 10);
+        Canvas::new(&mut bitmap).fill_rect(&RectF::new(2.0, 2.0, 4.0, 4.0), &WHITE);
+        let filled = drawn(&bitmap);
+        assert_eq!(filled.len(), 16);
+        assert!(filled.iter().all(|(x, y)| (2..6).contains(x) && (2..6).contains(y)));
+
+        // Thin outline goes through pixels containing the corners, so it also covers column and row 6, but not the inside.
+        let mut bitmap = Bitmap::new(10, 10);
+        Canvas::new(&mut bitmap).rect(&RectF::new(2.0, 2.0, 4.0, 4.0), &Stroke::default(), &WHITE);
+        let outlined = drawn(&bitmap);
+        assert_eq!(outlined.len(), 16);
+        assert!(outlined.iter().all(|(x, y)| [2, 6].contains(x) || [2, 6].contains(y)));
+
+        // Filled circle covers about its area, outline stays close to its radius.
+        let mut bitmap = Bitmap::new(20, 20);
+        Canvas::new(&mut bitmap).fill_circle((10.0, 10.0), 6.0, &WHITE);
+        let area = drawn(&bitmap).len() as f32;
+        assert!((area - PI * 36.0).abs() < 5.0, "{}", area);
+
+        let mut bitmap = Bitmap::new(20, 20);
+        Canvas::new(&mut bitmap).circle((10.0, 10.0), 6.0, &Stroke::default(), &WHITE);
+        for (x, y) in drawn(&bitmap) {
+            let distance = (x as f32 + 0.5 - 10.0).hypot(y as f32 + 0.5 - 10.0);
+            assert!((distance - 6.0).abs() < 1.5, "{} {}", x, y);
+        }
+    }
+
+    #[test]
+    fn arcs_across_zero() {
+        // Arc crossing the positive x axis starts and ends where expected, in either direction.
+        let points = arc_points((0.0, 0.0), 10.0, -FRAC_PI_2, FRAC_PI_2);
+        let (first, last) = (points[0], points[points.len() - 1]);
+        assert!(first.0.abs() < 1e-4 && (first.1 + 10.0).abs() < 1e-4);
+        assert!(last.0.abs() < 1e-4 && (last.1 - 10.0).abs() < 1e-4);
+        assert!(points.iter().all(|(x, _)| *x >= -1e-4));
+
+        let reversed = arc_points((0.0, 0.0), 10.0, FRAC_PI_2, -FRAC_PI_2);
+        assert_eq!(reversed.len(), points.len());
+        assert!(reversed.iter().rev().zip(&points).all(|(a, b)| (a.0 - b.0).abs() < 1e-4 && (a.1 - b.1).abs() < 1e-4));
+
+        // Angles past the full turn draw the same slice.
+        let fill = |start: f32, end: f32| {
+            let mut bitmap = Bitmap::new(20, 20);
+            Canvas::new(&mut bitmap).fill_pie((10.0, 10.0), 8.0, start, end, &WHITE);
+            drawn(&bitmap)
+        };
+        let right = fill(-FRAC_PI_2, FRAC_PI_2);
+        assert!(right.iter().all(|(x, _)| *x >= 10));
+        assert!(right.contains(&(16, 10)) && !right.contains(&(3, 10)));
+        assert_eq!(fill(3.0 * FRAC_PI_2, 5.0 * FRAC_PI_2), right);
+
+        // Full turn is a whole circle without the spoke to the center.
+        assert_eq!(pie_points((0.0, 0.0), 10.0, 0.0, TAU).len(), arc_points((0.0, 0.0), 10.0, 0.0, TAU).len());
+        let mut bitmap = Bitmap::new(20, 20);
+        Canvas::new(&mut bitmap).pie((10.0, 10.0), 8.0, 0.0, TAU, &Stroke::default(), &WHITE);
+        assert!(!drawn(&bitmap).contains(&(10, 10)));
+    }
+
+    #[test]
+    fn rotated_ellipses() {
+        // Bounding box of an ellipse rotated by 45 degrees, flattening keeps the points on the ellipse.
+        let points = ellipse_arc_points(&EllipseArc::new((0.0, 0.0), 10.0, 4.0, FRAC_PI_2 / 2.0));
+        let half_size = (10.0f32.powi(2) / 2.0 + 4.0f32.powi(2) / 2.0).sqrt();
+        for extreme in [
+            points.iter().map(|(x, _)| *x).fold(f32::NEG_INFINITY, f32::max),
+            -points.iter().map(|(x, _)| *x).fold(f32::INFINITY, f32::min),
+            points.iter().map(|(_, y)| *y).fold(f32::NEG_INFINITY, f32::max),
+            -points.iter().map(|(_, y)| *y).fold(f32::INFINITY, f32::min),
+        ] {
+            assert!(extreme <= half_size + 1e-4 && extreme >= half_size - FLATNESS, "{} {}", extreme, half_size);
+        }
+
+        // Arc of an ellipse turned on its side starts at the end of its rotated major axis and ends at its minor one.
+        let points = ellipse_arc_points(&EllipseArc::new((0.0, 0.0), 10.0, 4.0, FRAC_PI_2).with_angles(0.0, FRAC_PI_2));
+        let (first, last) = (points[0], points[points.len() - 1]);
+        assert!(first.0.abs() < 1e-4 && (first.1 - 10.0).abs() < 1e-4, "{:?}", first);
+        assert!((last.0 + 4.0).abs() < 1e-4 && last.1.abs() < 1e-4, "{:?}", last);
+
+        let mut bitmap = Bitmap::new(20, 20);
+        Canvas::new(&mut bitmap).fill_ellipse((10.0, 10.0), 8.0, 3.0, FRAC_PI_2, &WHITE);
+        let filled = drawn(&bitmap);
+        assert!(filled.iter().all(|(x, y)| (7..13).contains(x) && (2..18).contains(y)));
+        assert!(filled.contains(&(10, 3)) && filled.contains(&(10, 16)) && !filled.contains(&(4, 10)));
+
+        // Lower half of the outline, from the right end of the major axis to the left one.
+        let mut bitmap = Bitmap::new(20, 20);
+        let arc = EllipseArc::new((10.0, 10.0), 8.0, 4.0, 0.0).with_angles(0.0, PI);
+        Canvas::new(&mut bitmap).elliptical_arc(&arc, &Stroke::default(), &WHITE);
+        let outline = drawn(&bitmap);
+        assert!(outline.iter().all(|(_, y)| *y >= 10));
+        assert!(outline.contains(&(18, 10)) && outline.contains(&(2, 10)) && outline.contains(&(10, 13)));
+    }
+}
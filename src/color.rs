@@ -0,0 +1,214 @@
+use std::ops::{Add, AddAssign, Mul};
+use std::sync::OnceLock;
+
+use nalgebra as na;
+
+use crate::Pixel;
+
+type V3 = na::Vector3<f32>;
+
+/// Number of entries of the table encoding linear values to sRGB bytes.
+const ENCODE_TABLE_SIZE: usize = 4096;
+
+
+/// Decodes sRGB encoded value in range 0.0 - 1.0 to linear light intensity.
+pub fn srgb_decode(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+
+/// Encodes linear light intensity in range 0.0 - 1.0 with the sRGB transfer function.
+pub fn srgb_encode(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+
+/// Converts 8 bit sRGB channel to linear intensity in range 0.0 - 1.0 using a lookup table.
+pub fn srgb_to_linear(value: u8) -> f32 {
+    static TABLE: OnceLock<[f32; 256]> = OnceLock::new();
+    TABLE.get_or_init(|| std::array::from_fn(|index| srgb_decode(index as f32 / 255.0)))[value as usize]
+}
+
+
+/// Converts linear intensity to 8 bit sRGB channel using a lookup table, values outside of 0.0 - 1.0 are clamped.
+pub fn linear_to_srgb(value: f32) -> u8 {
+    static TABLE: OnceLock<Vec<u8>> = OnceLock::new();
+    let table = TABLE.get_or_init(|| {
+        (0..ENCODE_TABLE_SIZE)
+            .map(|index| (srgb_encode(index as f32 / (ENCODE_TABLE_SIZE - 1) as f32) * 255.0).round() as u8)
+            .collect()
+    });
+    // NaN is clamped to 0 by the saturating cast.
+    table[(value.clamp(0.0, 1.0) * (ENCODE_TABLE_SIZE - 1) as f32).round() as usize]
+}
+
+
+/// Color with linear float channels and straight alfa.
+///
+/// Light adds up linearly only in this space, so lighting, blending and interpolation of colors
+/// should be done on linear colors, which are encoded to sRGB only when stored in 8 bit pixels.
+/// Color channels are not limited to 0.0 - 1.0, values above 1.0 represent light brighter than the display can show.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct LinearColor {
+    pub red: f32,
+    pub green: f32,
+    pub blue: f32,
+    pub alfa: f32,
+}
+
+
+impl LinearColor {
+    /// Creates opaque color.
+    pub const fn new(red: f32, green: f32, blue: f32) -> Self {
+        Self { red, green, blue, alfa: 1.0 }
+    }
+
+    pub const fn with_alfa(self, alfa: f32) -> Self {
+        Self { alfa, ..self }
+    }
+
+    /// Decodes 8 bit sRGB pixel.
+    pub fn from_srgb(pixel: Pixel) -> Self {
+        Self::new(srgb_to_linear(pixel.red), srgb_to_linear(pixel.green), srgb_to_linear(pixel.blue)).with_alfa(pixel.alfa as f32 / 255.0)
+    }
+
+    /// Encodes the color as 8 bit sRGB pixel, clamping values outside of the displayable range.
+    pub fn to_srgb(self) -> Pixel {
+        Pixel::new(linear_to_srgb(self.red), linear_to_srgb(self.green), linear_to_srgb(self.blue))
+            .with_alfa((self.alfa.clamp(0.0, 1.0) * 255.0).round() as u8)
+    }
+
+    /// Creates opaque color from a vector of linear red, green and blue, as used by the lighting.
+    pub fn from_vector(color: &V3) -> Self {
+        Self::new(color.x, color.y, color.z)
+    }
+
+    pub fn to_vector(self) -> V3 {
+        V3::new(self.red, self.green, self.blue)
+    }
+
+    pub const fn from_rgba(rgba: [f32; 4]) -> Self {
+        let [red, green, blue, alfa] = rgba;
+        Self { red, green, blue, alfa }
+    }
+
+    pub const fn to_rgba(self) -> [f32; 4] {
+        [self.red, self.green, self.blue, self.alfa]
+    }
+
+    /// Relative luminance of the color with Rec. 709 weights.
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.red + 0.7152 * self.green + 0.0722 * self.blue
+    }
+
+    /// Linear interpolation between the colors, `t` of 0.0 gives this color and 1.0 the other one.
+    pub fn lerp(&self, other: &LinearColor, t: f32) -> LinearColor {
+        *self * (1.0 - t) + *other * t
+    }
+
+    /// Composites the color over `background` using its alfa as opacity (Porter-Duff source over).
+    pub fn over(&self, background: &LinearColor) -> LinearColor {
+        let alfa = self.alfa + background.alfa * (1.0 - self.alfa);
+        if alfa <= 0.0 {
+            return LinearColor::default();
+        }
+        let mix = |source: f32, destination: f32| (source * self.alfa + destination * background.alfa * (1.0 - self.alfa)) / alfa;
+        LinearColor {
+            red: mix(self.red, background.red),
+            green: mix(self.green, background.green),
+            blue: mix(self.blue, background.blue),
+            alfa,
+        }
+    }
+}
+
+
+impl Add for LinearColor {
+    type Output = LinearColor;
+
+    fn add(self, other: LinearColor) -> LinearColor {
+        LinearColor {
+            red: self.red + other.red,
+            green: self.green + other.green,
+            blue: self.blue + other.blue,
+            alfa: self.alfa + other.alfa,
+        }
+    }
+}
+
+
+impl AddAssign for LinearColor {
+    fn add_assign(&mut self, other: LinearColor) {
+        *self = *self + other;
+    }
+}
+
+
+/// Scales all channels including alfa.
+impl Mul<f32> for LinearColor {
+    type Output = LinearColor;
+
+    fn mul(self, factor: f32) -> LinearColor {
+        LinearColor { red: self.red * factor, green: self.green * factor, blue: self.blue * factor, alfa: self.alfa * factor }
+    }
+}
+
+
+/// Multiplies channels, like light reflected from a surface of the other color.
+impl Mul for LinearColor {
+    type Output = LinearColor;
+
+    fn mul(self, other: LinearColor) -> LinearColor {
+        LinearColor {
+            red: self.red * other.red,
+            green: self.green * other.green,
+            blue: self.blue * other.blue,
+            alfa: self.alfa * other.alfa,
+        }
+    }
+}
+
+
+/// Test suite for color space conversions.
+#[cfg(test)]
+mod test_color {
+    use super::*;
+
+    #[test]
+    fn lookup_tables_round_trip() {
+        for value in 0..=255 {
+            assert_eq!(linear_to_srgb(srgb_to_linear(value)), value);
+            assert!((srgb_to_linear(value) - srgb_decode(value as f32 / 255.0)).abs() < 1e-6);
+        }
+        assert_eq!(srgb_to_linear(0), 0.0);
+        assert_eq!(srgb_to_linear(255), 1.0);
+        assert_eq!(linear_to_srgb(-1.0), 0);
+        assert_eq!(linear_to_srgb(4.0), 255);
+        assert_eq!(linear_to_srgb(f32::NAN), 0);
+    }
+
+    #[test]
+    fn middle_gray() {
+        // Half of the light of white is much brighter than half of the sRGB code values.
+        assert_eq!(LinearColor::new(0.5, 0.5, 0.5).to_srgb(), Pixel::new(188, 188, 188));
+        assert!((LinearColor::from_srgb(Pixel::new(128, 128, 128)).red - 0.2158).abs() < 1e-3);
+        let gray = LinearColor::new(0.0, 0.0, 0.0).lerp(&LinearColor::new(1.0, 1.0, 1.0), 0.5);
+        assert_eq!(gray, LinearColor::new(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn linear_over() {
+        let half_white = LinearColor::new(1.0, 1.0, 1.0).with_alfa(0.5);
+        assert_eq!(half_white.over(&LinearColor::new(0.0, 0.0, 0.0)), LinearColor::new(0.5, 0.5, 0.5));
+        assert_eq!(half_white.over(&LinearColor::default()), half_white);
+        assert_eq!(LinearColor::default().over(&LinearColor::default()), LinearColor::default());
+    }
+}
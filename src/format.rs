@@ -0,0 +1,419 @@
+use std::fmt::Debug;
+
+use crate::Pixel;
+use crate::bitmap::Rect;
+use crate::color::{LinearColor, linear_to_srgb, srgb_to_linear};
+use crate::view::{BitmapView, BitmapViewMut, RenderTarget};
+
+
+/// 32 bit pixel with blue, green, red and alfa bytes, the layout of Windows DIBs.
+pub type Bgra8 = Pixel;
+
+
+/// Memory layout of a single pixel of a render target, with its color conversions and blending.
+///
+/// Drawing functions produce 8 bit sRGB `Pixel` colors, every format converts from and to them.
+/// Conversions through `to_rgba` and `from_rgba` use linear float colors with straight alfa,
+/// where 1.0 is the full intensity of 8 bit channels. Blending is done in linear space.
+pub trait PixelFormat: Copy + Default + PartialEq + Debug + Send + Sync {
+    /// Size of the pixel in bytes as written by `write_bytes`.
+    const BYTES: usize;
+
+    fn from_pixel(pixel: Pixel) -> Self;
+    fn to_pixel(&self) -> Pixel;
+
+    /// Creates pixel from linear red, green, blue and alfa, values outside of the representable range are clamped.
+    fn from_rgba(rgba: [f32; 4]) -> Self;
+    fn to_rgba(&self) -> [f32; 4];
+
+    /// Composites the pixel over `background` using its alfa as opacity (Porter-Duff source over).
+    fn over(&self, background: &Self) -> Self;
+
+    /// Composites 8 bit color over `background` using its alfa as opacity.
+    ///
+    /// Formats without alfa channel override it, the alfa would be lost when converting the source first.
+    fn composite(source: &Pixel, background: &Self) -> Self {
+        Self::from_pixel(*source).over(background)
+    }
+
+    /// Appends the pixel to the buffer, multi byte values are little endian.
+    fn write_bytes(&self, bytes: &mut Vec<u8>);
+}
+
+
+impl PixelFormat for Pixel {
+    const BYTES: usize = 4;
+
+    fn from_pixel(pixel: Pixel) -> Self {
+        pixel
+    }
+
+    fn to_pixel(&self) -> Pixel {
+        *self
+    }
+
+    fn from_rgba(rgba: [f32; 4]) -> Self {
+        LinearColor::from_rgba(rgba).to_srgb()
+    }
+
+    fn to_rgba(&self) -> [f32; 4] {
+        LinearColor::from_srgb(*self).to_rgba()
+    }
+
+    fn over(&self, background: &Self) -> Self {
+        self.over_linear(background)
+    }
+
+    fn write_bytes(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&[self.blue, self.green, self.red, self.alfa]);
+    }
+}
+
+
+/// 32 bit pixel with red, green, blue and alfa bytes, the layout of most image formats and GPU textures.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Rgba8 {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    pub alfa: u8,
+}
+
+
+impl PixelFormat for Rgba8 {
+    const BYTES: usize = 4;
+
+    fn from_pixel(pixel: Pixel) -> Self {
+        Self { red: pixel.red, green: pixel.green, blue: pixel.blue, alfa: pixel.alfa }
+    }
+
+    fn to_pixel(&self) -> Pixel {
+        Pixel::new(self.red, self.green, self.blue).with_alfa(self.alfa)
+    }
+
+    fn from_rgba(rgba: [f32; 4]) -> Self {
+        Self::from_pixel(Pixel::from_rgba(rgba))
+    }
+
+    fn to_rgba(&self) -> [f32; 4] {
+        self.to_pixel().to_rgba()
+    }
+
+    fn over(&self, background: &Self) -> Self {
+        Self::from_pixel(self.to_pixel().over_linear(&background.to_pixel()))
+    }
+
+    fn write_bytes(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&[self.red, self.green, self.blue, self.alfa]);
+    }
+}
+
+
+/// 16 bit opaque pixel with 5 bits of red, 6 bits of green and 5 bits of blue, common on embedded displays.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Rgb565(pub u16);
+
+
+impl Rgb565 {
+    /// Creates pixel from 8 bit channels rounded to the closest representable values.
+    pub const fn new(red: u8, green: u8, blue: u8) -> Self {
+        Self((reduce(red, 31) << 11 | reduce(green, 63) << 5 | reduce(blue, 31)) as u16)
+    }
+
+    pub const fn red(&self) -> u8 {
+        expand(self.0 as u32 >> 11 & 31, 31)
+    }
+
+    pub const fn green(&self) -> u8 {
+        expand(self.0 as u32 >> 5 & 63, 63)
+    }
+
+    pub const fn blue(&self) -> u8 {
+        expand(self.0 as u32 & 31, 31)
+    }
+}
+
+
+/// Scales byte to a channel of `max` levels.
+const fn reduce(value: u8, max: u32) -> u32 {
+    (value as u32 * max + 127) / 255
+}
+
+
+/// Scales channel of `max` levels to a byte.
+const fn expand(value: u32, max: u32) -> u8 {
+    ((value * 255 + max / 2) / max) as u8
+}
+
+
+impl PixelFormat for Rgb565 {
+    const BYTES: usize = 2;
+
+    fn from_pixel(pixel: Pixel) -> Self {
+        Self::new(pixel.red, pixel.green, pixel.blue)
+    }
+
+    fn to_pixel(&self) -> Pixel {
+        Pixel::new(self.red(), self.green(), self.blue())
+    }
+
+    fn from_rgba(rgba: [f32; 4]) -> Self {
+        Self::from_pixel(Pixel::from_rgba(rgba))
+    }
+
+    fn to_rgba(&self) -> [f32; 4] {
+        self.to_pixel().to_rgba()
+    }
+
+    fn over(&self, _background: &Self) -> Self {
+        *self
+    }
+
+    fn composite(source: &Pixel, background: &Self) -> Self {
+        Self::from_pixel(source.over_linear(&background.to_pixel()))
+    }
+
+    fn write_bytes(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.0.to_le_bytes());
+    }
+}
+
+
+/// 8 bit opaque grayscale pixel.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Gray8(pub u8);
+
+
+impl PixelFormat for Gray8 {
+    const BYTES: usize = 1;
+
+    /// Converts color to its luma using Rec. 601 weights on sRGB encoded channels.
+    fn from_pixel(pixel: Pixel) -> Self {
+        let luma = 299 * pixel.red as u32 + 587 * pixel.green as u32 + 114 * pixel.blue as u32;
+        Self(((luma + 500) / 1000) as u8)
+    }
+
+    fn to_pixel(&self) -> Pixel {
+        Pixel::new(self.0, self.0, self.0)
+    }
+
+    fn from_rgba(rgba: [f32; 4]) -> Self {
+        Self::from_pixel(Pixel::from_rgba(rgba))
+    }
+
+    fn to_rgba(&self) -> [f32; 4] {
+        self.to_pixel().to_rgba()
+    }
+
+    fn over(&self, _background: &Self) -> Self {
+        *self
+    }
+
+    fn composite(source: &Pixel, background: &Self) -> Self {
+        let alfa = source.alfa as f32 / 255.0;
+        let luma = srgb_to_linear(Self::from_pixel(*source).0) * alfa + srgb_to_linear(background.0) * (1.0 - alfa);
+        Self(linear_to_srgb(luma))
+    }
+
+    fn write_bytes(&self, bytes: &mut Vec<u8>) {
+        bytes.push(self.0);
+    }
+}
+
+
+/// 128 bit pixel with linear float red, green, blue and alfa, for high dynamic range rendering.
+///
+/// Color channels are not limited to 0.0 - 1.0, values above 1.0 are clipped only when converting to 8 bit formats.
+pub type RgbaF32 = LinearColor;
+
+
+impl PixelFormat for RgbaF32 {
+    const BYTES: usize = 16;
+
+    fn from_pixel(pixel: Pixel) -> Self {
+        LinearColor::from_srgb(pixel)
+    }
+
+    fn to_pixel(&self) -> Pixel {
+        self.to_srgb()
+    }
+
+    fn from_rgba(rgba: [f32; 4]) -> Self {
+        let [red, green, blue, alfa] = rgba;
+        Self { red: red.max(0.0), green: green.max(0.0), blue: blue.max(0.0), alfa: alfa.clamp(0.0, 1.0) }
+    }
+
+    fn to_rgba(&self) -> [f32; 4] {
+        [self.red, self.green, self.blue, self.alfa]
+    }
+
+    fn over(&self, background: &Self) -> Self {
+        LinearColor::over(self, background)
+    }
+
+    fn write_bytes(&self, bytes: &mut Vec<u8>) {
+        for channel in self.to_rgba() {
+            bytes.extend_from_slice(&channel.to_le_bytes());
+        }
+    }
+}
+
+
+/// Render target storing pixels of any format row by row in memory.
+///
+/// Unlike `Bitmap` it can not be presented in a window directly, convert it with `to_bitmap` first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Surface<F: PixelFormat> {
+    width: usize,
+    height: usize,
+    buffer: Vec<F>,
+}
+
+
+impl<F: PixelFormat> Surface<F> {
+    /// Creates Surface filled with the default value of the format.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self::filled(width, height, F::default())
+    }
+
+    pub fn filled(width: usize, height: usize, pixel: F) -> Self {
+        Self { width, height, buffer: vec![pixel; width * height] }
+    }
+
+    /// Changes dimensions of the Surface, contents are reset to the default value when the size changes and kept otherwise.
+    pub fn resize(&mut self, width: usize, height: usize) {
+        if (width, height) == (self.width, self.height) {
+            return;
+        }
+        *self = Self::new(width, height);
+    }
+
+    /// Converts Bitmap to the format of the Surface.
+    pub fn from_bitmap(bitmap: &crate::Bitmap) -> Self {
+        Self { width: bitmap.width, height: bitmap.height, buffer: bitmap.pixels().iter().map(|&pixel| F::from_pixel(pixel)).collect() }
+    }
+
+    /// Converts the Surface to a Bitmap which can be presented in a window.
+    pub fn to_bitmap(&self) -> crate::Bitmap {
+        let mut bitmap = crate::Bitmap::new(self.width, self.height);
+        for (destination, source) in bitmap.pixels_mut().iter_mut().zip(&self.buffer) {
+            *destination = source.to_pixel();
+        }
+        bitmap
+    }
+
+    /// Converts the Surface to another format through float colors.
+    pub fn convert<G: PixelFormat>(&self) -> Surface<G> {
+        Surface { width: self.width, height: self.height, buffer: self.buffer.iter().map(|pixel| G::from_rgba(pixel.to_rgba())).collect() }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<F> {
+        (x < self.width && y < self.height).then(|| self.buffer[y * self.width + x])
+    }
+
+    /// All pixels row by row.
+    pub fn pixels(&self) -> &[F] {
+        &self.buffer
+    }
+
+    /// All pixels row by row.
+    pub fn pixels_mut(&mut self) -> &mut [F] {
+        &mut self.buffer
+    }
+
+    pub fn fill(&mut self, pixel: F) {
+        self.buffer.fill(pixel);
+    }
+
+    /// Pixels packed row by row without padding, as expected by display controllers and image files.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.buffer.len() * F::BYTES);
+        self.buffer.iter().for_each(|pixel| pixel.write_bytes(&mut bytes));
+        bytes
+    }
+
+    /// Read only view of the part of the Surface inside of `rect`.
+    pub fn view(&self, rect: Rect) -> BitmapView<'_, F> {
+        BitmapView::from_buffer(&self.buffer, self.width, self.height, rect)
+    }
+
+    /// View of the part of the Surface inside of `rect`, which can be drawn into like a separate Surface.
+    pub fn view_mut(&mut self, rect: Rect) -> BitmapViewMut<'_, F> {
+        BitmapViewMut::from_buffer(&mut self.buffer, self.width, self.height, rect)
+    }
+}
+
+
+impl<F: PixelFormat> RenderTarget for Surface<F> {
+    type Format = F;
+
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn get(&self, x: usize, y: usize) -> Option<F> {
+        Surface::get(self, x, y)
+    }
+
+    fn set(&mut self, x: usize, y: usize, pixel: F) {
+        assert!(x < self.width && y < self.height, "pixel ({}, {}) outside of {}x{} surface", x, y, self.width, self.height);
+        self.buffer[y * self.width + x] = pixel;
+    }
+}
+
+
+/// Test suite for pixel formats.
+#[cfg(test)]
+mod test_format {
+    use super::*;
+    use crate::graphics::{draw_line, fill_elipsis};
+
+    #[test]
+    fn conversions() {
+        let pixel = Pixel::new(200, 100, 50).with_alfa(128);
+        assert_eq!(Rgba8::from_pixel(pixel).to_pixel(), pixel);
+        assert_eq!(RgbaF32::from_pixel(pixel).to_pixel(), pixel);
+        assert_eq!(Gray8::from_pixel(pixel), Gray8(124));
+        assert_eq!(Rgb565::from_pixel(pixel).to_pixel(), Pixel::new(197, 101, 49));
+        assert_eq!(Rgb565::new(255, 255, 255), Rgb565(0xffff));
+
+        // Every 16 bit color survives the round trip through 8 bits.
+        assert!((0..=u16::MAX).all(|value| Rgb565::from_pixel(Rgb565(value).to_pixel()) == Rgb565(value)));
+        assert_eq!(RgbaF32::new(4.0, 0.5, -1.0).to_pixel(), Pixel::new(255, 188, 0));
+    }
+
+    #[test]
+    fn blending() {
+        // Colors are mixed in linear space, half of white over black is brighter than 128.
+        let source = Pixel::new(255, 255, 255).with_alfa(128);
+        let black = Pixel::new(0, 0, 0);
+        assert_eq!(Rgb565::composite(&source, &Rgb565::from_pixel(black)).to_pixel(), Pixel::new(189, 186, 189));
+        assert_eq!(Gray8::composite(&source, &Gray8(0)), Gray8(188));
+        assert_eq!(Rgba8::composite(&source, &Rgba8::from_pixel(black)).to_pixel(), Pixel::new(188, 188, 188));
+        assert_eq!(Pixel::composite(&source, &black), Pixel::new(188, 188, 188));
+
+        let half = RgbaF32 { alfa: 0.5, ..RgbaF32::new(3.0, 1.0, 0.0) };
+        assert_eq!(half.over(&RgbaF32::new(1.0, 1.0, 1.0)), RgbaF32::new(2.0, 1.0, 0.5));
+        assert_eq!(half.over(&RgbaF32::default()), half);
+    }
+
+    #[test]
+    fn drawing_into_surfaces() {
+        let mut gray: Surface<Gray8> = Surface::new(8, 8);
+        draw_line(&mut gray, 0, 0, 8, 8, &(255, 255, 255));
+        assert_eq!(gray.pixels().iter().filter(|pixel| pixel.0 == 255).count(), 8);
+
+        let mut hdr: Surface<RgbaF32> = Surface::new(16, 16);
+        fill_elipsis(&mut hdr.view_mut(Rect::new(8, 8, 8, 8)), 3, 3, 4, 4, &(255, 0, 0));
+        assert!(hdr.get(12, 12).unwrap().red == 1.0);
+        assert_eq!(hdr.get(4, 4), Some(RgbaF32::default()));
+
+        let bitmap = hdr.to_bitmap();
+        assert_eq!(Surface::<RgbaF32>::from_bitmap(&bitmap).convert::<Rgb565>().get(12, 12), Some(Rgb565::new(255, 0, 0)));
+        assert_eq!(Surface::filled(2, 1, Rgb565(0x1234)).to_bytes(), vec![0x34, 0x12, 0x34, 0x12]);
+    }
+}
@@ -0,0 +1,383 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use crate::Bitmap;
+use crate::palette::{Dithering, Palette, quantize};
+
+/// Largest number of codes of the LZW dictionary, codes are at most 12 bits wide.
+const MAX_CODES: u16 = 4096;
+
+
+/// Source of the colors the frames are quantized to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GifPalette {
+    /// Single palette of at most given number of colors generated from all the frames, stored once.
+    Global(usize),
+    /// Every frame gets its own palette of at most given number of colors, which fits better but adds 768 bytes per frame.
+    PerFrame(usize),
+    /// Given palette used for all the frames, for example one of the fixed retro palettes.
+    Fixed(Palette),
+}
+
+
+/// How many times the animation is played.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Looping {
+    /// Animation stops at the last frame.
+    Once,
+    /// Animation is repeated endlessly.
+    Forever,
+    /// Animation is repeated given number of times after the first playback, zero plays it once.
+    Repeat(u16),
+}
+
+
+/// Settings of animated GIF export.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GifEncoder {
+    pub palette: GifPalette,
+    /// Ordered dithering is the default, error diffusion makes the noise change from frame to frame.
+    pub dithering: Dithering,
+    /// Time every frame is displayed in milliseconds, stored rounded to hundredths of a second.
+    pub delay: u32,
+    pub looping: Looping,
+}
+
+
+impl Default for GifEncoder {
+    fn default() -> Self {
+        Self { palette: GifPalette::Global(256), dithering: Dithering::Bayer { size: 4 }, delay: 40, looping: Looping::Forever }
+    }
+}
+
+
+impl GifEncoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_palette(self, palette: GifPalette) -> Self {
+        Self { palette, ..self }
+    }
+
+    pub fn with_dithering(self, dithering: Dithering) -> Self {
+        Self { dithering, ..self }
+    }
+
+    pub fn with_delay(self, delay: u32) -> Self {
+        Self { delay, ..self }
+    }
+
+    pub fn with_looping(self, looping: Looping) -> Self {
+        Self { looping, ..self }
+    }
+
+    /// Encodes frames of the same dimensions as an animated GIF.
+    pub fn write<W: Write>(&self, frames: &[Bitmap], writer: &mut W) -> Result<(), std::io::Error> {
+        let invalid = |message: String| std::io::Error::new(std::io::ErrorKind::InvalidInput, message);
+        let first = frames.first().ok_or_else(|| invalid(String::from("GIF needs at least one frame")))?;
+        let (width, height) = (first.width, first.height);
+        if width > u16::MAX as usize || height > u16::MAX as usize {
+            return Err(invalid(format!("Frame of {} x {} pixels is too large for GIF", width, height)));
+        }
+        if let Some(frame) = frames.iter().find(|frame| (frame.width, frame.height) != (width, height)) {
+            return Err(invalid(format!("Frame of {} x {} pixels differs from the first one of {} x {}", frame.width, frame.height, width, height)));
+        }
+
+        let global = match &self.palette {
+            GifPalette::Global(count) => {
+                let pixels: Vec<_> = frames.iter().flat_map(|frame| frame.pixels().iter().copied()).collect();
+                Some(Palette::median_cut(&pixels, *count))
+            },
+            GifPalette::PerFrame(_) => None,
+            GifPalette::Fixed(palette) => Some(palette.clone()),
+        };
+
+        writer.write_all(b"GIF89a")?;
+        writer.write_all(&(width as u16).to_le_bytes())?;
+        writer.write_all(&(height as u16).to_le_bytes())?;
+        // Global color table flag, 8 bits per channel of the original image and size of the table, background index and aspect ratio.
+        let packed = match &global {
+            Some(palette) => 0x80 | 0x70 | (table_bits(palette) - 1),
+            None => 0x70,
+        };
+        writer.write_all(&[packed, 0, 0])?;
+        if let Some(palette) = &global {
+            write_color_table(writer, palette)?;
+        }
+
+        let repetitions = match self.looping {
+            Looping::Once | Looping::Repeat(0) => None,
+            Looping::Forever => Some(0),
+            Looping::Repeat(count) => Some(count),
+        };
+        if let Some(count) = repetitions {
+            // Netscape application extension, loop count of 0 means forever.
+            writer.write_all(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01")?;
+            writer.write_all(&count.to_le_bytes())?;
+            writer.write_all(&[0])?;
+        }
+
+        let delay = ((self.delay + 5) / 10).min(u16::MAX as u32) as u16;
+        for frame in frames {
+            let local = match &self.palette {
+                GifPalette::PerFrame(count) => Some(Palette::median_cut(frame.pixels(), *count)),
+                _ => None,
+            };
+            let palette = local.as_ref().or(global.as_ref()).unwrap();
+            let indices = quantize(frame.pixels(), width, height, palette, self.dithering);
+
+            // Graphic control extension, frames are left in place and fully replaced by the next ones.
+            writer.write_all(&[0x21, 0xF9, 0x04, 0x04])?;
+            writer.write_all(&delay.to_le_bytes())?;
+            writer.write_all(&[0, 0])?;
+
+            // Image descriptor covering the whole screen.
+            writer.write_all(&[0x2C, 0, 0, 0, 0])?;
+            writer.write_all(&(width as u16).to_le_bytes())?;
+            writer.write_all(&(height as u16).to_le_bytes())?;
+            match &local {
+                Some(palette) => {
+                    writer.write_all(&[0x80 | (table_bits(palette) - 1)])?;
+                    write_color_table(writer, palette)?;
+                },
+                None => writer.write_all(&[0])?,
+            }
+
+            let minimum_code_size = table_bits(palette).max(2);
+            writer.write_all(&[minimum_code_size])?;
+            for block in lzw_encode(&indices, minimum_code_size).chunks(255) {
+                writer.write_all(&[block.len() as u8])?;
+                writer.write_all(block)?;
+            }
+            writer.write_all(&[0])?;
+        }
+        writer.write_all(&[0x3B])
+    }
+
+    pub fn save(&self, frames: &[Bitmap], path: &str) -> Result<(), std::io::Error> {
+        let mut file_handle = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.write(frames, &mut file_handle)
+    }
+}
+
+
+/// Number of bits of indices into the palette, color tables have 2^bits entries.
+fn table_bits(palette: &Palette) -> u8 {
+    (palette.len().next_power_of_two().trailing_zeros() as u8).max(1)
+}
+
+
+/// Writes colors of the palette padded with black to the size of the color table.
+fn write_color_table<W: Write>(writer: &mut W, palette: &Palette) -> Result<(), std::io::Error> {
+    let mut bytes: Vec<u8> = palette.colors().iter().flat_map(|color| [color.red, color.green, color.blue]).collect();
+    bytes.resize(3 << table_bits(palette), 0);
+    writer.write_all(&bytes)
+}
+
+
+/// Packs codes of variable width into bytes, starting from the least significant bits.
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+
+impl BitWriter {
+    fn write(&mut self, code: u16, width: u8) {
+        self.buffer |= (code as u32) << self.count;
+        self.count += width as u32;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+
+/// Compresses palette indices with the variable code width LZW used by GIF.
+///
+/// Sequences of indices already seen are replaced by codes of the dictionary, which grows with every emitted code
+/// and is cleared when it reaches 4096 entries.
+fn lzw_encode(indices: &[u8], minimum_code_size: u8) -> Vec<u8> {
+    let clear = 1u16 << minimum_code_size;
+    let end = clear + 1;
+    let mut output = BitWriter { bytes: Vec::new(), buffer: 0, count: 0 };
+    let mut dictionary: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut code_size = minimum_code_size + 1;
+    let mut next_code = end + 1;
+
+    output.write(clear, code_size);
+    let mut prefix: Option<u16> = None;
+    for &index in indices {
+        let Some(current) = prefix else {
+            prefix = Some(index as u16);
+            continue;
+        };
+        if let Some(&code) = dictionary.get(&(current, index)) {
+            prefix = Some(code);
+            continue;
+        }
+        output.write(current, code_size);
+        if next_code < MAX_CODES {
+            dictionary.insert((current, index), next_code);
+            next_code += 1;
+            // Decoder adds its entries one code later, the width grows once the last added code does not fit.
+            if next_code > 1 << code_size && code_size < 12 {
+                code_size += 1;
+            }
+        } else {
+            output.write(clear, code_size);
+            dictionary.clear();
+            code_size = minimum_code_size + 1;
+            next_code = end + 1;
+        }
+        prefix = Some(index as u16);
+    }
+    if let Some(current) = prefix {
+        output.write(current, code_size);
+    }
+    output.write(end, code_size);
+    output.finish()
+}
+
+
+/// Test suite for GIF export.
+#[cfg(test)]
+mod test_gif {
+    use super::*;
+    use crate::Pixel;
+
+    /// Reference LZW decoder following the GIF specification.
+    fn lzw_decode(data: &[u8], minimum_code_size: u8) -> Vec<u8> {
+        let clear = 1usize << minimum_code_size;
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let mut code_size = minimum_code_size + 1;
+        let (mut buffer, mut count, mut position) = (0u32, 0, 0);
+        let mut previous: Option<usize> = None;
+        let mut output = Vec::new();
+        loop {
+            while count < code_size as u32 {
+                buffer |= (data[position] as u32) << count;
+                position += 1;
+                count += 8;
+            }
+            let code = (buffer & ((1 << code_size) - 1)) as usize;
+            buffer >>= code_size;
+            count -= code_size as u32;
+
+            if code == clear {
+                table = (0..clear).map(|index| vec![index as u8]).chain([vec![], vec![]]).collect();
+                code_size = minimum_code_size + 1;
+                previous = None;
+                continue;
+            }
+            if code == clear + 1 {
+                return output;
+            }
+            let entry = match previous {
+                None => table[code].clone(),
+                Some(previous) => {
+                    let mut entry = if code < table.len() { table[code].clone() } else { table[previous].clone() };
+                    if code == table.len() {
+                        entry.push(table[previous][0]);
+                    }
+                    if table.len() < MAX_CODES as usize {
+                        let mut added = table[previous].clone();
+                        added.push(entry[0]);
+                        table.push(added);
+                    }
+                    entry
+                },
+            };
+            if table.len() >= 1 << code_size && code_size < 12 {
+                code_size += 1;
+            }
+            output.extend(&entry);
+            previous = Some(code);
+        }
+    }
+
+    #[test]
+    fn lzw_round_trip() {
+        let mut random = 12345u32;
+        let noise: Vec<u8> = (0..20000).map(|_| {
+            random = random.wrapping_mul(1103515245).wrapping_add(12345);
+            (random >> 16) as u8
+        }).collect();
+        let repeating: Vec<u8> = (0..20000).map(|index| (index / 7 % 4) as u8).collect();
+        for (indices, minimum_code_size) in [(&noise, 8), (&repeating, 2), (&vec![1u8], 2), (&vec![], 2)] {
+            let encoded = lzw_encode(indices, minimum_code_size);
+            assert_eq!(&lzw_decode(&encoded, minimum_code_size), indices);
+        }
+        // Long runs compress well.
+        assert!(lzw_encode(&repeating, 2).len() < 2000);
+    }
+
+    #[test]
+    fn animation() {
+        let frames: Vec<Bitmap> = (0..3).map(|frame| {
+            let mut bitmap = Bitmap::new(5, 3);
+            bitmap.fill(&(0, 0, 0));
+            bitmap.set_pixel(frame, 1, Pixel::new(255, 255, 255));
+            bitmap
+        }).collect();
+        let encoder = GifEncoder::new().with_palette(GifPalette::Fixed(Palette::grayscale(2))).with_dithering(Dithering::None).with_delay(100);
+        let mut gif = Vec::new();
+        encoder.write(&frames, &mut gif).unwrap();
+
+        assert_eq!(&gif[..13], b"GIF89a\x05\x00\x03\x00\xF0\x00\x00");
+        assert_eq!(&gif[13..19], &[0, 0, 0, 255, 255, 255]);
+        assert_eq!(&gif[19..38], b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00");
+        assert_eq!(*gif.last().unwrap(), 0x3B);
+
+        // Frames follow the extensions, each with its delay and compressed indices.
+        let mut position = 38;
+        for frame in 0..3 {
+            assert_eq!(&gif[position..position + 8], &[0x21, 0xF9, 0x04, 0x04, 10, 0, 0, 0]);
+            assert_eq!(&gif[position + 8..position + 18], &[0x2C, 0, 0, 0, 0, 5, 0, 3, 0, 0]);
+            assert_eq!(gif[position + 18], 2);
+            let length = gif[position + 19] as usize;
+            let indices = lzw_decode(&gif[position + 20..position + 20 + length], 2);
+            assert_eq!(indices, (0..15).map(|index| (index == 5 + frame) as u8).collect::<Vec<_>>());
+            assert_eq!(gif[position + 20 + length], 0);
+            position += 21 + length;
+        }
+        assert_eq!(position, gif.len() - 1);
+
+        let mut single = Vec::new();
+        encoder.clone().with_looping(Looping::Once).with_palette(GifPalette::PerFrame(2)).write(&frames[..1], &mut single).unwrap();
+        assert_eq!(single[10], 0x70);
+        assert_eq!(&single[13..16], &[0x21, 0xF9, 0x04]);
+
+        assert!(encoder.write(&[], &mut Vec::new()).is_err());
+        assert!(encoder.write(&[Bitmap::new(2, 2), Bitmap::new(3, 2)], &mut Vec::new()).is_err());
+    }
+
+
+    #[test]
+    fn repetitions() {
+        let frames = [Bitmap::new(2, 2), Bitmap::new(2, 2)];
+        let encode = |looping| {
+            let mut gif = Vec::new();
+            GifEncoder::new().with_looping(looping).write(&frames, &mut gif).unwrap();
+            gif
+        };
+        let once = encode(Looping::Once);
+        assert!(!once.windows(11).any(|window| window == b"NETSCAPE2.0"));
+        assert_eq!(encode(Looping::Repeat(0)), once);
+
+        let repeated = encode(Looping::Repeat(3));
+        let start = repeated.windows(11).position(|window| window == b"NETSCAPE2.0").unwrap();
+        assert_eq!(&repeated[start + 11..start + 16], &[0x03, 0x01, 0x03, 0x00, 0x00]);
+    }
+}
@@ -1,6 +1,11 @@
-use std::cmp::Ordering::Equal;
 #[allow(dead_code)]
-use crate::{Bitmap, Pixel};
+use crate::Pixel;
+use crate::bitmap::Rect;
+use crate::view::RenderTarget;
+use crate::line::clip_segment;
+use crate::lighting::{Lighting, Material, Shading};
+use crate::pipeline::{Pipeline, PipelineStats};
+use crate::shader::{FlatShader, FragmentShader, MeshVertex, PhongShader, SceneUniforms, VertexShader};
 
 use nalgebra as na;
 use windows::Win32::UI::WindowsAndMessaging::POINTER_DEVICE_PRODUCT_STRING_MAX;
@@ -19,7 +24,7 @@ type T3 = STriangle<3>;
 type Triangle2D = STriangle<2>;
 
 
-fn draw_low(bitmap: &mut Bitmap, x0: i32, y0: i32, x1: i32, y1: i32, color: &Rgb) {
+fn draw_low<T: RenderTarget>(bitmap: &mut T, x0: i32, y0: i32, x1: i32, y1: i32, color: &Rgb) {
     let dx = x1 - x0;
     let mut dy = y1 - y0;
     let yi = if dy < 0 {
@@ -32,7 +37,9 @@ fn draw_low(bitmap: &mut Bitmap, x0: i32, y0: i32, x1: i32, y1: i32, color: &Rgb
     let mut diff = 2 * dy - dx;
     let mut y = y0;
     for x in x0..x1 {
-        bitmap.set_pixel(x as usize, y as usize, Pixel::from_rgb_tuple(*color));
+        if bitmap.rect().contains(x, y) {
+            bitmap.set_pixel(x as usize, y as usize, Pixel::from_rgb_tuple(*color));
+        }
         if diff > 0 {
             y += yi;
             diff += 2 * (dy - dx);
@@ -43,7 +50,7 @@ fn draw_low(bitmap: &mut Bitmap, x0: i32, y0: i32, x1: i32, y1: i32, color: &Rgb
 }
 
 
-fn draw_high(bitmap: &mut Bitmap, x0: i32, y0: i32, x1: i32, y1: i32, color: &Rgb) {
+fn draw_high<T: RenderTarget>(bitmap: &mut T, x0: i32, y0: i32, x1: i32, y1: i32, color: &Rgb) {
     let mut dx = x1 - x0;
     let dy = y1 - y0;
     let xi = if dx < 0 {
@@ -57,7 +64,9 @@ fn draw_high(bitmap: &mut Bitmap, x0: i32, y0: i32, x1: i32, y1: i32, color: &Rg
     let mut x = x0;
 
     for y in y0..y1 {
-        bitmap.set_pixel(x as usize, y as usize, Pixel::from_rgb_tuple(*color));
+        if bitmap.rect().contains(x, y) {
+            bitmap.set_pixel(x as usize, y as usize, Pixel::from_rgb_tuple(*color));
+        }
         if diff > 0 {
             x += xi;
             diff += 2 * (dx - dy);
@@ -67,15 +76,25 @@ fn draw_high(bitmap: &mut Bitmap, x0: i32, y0: i32, x1: i32, y1: i32, color: &Rg
     }
 }
 
-pub fn draw_elipsis(bitmap: &mut Bitmap, a: i32, b: i32, offset_x: i32, offset_y: i32) {
+/// Draws outline of an axis aligned ellipse with semi-axes `a` and `b` centered at specified offset.
+///
+/// Implementation of the midpoint ellipse algorithm. Pixels outside of the bitmap are skipped.
+#[allow(dead_code)]
+pub fn draw_elipsis<T: RenderTarget>(bitmap: &mut T, a: i32, b: i32, offset_x: i32, offset_y: i32, color: &Rgb) {
     let (mut x, mut y) = (0, b);
     let mut f = b * b - a * a * b + (a * a) / 4;
 
-    // Region 1
-    while 2 * b * b * x < 2 * a * a * y {
+    let mut plot = |x: i32, y: i32| {
         for point in symmetry_points(x, y, offset_x, offset_y) {
-            bitmap.set_pixel(point.0 as _, point.1 as _, Pixel::from_rgb_tuple(((point.0 / a) as _, (point.1 / b) as _, 255)));
+            if bitmap.rect().contains(point.0, point.1) {
+                bitmap.set_pixel(point.0 as _, point.1 as _, Pixel::from_rgb_tuple(*color));
+            }
         }
+    };
+
+    // Region 1
+    while 2 * b * b * x < 2 * a * a * y {
+        plot(x, y);
         if f >= 0 {
             y -= 1;
             f -= 2 * a * a * y;
@@ -87,9 +106,7 @@ pub fn draw_elipsis(bitmap: &mut Bitmap, a: i32, b: i32, offset_x: i32, offset_y
     // Region 2
     f = (b * b) * (x + 1) * (x + 1) + (a * a) * (y - 1) * (y - 1) - (a * a) * b * b;
     while y >= 0 {
-        for point in symmetry_points(x, y, offset_x, offset_y) {
-            bitmap.set_pixel(point.0 as _, point.1 as _, Pixel::from_rgb_tuple(((point.0 / a) as _, (point.1 / b) as _, 255)));
-        }
+        plot(x, y);
         if f <= 0 {
             x += 1;
             f += 2 * b * b * x + b * b;
@@ -99,6 +116,20 @@ pub fn draw_elipsis(bitmap: &mut Bitmap, a: i32, b: i32, offset_x: i32, offset_y
     }
 }
 
+/// Fills axis aligned ellipse with semi-axes `a` and `b` centered at specified offset.
+///
+/// Pixels outside of the bitmap are skipped.
+#[allow(dead_code)]
+pub fn fill_elipsis<T: RenderTarget>(bitmap: &mut T, a: i32, b: i32, offset_x: i32, offset_y: i32, color: &Rgb) {
+    if a < 0 || b <= 0 {
+        return;
+    }
+    for y in -b..=b {
+        let half_width = (a as f32 * (1.0 - (y * y) as f32 / (b * b) as f32).sqrt()).round() as i32;
+        draw_horizontal_line(bitmap, offset_y + y, offset_x - half_width, offset_x + half_width, color);
+    }
+}
+
 fn symmetry_points(x: i32, y: i32, offset_x: i32, offset_y: i32) -> Vec<(i32, i32)> {
     vec![(x, y), (-x, y), (x, -y), (-x, -y)]
         .into_iter()
@@ -110,7 +141,24 @@ fn symmetry_points(x: i32, y: i32, offset_x: i32, offset_y: i32) -> Vec<(i32, i3
 /// Draws a line between points specified.
 ///
 /// Implementation of general bresenham algorithm using integer arithmetic.
-pub fn draw_line(bitmap: &mut Bitmap, x0: i32, y0: i32, x1: i32, y1: i32, color: &Rgb) {
+/// Line is clipped to the bitmap first, so end points may lie anywhere, even far outside of it.
+pub fn draw_line<T: RenderTarget>(bitmap: &mut T, x0: i32, y0: i32, x1: i32, y1: i32, color: &Rgb) {
+    let rect = bitmap.rect();
+    let (x0, y0, x1, y1) = if rect.contains(x0, y0) && rect.contains(x1, y1) {
+        (x0, y0, x1, y1)
+    } else {
+        // One pixel margin, which is skipped while drawing, keeps the clipped line on the same pixels.
+        let (min, max) = ((rect.x as f64 - 1.0, rect.y as f64 - 1.0), (rect.right() as f64, rect.bottom() as f64));
+        let (fx0, fy0, fx1, fy1) = (x0 as f64, y0 as f64, x1 as f64, y1 as f64);
+        let (t0, t1) = match clip_segment((fx0, fy0), (fx1, fy1), min, max) {
+            Some(range) => range,
+            None => return,
+        };
+        let at = |t: f64| ((fx0 + (fx1 - fx0) * t).round() as i32, (fy0 + (fy1 - fy0) * t).round() as i32);
+        let ((x0, y0), (x1, y1)) = (at(t0), at(t1));
+        (x0, y0, x1, y1)
+    };
+
     if (y1 - y0).abs() < (x1 - x0).abs() {
         if x0 > x1 {
             draw_low(bitmap, x1, y1, x0, y0, color);
@@ -125,26 +173,49 @@ pub fn draw_line(bitmap: &mut Bitmap, x0: i32, y0: i32, x1: i32, y1: i32, color:
 }
 
 
+/// Draws square point of given size centered at specified coordinates.
+///
+/// Parts of the point outside of the bitmap are skipped.
+pub fn draw_point<T: RenderTarget>(bitmap: &mut T, x: i32, y: i32, size: i32, color: &Rgb) {
+    let (left, top) = (x.saturating_sub(size / 2), y.saturating_sub(size / 2));
+    let x_min = left.max(0);
+    let y_min = top.max(0);
+    let x_max = left.saturating_add(size).min(bitmap.width() as i32);
+    let y_max = top.saturating_add(size).min(bitmap.height() as i32);
+    for y in y_min..y_max {
+        for x in x_min..x_max {
+            bitmap.set_pixel(x as usize, y as usize, Pixel::from_rgb_tuple(*color));
+        }
+    }
+}
+
+
 /// Line drawing algorithm optimized for drawing horizontal lines.
-fn draw_horizontal_line(bitmap: &mut Bitmap, y: i32, x0: i32, x1: i32, color: &Rgb) {
+fn draw_horizontal_line<T: RenderTarget>(bitmap: &mut T, y: i32, x0: i32, x1: i32, color: &Rgb) {
+    if y < 0 || y >= bitmap.height() as i32 {
+        return;
+    }
     let (x_min, x_max) = if x0 < x1 { (x0, x1) } else { (x1, x0) };
-    for x in x_min..=x_max {
+    for x in x_min.max(0)..=x_max.min(bitmap.width() as i32 - 1) {
         bitmap.set_pixel(x as usize, y as usize, Pixel::from_rgb_tuple(*color));
     }
 }
 
 
 /// Line drawing algorithm optimized for drawing vertical lines.
-fn draw_vertical_line(bitmap: &mut Bitmap, x: i32, y0: i32, y1: i32, color: &Rgb) {
+fn draw_vertical_line<T: RenderTarget>(bitmap: &mut T, x: i32, y0: i32, y1: i32, color: &Rgb) {
+    if x < 0 || x >= bitmap.width() as i32 {
+        return;
+    }
     let (y_min, y_max) = if y0 < y1 { (y0, y1) } else { (y1, y0) };
-    for y in y_min..=y_max {
+    for y in y_min.max(0)..=y_max.min(bitmap.height() as i32 - 1) {
         bitmap.set_pixel(x as usize, y as usize, Pixel::from_rgb_tuple(*color));
     }
 }
 
 
 /// Fills the upper part of the split triangle
-fn fill_top_triangle(bitmap: &mut Bitmap, v1: &na::Vector2<i32>, v2: &na::Vector2<i32>, v3: &na::Vector2<i32>, color: &Rgb) {
+fn fill_top_triangle<T: RenderTarget>(bitmap: &mut T, v1: &na::Vector2<i32>, v2: &na::Vector2<i32>, v3: &na::Vector2<i32>, color: &Rgb) {
     let inverse_slope1 = (v2.x - v1.x) as f32 / (v2.y - v1.y) as f32;
     let inverse_slope2 = (v3.x - v1.x) as f32 / (v3.y - v1.y) as f32;
 
@@ -160,7 +231,7 @@ fn fill_top_triangle(bitmap: &mut Bitmap, v1: &na::Vector2<i32>, v2: &na::Vector
 
 
 /// Fills the bottom part of the split triangle
-fn fill_bottom_triangle(bitmap: &mut Bitmap, v1: &na::Vector2<i32>, v2: &na::Vector2<i32>, v3: &na::Vector2<i32>, color: &Rgb) {
+fn fill_bottom_triangle<T: RenderTarget>(bitmap: &mut T, v1: &na::Vector2<i32>, v2: &na::Vector2<i32>, v3: &na::Vector2<i32>, color: &Rgb) {
     let inverse_slope1 = (v3.x - v1.x) as f32 / (v3.y - v1.y) as f32;
     let inverse_slope2 = (v3.x - v2.x) as f32 / (v3.y - v2.y) as f32;
 
@@ -195,13 +266,17 @@ impl<const S: usize> STriangle<S> {
 }
 
 
-/// Triangle's vertices go counter clockwise.
+/// Triangle's vertices go counter clockwise when seen from the outside, which matches the default `Pipeline::front_face`.
+///
+/// Apart from the face normal every vertex carries its own normal used for smooth shading.
+/// By default vertex normals are equal to the face normal.
 #[derive(Copy, Clone, Debug)]
 pub struct Triangle {
     v0: P3,
     v1: P3,
     v2: P3,
     normal: V3,
+    vertex_normals: [V3; 3],
 }
 
 
@@ -217,8 +292,14 @@ impl Triangle {
     pub fn new(v0: P3, v1: P3, v2: P3) -> Self {
         let vec_01 = V3::new(v1.x - v0.x, v1.y - v0.y, v1.z - v0.z);
         let vec_02 = V3::new(v2.x - v0.x, v2.y - v0.y, v2.z - v0.z);
+        let normal = (vec_01.cross(&vec_02)).normalize();
         
-        Self { v0, v1, v2, normal: (vec_01.cross(&vec_02)).normalize() }
+        Self { v0, v1, v2, normal, vertex_normals: [normal; 3] }
+    }
+
+    /// Creates triangle with explicitly specified vertex normals.
+    pub fn with_normals(v0: P3, v1: P3, v2: P3, vertex_normals: [V3; 3]) -> Self {
+        Self { vertex_normals: vertex_normals.map(|normal| normal.normalize()), ..Self::new(v0, v1, v2) }
     }
 
     pub fn normal_vector(v0: &P3, v1: &P3, v2: &P3) -> V3 {
@@ -231,16 +312,32 @@ impl Triangle {
         [self.v0, self.v1, self.v2].into_iter()
     }
 
+    pub fn normal(&self) -> &V3 {
+        &self.normal
+    }
+
+    pub fn vertex_normals(&self) -> &[V3; 3] {
+        &self.vertex_normals
+    }
+
+    pub fn set_vertex_normals(&mut self, vertex_normals: [V3; 3]) {
+        self.vertex_normals = vertex_normals.map(|normal| normal.normalize());
+    }
+
     pub fn apply_rotation(&mut self, transform: &na::Rotation3<f32>) {
         self.v0 = transform.transform_point(&self.v0);
         self.v1 = transform.transform_point(&self.v1);
         self.v2 = transform.transform_point(&self.v2);
+        self.normal = transform.transform_vector(&self.normal);
+        self.vertex_normals = self.vertex_normals.map(|normal| transform.transform_vector(&normal));
     }
 
     pub fn apply_isometry(&mut self, transform: &na::Isometry3<f32>) {
         self.v0 = transform.transform_point(&self.v0);
         self.v1 = transform.transform_point(&self.v1);
         self.v2 = transform.transform_point(&self.v2);
+        self.normal = transform.transform_vector(&self.normal);
+        self.vertex_normals = self.vertex_normals.map(|normal| transform.transform_vector(&normal));
     }
 
     pub fn apply_translation(&mut self, transform: &na::Translation3<f32>) {
@@ -253,6 +350,10 @@ impl Triangle {
         self.v0 = transform.transform_point(&self.v0);
         self.v1 = transform.transform_point(&self.v1);
         self.v2 = transform.transform_point(&self.v2);
+        // NOTE: normals are transformed by the inverse transpose, which for scaling is just the inverse.
+        let inverse = transform.vector.map(|factor| 1.0 / factor);
+        self.normal = self.normal.component_mul(&inverse).normalize();
+        self.vertex_normals = self.vertex_normals.map(|normal| normal.component_mul(&inverse).normalize());
     }
 
     pub fn apply_projection(&mut self, transform: &na::Perspective3<f32>) {
@@ -263,7 +364,7 @@ impl Triangle {
 }
 
 
-pub fn fill_triangle(bitmap: &mut Bitmap, triangle: &mut Triangle2D, color: &Rgb) {
+pub fn fill_triangle<T: RenderTarget>(bitmap: &mut T, triangle: &mut Triangle2D, color: &Rgb) {
     triangle.vertices.sort_unstable_by(|point1, point2| { point1.y.partial_cmp(&point2.y).unwrap() });
 
     let [v1, v2, v3] = &triangle.vertices;
@@ -282,28 +383,101 @@ pub fn fill_triangle(bitmap: &mut Bitmap, triangle: &mut Triangle2D, color: &Rgb
 }
 
 
+/// Rasterizes triangle with edge functions, calling `shade` for every pixel whose center it covers.
+///
+/// `shade` receives perspective correct barycentric coordinates of the pixel center with respect to `v0`, `v1` and `v2`,
+/// which can be used to interpolate any per vertex attribute, and returns color of the pixel or `None` to leave it unchanged.
+/// `inverse_w` holds reciprocals of clip space `w` of the vertices, `[1.0; 3]` gives plain screen space interpolation.
+/// Only `x` and `y` coordinates of the vertices are used and pixels outside of the bitmap are skipped.
+/// Both windings are accepted.
+#[allow(dead_code)]
+pub fn rasterize_triangle<T: RenderTarget, F: FnMut(&[f32; 3]) -> Option<Rgb>>(bitmap: &mut T, v0: &P3, v1: &P3, v2: &P3, inverse_w: &[f32; 3], mut shade: F) {
+    let rect = bitmap.rect();
+    walk_triangle(&rect, v0, v1, v2, |x, y, barycentric| {
+        if let Some(color) = shade(&perspective_correct(barycentric, inverse_w)) {
+            bitmap.set_pixel(x, y, Pixel::from_rgb_tuple(color));
+        }
+    });
+}
+
+
+/// Converts screen space barycentric coordinates into weights for interpolating attributes linear in world space.
+///
+/// Attributes divided by `w` are linear in screen space, so the weights are scaled by `inverse_w` and normalized again.
+pub fn perspective_correct(barycentric: &[f32; 3], inverse_w: &[f32; 3]) -> [f32; 3] {
+    let weighted = [0, 1, 2].map(|index| barycentric[index] * inverse_w[index]);
+    let sum = weighted[0] + weighted[1] + weighted[2];
+    weighted.map(|weight| weight / sum)
+}
+
+
+/// Visits every pixel inside of `clip` whose center the triangle covers.
+///
+/// `visit` receives coordinates of the pixel and barycentric coordinates of its center with respect to `v0`, `v1` and `v2`.
+/// Only `x` and `y` coordinates of the vertices are used, both windings are accepted.
+/// Centers lying exactly on an edge follow the top-left rule, so triangles sharing an edge never visit the same pixel.
+pub fn walk_triangle<F: FnMut(usize, usize, &[f32; 3])>(clip: &Rect, v0: &P3, v1: &P3, v2: &P3, mut visit: F) {
+    let oriented = |a: &P3, b: &P3, x: f32, y: f32| (b.x - a.x) * (y - a.y) - (b.y - a.y) * (x - a.x);
+    // Endpoints are always taken in the same order, so triangles sharing an edge get exactly opposite values on it.
+    let edge = |a: &P3, b: &P3, x: f32, y: f32| if (a.x, a.y) <= (b.x, b.y) { oriented(a, b, x, y) } else { -oriented(b, a, x, y) };
+
+    let area = edge(v0, v1, v2.x, v2.y);
+    if area == 0.0 {
+        return;
+    }
+
+    // Edges are oriented so that the inside lies on their positive side, with y pointing down
+    // that makes left edges go up and top edges go right.
+    let top_left = |a: &P3, b: &P3| {
+        let (dx, dy) = if area > 0.0 { (b.x - a.x, b.y - a.y) } else { (a.x - b.x, a.y - b.y) };
+        dy < 0.0 || (dy == 0.0 && dx > 0.0)
+    };
+    let inclusive = [top_left(v1, v2), top_left(v2, v0), top_left(v0, v1)];
+
+    let x_min = v0.x.min(v1.x).min(v2.x).floor().max(clip.x as f32) as i32;
+    let y_min = v0.y.min(v1.y).min(v2.y).floor().max(clip.y as f32) as i32;
+    let x_max = v0.x.max(v1.x).max(v2.x).ceil().min(clip.right() as f32 - 1.0) as i32;
+    let y_max = v0.y.max(v1.y).max(v2.y).ceil().min(clip.bottom() as f32 - 1.0) as i32;
+
+    for y in y_min..=y_max {
+        let center_y = y as f32 + 0.5;
+        for x in x_min..=x_max {
+            let center_x = x as f32 + 0.5;
+            let barycentric = [
+                edge(v1, v2, center_x, center_y) / area,
+                edge(v2, v0, center_x, center_y) / area,
+                edge(v0, v1, center_x, center_y) / area,
+            ];
+            if barycentric.iter().zip(inclusive).all(|(&weight, inclusive)| weight > 0.0 || (inclusive && weight == 0.0)) {
+                visit(x as usize, y as usize, &barycentric);
+            }
+        }
+    }
+}
+
+
 pub struct Mesh {
     triangles: Vec<Triangle>,
-    model_processing_buffer: Vec<Triangle>,
-    color: Rgb,
+    model_processing_buffer: Vec<MeshVertex>,
+    material: Material,
     angle_acc: f32,
     timer: std::time::Instant,
-    translation: na::Isometry3<f32>,
+    view: na::Isometry3<f32>,
     projection: na::Perspective3<f32>,
-    screen_space_translation: na::Translation3<f32>,
-    screen_space_scaling: na::Scale3<f32>,
     eye: P3,
     target: P3,
 }
 
 
 impl Mesh {
-    pub fn new(triangles: Vec<Triangle>, color: Rgb) -> Self {
-        let eye = P3::new(-0.5, -0.5, 10.0);
-        let target = P3::new(-0.5, -0.5, 9.0);
+    pub fn new(triangles: Vec<Triangle>, material: Material) -> Self {
+        let eye = P3::new(0.0, 0.0, 5.0);
+        let target = P3::origin();
         let up = V3::y();
 
-        let translation = na::Isometry3::face_towards(&eye, &target, &up);
+        // NOTE: view maps world space to camera space. `face_towards` maps the other way round,
+        // which only went unnoticed while nothing but the projected outline depended on it.
+        let view = na::Isometry3::look_at_rh(&eye, &target, &up);
 
         let fov = std::f32::consts::FRAC_PI_6;
         let aspect_ratio = ASPECT_RATIO;
@@ -312,89 +486,99 @@ impl Mesh {
 
         let projection = na::Perspective3::new(aspect_ratio, fov, znear, zfar);
 
-        let screen_space_translation = na::Translation3::new(1.0, 1.0, 0.0);
-        let screen_space_scaling = na::Scale3::new(WIDTH as f32 / 2.0, HEIGHT as f32 / 2.0, 1.0);
-
         let model_processing_buffer = Vec::with_capacity(36);
 
         Self {
             triangles,
             model_processing_buffer,
-            color,
+            material,
             angle_acc: 0.0,
             timer: std::time::Instant::now(),
             projection,
-            translation,
-            screen_space_translation,
-            screen_space_scaling,
+            view,
             eye,
             target
         }
     }
 
-    pub fn update(&mut self, bitmap: &mut Bitmap) {
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    pub fn set_material(&mut self, material: Material) {
+        self.material = material;
+    }
+
+    /// Position of the camera in world space.
+    #[allow(dead_code)]
+    pub fn eye(&self) -> &P3 {
+        &self.eye
+    }
+
+    /// Changes aspect ratio of the projection, used when the render target is resized.
+    pub fn set_aspect_ratio(&mut self, aspect_ratio: f32) {
+        if aspect_ratio.is_finite() && aspect_ratio > 0.0 {
+            self.projection.set_aspect(aspect_ratio);
+        }
+    }
+
+    /// Advances the animation and draws the mesh with the built in shader selected by its material.
+    pub fn update<T: RenderTarget>(&mut self, bitmap: &mut T, pipeline: &Pipeline, lighting: &Lighting) -> PipelineStats {
+        match self.material.shading {
+            Shading::Flat => self.update_with(bitmap, pipeline, lighting, &FlatShader, &FlatShader),
+            Shading::Phong => self.update_with(bitmap, pipeline, lighting, &PhongShader, &PhongShader),
+        }
+    }
+
+    /// Advances the animation and draws the mesh with custom shaders.
+    pub fn update_with<'a, T, V, F>(&mut self, bitmap: &mut T, pipeline: &Pipeline, lighting: &'a Lighting, vertex_shader: &V, fragment_shader: &F) -> PipelineStats
+    where
+        T: RenderTarget,
+        V: VertexShader<SceneUniforms<'a>, Vertex = MeshVertex>,
+        F: FragmentShader<SceneUniforms<'a>, Varying = V::Varying>,
+    {
         let z_rot = na::Rotation3::new(V3::z() * std::f32::consts::FRAC_PI_6 * self.angle_acc);
         let x_rot = na::Rotation3::new(V3::x() * std::f32::consts::FRAC_PI_2 * self.angle_acc);
         let _y_rot = na::Rotation3::new(V3::y() * std::f32::consts::FRAC_PI_2 * self.angle_acc * 0.5);
         self.angle_acc += self.timer.elapsed().as_secs_f32();
         self.timer = std::time::Instant::now();
 
+        // Unit cube model spans 0.0 - 1.0 on every axis, rotate it around its center.
+        let centering = na::Translation3::new(-0.5, -0.5, -0.5);
+        let model = (x_rot * z_rot).to_homogeneous() * centering.to_homogeneous();
+        let view_projection = self.projection.as_matrix() * self.view.to_homogeneous();
+
+        // Lighting is computed in world space.
+        let uniforms = SceneUniforms::new(model, view_projection, self.eye, self.material, lighting);
+
         self.model_processing_buffer.clear();
+        for triangle in &self.triangles {
+            let face_centroid = P3::from((triangle.v0.coords + triangle.v1.coords + triangle.v2.coords) / 3.0);
+            for (vertex, normal) in triangle.vertices().zip(triangle.vertex_normals) {
+                self.model_processing_buffer.push(MeshVertex { position: vertex, normal, face_normal: triangle.normal, face_centroid });
+            }
+        }
 
-        let camera_direction = V3::new(
-            self.eye.x - self.target.x,
-            self.eye.y - self.target.y,
-            self.eye.z - self.target.z
-        );
-        let normalized_camera_direction = camera_direction.normalize();
+        pipeline.draw(bitmap, &uniforms, vertex_shader, fragment_shader, &self.model_processing_buffer)
+    }
 
-        for triangle in &mut self.triangles {
-            let mut normals = Vec::<P3>::with_capacity(3);
-            let mut points = Vec::<P3>::with_capacity(3);
+    /// Replaces vertex normals of all triangles with normals averaged over triangles sharing the vertex.
+    ///
+    /// Face normals are weighted by triangle area. Vertices are considered shared when their positions are equal.
+    #[allow(dead_code)]
+    pub fn smooth_normals(&mut self) {
+        let key = |point: &P3| [point.x.to_bits(), point.y.to_bits(), point.z.to_bits()];
+
+        let mut accumulated = std::collections::HashMap::<[u32; 3], V3>::new();
+        for triangle in &self.triangles {
+            let weighted = (triangle.v1 - triangle.v0).cross(&(triangle.v2 - triangle.v0));
             for vertex in triangle.vertices() {
-                let z_rotated = z_rot.transform_point(&vertex);
-                let xz_rotated = x_rot.transform_point(&z_rotated);
-                let moved = self.translation.transform_point(&xz_rotated);
-                normals.push(moved.clone());
-
-                let projected = self.projection.project_point(&moved);
-                let screen_trans = self.screen_space_translation.transform_point(&projected);
-                let screen_scaled = self.screen_space_scaling.transform_point(&screen_trans);
-                points.push(screen_scaled);
+                *accumulated.entry(key(&vertex)).or_insert_with(V3::zeros) += weighted;
             }
-            let normal = Triangle::normal_vector(&normals[0], &normals[1], &normals[2]);
-            self.model_processing_buffer.push(Triangle { v0: points[0], v1: points[1], v2: points[2], normal } );
         }
-        self.model_processing_buffer.sort_unstable_by(|triangle1, triangle2| {
-            ((triangle1.v0.z + triangle1.v1.z + triangle1.v2.z) / 3.0).partial_cmp(
-                &((triangle2.v0.z + triangle2.v1.z + triangle2.v2.z) / 3.0)).unwrap_or(Equal)
-            });
-        for triangle in &self.model_processing_buffer {
-            let normal = triangle.normal;
-            let color_coef = normal.dot(&normalized_camera_direction);
-            if color_coef < 0.0 {
-                let color = (
-                    (-color_coef * 255.0) as u8,
-                    (-color_coef * 255.0) as u8,
-                    0,
-                );
-                draw_triangle_outline(bitmap,
-                                     triangle.v0.x as i32,
-                                     triangle.v0.y as i32,
-                                     triangle.v1.x as i32,
-                                     triangle.v1.y as i32,
-                                     triangle.v2.x as i32,
-                                     triangle.v2.y as i32,
-                                     &(0,0,0));
-                draw_filled_triangle(bitmap,
-                                     triangle.v0.x as i32,
-                                     triangle.v0.y as i32,
-                                     triangle.v1.x as i32,
-                                     triangle.v1.y as i32,
-                                     triangle.v2.x as i32,
-                                     triangle.v2.y as i32,
-                                     &color);
-            }
+        for triangle in &mut self.triangles {
+            let normals = [triangle.v0, triangle.v1, triangle.v2].map(|vertex| accumulated[&key(&vertex)]);
+            triangle.set_vertex_normals(normals);
         }
     }
 }
@@ -431,20 +615,20 @@ impl Default for Mesh {
 
         Self::new(
             unit_cube_model,
-            color
+            Material::from_rgb(color)
         )
     }
 }
 
 
-pub fn draw_triangle_outline(bitmap: &mut Bitmap, x0: i32, y0: i32, x1: i32, y1: i32, x2: i32, y2: i32, color: &Rgb) {
+pub fn draw_triangle_outline<T: RenderTarget>(bitmap: &mut T, x0: i32, y0: i32, x1: i32, y1: i32, x2: i32, y2: i32, color: &Rgb) {
     draw_line(bitmap, x0, y0, x1, y1, &color);
     draw_line(bitmap, x0, y0, x2, y2, &color);
     draw_line(bitmap, x1, y1, x2, y2, &color);
 }
 
 
-pub fn draw_lines(bitmap: &mut Bitmap) {
+pub fn draw_lines<T: RenderTarget>(bitmap: &mut T) {
     let triangle = Triangle2D::from([
         na::Vector2::new(WIDTH / 2, HEIGHT / 4),
         na::Vector2::new(WIDTH / 4, 3 * HEIGHT / 4),
@@ -459,7 +643,7 @@ pub fn draw_lines(bitmap: &mut Bitmap) {
 }
 
 
-pub fn draw_filled_triangle(bitmap: &mut Bitmap, x0: i32, y0: i32, x1: i32, y1: i32, x2: i32, y2: i32, color: &Rgb) {
+pub fn draw_filled_triangle<T: RenderTarget>(bitmap: &mut T, x0: i32, y0: i32, x1: i32, y1: i32, x2: i32, y2: i32, color: &Rgb) {
     let mut triangle = Triangle2D::from([
         na::Vector2::new(x0, y0),
         na::Vector2::new(x1, y1),
@@ -468,3 +652,129 @@ pub fn draw_filled_triangle(bitmap: &mut Bitmap, x0: i32, y0: i32, x1: i32, y1:
 
     fill_triangle(bitmap, &mut triangle, &color);
 }
+
+
+/// Test suite for triangle rasterization and vertex normals.
+#[cfg(test)]
+mod test_rasterize {
+    use super::*;
+    use crate::Bitmap;
+
+    #[test]
+    fn coverage() {
+        let mut bitmap = Bitmap::new(8, 8);
+        bitmap.fill(&(0, 0, 0));
+        let (top_left, top_right, bottom_left, bottom_right) = (P3::new(2.0, 2.0, 0.0), P3::new(6.0, 2.0, 0.0), P3::new(2.0, 6.0, 0.0), P3::new(6.0, 6.0, 0.0));
+        let mut shaded = 0;
+        for (v0, v1, v2) in [(&top_left, &top_right, &bottom_right), (&top_left, &bottom_left, &bottom_right)] {
+            rasterize_triangle(&mut bitmap, v0, v1, v2, &[1.0; 3], |_| {
+                shaded += 1;
+                Some((255, 255, 255))
+            });
+        }
+        // The square is covered exactly, pixels centered on the shared diagonal are visited by one triangle only.
+        for (x, y, pixel) in bitmap.enumerate_pixels() {
+            assert_eq!(*pixel == Pixel::new(255, 255, 255), (2..6).contains(&x) && (2..6).contains(&y), "{} {}", x, y);
+        }
+        assert_eq!(shaded, 16);
+
+        // Parts outside of the bitmap are skipped.
+        let mut visited = 0;
+        rasterize_triangle(&mut bitmap, &P3::new(-10.0, -10.0, 0.0), &P3::new(30.0, -10.0, 0.0), &P3::new(-10.0, 30.0, 0.0), &[1.0; 3], |_| {
+            visited += 1;
+            None
+        });
+        assert_eq!(visited, 64);
+    }
+
+    #[test]
+    fn shared_edges() {
+        // Fan of two triangles covering the whole target, the shared edge ends far outside and passes right next to the center of (6, 5).
+        let (first, shared, last) = (P3::new(0.0, 10.0, 0.0), P3::new(32.499996, -12.499994, 0.0), P3::new(-22.499996, -12.499994, 0.0));
+        let mut visits = [[0; 10]; 10];
+        for (v0, v1, v2) in [(&first, &P3::new(10.0, 10.0, 0.0), &shared), (&first, &shared, &last)] {
+            walk_triangle(&Rect::new(0, 0, 10, 10), v0, v1, v2, |x, y, _| visits[y][x] += 1);
+        }
+        assert!(visits.iter().flatten().all(|&count| count == 1), "{:?}", visits);
+    }
+
+    #[test]
+    fn perspective_correction() {
+        assert_eq!(perspective_correct(&[0.5, 0.5, 0.0], &[1.0, 1.0, 1.0]), [0.5, 0.5, 0.0]);
+        // Halfway on screen between vertices at w of 1 and 3 is only a quarter of the way in world space.
+        assert_eq!(perspective_correct(&[0.5, 0.5, 0.0], &[1.0, 1.0 / 3.0, 1.0]), [0.75, 0.25, 0.0]);
+
+        let mut weights = Vec::new();
+        let mut bitmap = Bitmap::new(4, 1);
+        rasterize_triangle(&mut bitmap, &P3::new(0.0, 0.0, 0.0), &P3::new(4.0, 0.0, 0.0), &P3::new(0.0, 4.0, 0.0), &[1.0, 0.25, 1.0], |barycentric| {
+            weights.push(*barycentric);
+            None
+        });
+        // The last center lies on the right edge of the triangle.
+        assert_eq!(weights.len(), 3);
+        assert!(weights.iter().all(|weight| (weight.iter().sum::<f32>() - 1.0).abs() < 1e-6));
+        // Far vertex gets less weight than its screen space share.
+        assert!(weights[2][1] < 2.5 / 4.0 * 0.5);
+    }
+
+    #[test]
+    fn smooth_normals() {
+        // Two triangles folded at a right angle along the edge from the origin to (0, 1, 0).
+        let floor = Triangle::new(P3::new(0.0, 0.0, 0.0), P3::new(0.0, 1.0, 0.0), P3::new(1.0, 0.0, 0.0));
+        let wall = Triangle::new(P3::new(0.0, 0.0, 0.0), P3::new(0.0, 0.0, 1.0), P3::new(0.0, 1.0, 0.0));
+        let (floor_normal, wall_normal) = (*floor.normal(), *wall.normal());
+        let mut mesh = Mesh::new(vec![floor, wall], Material::default());
+        mesh.smooth_normals();
+
+        let shared = (floor_normal + wall_normal).normalize();
+        let [floor, wall] = &mesh.triangles[..] else { panic!() };
+        for (normal, expected) in floor.vertex_normals().iter().zip([shared, shared, floor_normal]) {
+            assert!((normal - expected).norm() < 1e-6, "{} {}", normal, expected);
+        }
+        for (normal, expected) in wall.vertex_normals().iter().zip([shared, wall_normal, shared]) {
+            assert!((normal - expected).norm() < 1e-6, "{} {}", normal, expected);
+        }
+    }
+}
+
+
+/// Test suite for axis aligned ellipses.
+#[cfg(test)]
+mod test_elipsis {
+    use super::*;
+    use crate::Bitmap;
+
+    fn drawn(bitmap: &Bitmap) -> Vec<(usize, usize)> {
+        bitmap.enumerate_pixels()
+            .filter(|(_, _, pixel)| **pixel == Pixel::new(255, 255, 255))
+            .map(|(x, y, _)| (x, y))
+            .collect()
+    }
+
+    #[test]
+    fn filled() {
+        let mut bitmap = Bitmap::new(10, 10);
+        fill_elipsis(&mut bitmap, 3, 2, 5, 5, &(255, 255, 255));
+        let filled = drawn(&bitmap);
+        // Three rows seven pixels wide with single pixels at the ends of the minor axis.
+        assert_eq!(filled.len(), 3 * 7 + 2);
+        assert!(filled.iter().all(|(x, y)| (2..=8).contains(x) && (3..=7).contains(y)));
+        assert!(filled.contains(&(5, 3)) && filled.contains(&(5, 7)) && !filled.contains(&(4, 3)));
+
+        // Ellipse centered in the corner is clipped to the bitmap.
+        let mut bitmap = Bitmap::new(10, 10);
+        fill_elipsis(&mut bitmap, 3, 2, 0, 0, &(255, 255, 255));
+        assert_eq!(drawn(&bitmap).len(), 4 + 4 + 1);
+    }
+
+    #[test]
+    fn outline() {
+        let mut bitmap = Bitmap::new(10, 10);
+        draw_elipsis(&mut bitmap, 3, 2, 5, 5, &(255, 255, 255));
+        let outline = drawn(&bitmap);
+        for point in [(2, 5), (8, 5), (5, 3), (5, 7)] {
+            assert!(outline.contains(&point), "{:?}", point);
+        }
+        assert!(!outline.contains(&(5, 5)));
+    }
+}
@@ -0,0 +1,232 @@
+use std::io::Write;
+
+use crate::color::LinearColor;
+use crate::format::{RgbaF32, Surface};
+use crate::view::RenderTarget;
+
+
+/// Float render target accumulating linear light without clipping, displayed after tone mapping.
+pub type HdrBuffer = Surface<RgbaF32>;
+
+
+/// Operator compressing high dynamic range colors into the displayable 0.0 - 1.0 range.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ToneMapping {
+    /// Values above 1.0 are clipped, the same as rendering directly into a Bitmap.
+    Clamp,
+    /// Reinhard operator applied to luminance, which keeps hues of bright colors.
+    ///
+    /// `white` is the smallest luminance mapped to full white, infinity gives the basic operator
+    /// which approaches white only asymptotically. Channels of saturated colors still above 1.0 are clipped.
+    Reinhard { white: f32 },
+    /// Fit of the ACES filmic curve by Krzysztof Narkowicz, with a toe in the shadows and desaturated highlights.
+    AcesFilmic,
+}
+
+
+impl ToneMapping {
+    /// Returns the next operator, used for cycling through all of them.
+    pub fn next(&self) -> Self {
+        match self {
+            ToneMapping::Clamp => ToneMapping::Reinhard { white: 4.0 },
+            ToneMapping::Reinhard { .. } => ToneMapping::AcesFilmic,
+            ToneMapping::AcesFilmic => ToneMapping::Clamp,
+        }
+    }
+
+    /// Maps linear color to the displayable range, alfa is kept.
+    pub fn map(&self, color: LinearColor) -> LinearColor {
+        match *self {
+            ToneMapping::Clamp => LinearColor::from_rgba(color.to_rgba().map(|channel| channel.clamp(0.0, 1.0))).with_alfa(color.alfa),
+            ToneMapping::Reinhard { white } => {
+                let luminance = color.luminance();
+                if luminance <= 0.0 {
+                    return LinearColor::new(0.0, 0.0, 0.0).with_alfa(color.alfa);
+                }
+                let mapped = (luminance * (1.0 + luminance / (white * white)) / (1.0 + luminance)).min(1.0);
+                // Saturated colors can still have a channel above 1.0 after scaling.
+                let scaled = color * (mapped / luminance);
+                LinearColor::from_rgba(scaled.to_rgba().map(|channel| channel.min(1.0))).with_alfa(color.alfa)
+            },
+            ToneMapping::AcesFilmic => {
+                let curve = |x: f32| {
+                    let x = x.max(0.0);
+                    ((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)).clamp(0.0, 1.0)
+                };
+                LinearColor::new(curve(color.red), curve(color.green), curve(color.blue)).with_alfa(color.alfa)
+            },
+        }
+    }
+}
+
+
+/// Exposure control followed by tone mapping, converts HDR buffers into displayable images.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ToneMapper {
+    pub operator: ToneMapping,
+    /// Exposure adjustment in stops, every stop doubles the brightness.
+    pub exposure: f32,
+}
+
+
+impl Default for ToneMapper {
+    fn default() -> Self {
+        Self { operator: ToneMapping::AcesFilmic, exposure: 0.0 }
+    }
+}
+
+
+impl ToneMapper {
+    pub const fn new(operator: ToneMapping, exposure: f32) -> Self {
+        Self { operator, exposure }
+    }
+
+    /// Applies exposure and tone mapping to a single color.
+    pub fn map(&self, color: LinearColor) -> LinearColor {
+        let scale = self.exposure.exp2();
+        self.operator.map(LinearColor { alfa: color.alfa, ..color * scale })
+    }
+
+    /// Writes tone mapped contents of the buffer into the target, starting at its top left corner.
+    ///
+    /// Parts of the buffer that do not fit into the target are skipped.
+    pub fn apply<T: RenderTarget>(&self, hdr: &HdrBuffer, target: &mut T) {
+        let width = hdr.width().min(target.width());
+        let height = hdr.height().min(target.height());
+        for (y, row) in hdr.pixels().chunks_exact(hdr.width().max(1)).take(height).enumerate() {
+            for (x, color) in row[..width].iter().enumerate() {
+                target.set_color(x, y, self.map(*color));
+            }
+        }
+    }
+}
+
+
+impl Surface<RgbaF32> {
+    /// Writes the buffer in Radiance RGBE format, usually saved with `.hdr` extension.
+    ///
+    /// Scanlines are stored flat without run length encoding, alfa is dropped.
+    pub fn write_hdr<W: Write>(&self, writer: &mut W) -> Result<(), std::io::Error> {
+        write!(writer, "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", self.height(), self.width())?;
+        let bytes: Vec<u8> = self.pixels().iter().flat_map(rgbe).collect();
+        writer.write_all(&bytes)
+    }
+
+    /// Writes the buffer in Portable Float Map format, with 32 bit little endian floats and alfa dropped.
+    pub fn write_pfm<W: Write>(&self, writer: &mut W) -> Result<(), std::io::Error> {
+        // Negative scale marks little endian data.
+        write!(writer, "PF\n{} {}\n-1.0\n", self.width(), self.height())?;
+        let mut bytes = Vec::with_capacity(self.pixels().len() * 12);
+        // Rows are stored from the bottom to the top.
+        for row in self.pixels().chunks_exact(self.width().max(1)).rev() {
+            for color in row {
+                for channel in [color.red, color.green, color.blue] {
+                    bytes.extend_from_slice(&channel.to_le_bytes());
+                }
+            }
+        }
+        writer.write_all(&bytes)
+    }
+
+    pub fn save_hdr(&self, path: &str) -> Result<(), std::io::Error> {
+        let mut file_handle = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_hdr(&mut file_handle)
+    }
+
+    pub fn save_pfm(&self, path: &str) -> Result<(), std::io::Error> {
+        let mut file_handle = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_pfm(&mut file_handle)
+    }
+}
+
+
+/// Encodes color as three mantissas sharing the exponent of the largest channel.
+///
+/// Channels too large for the format, including infinite ones, get the largest representable value.
+fn rgbe(color: &RgbaF32) -> [u8; 4] {
+    let largest = color.red.max(color.green).max(color.blue).min(f32::MAX);
+    if largest.is_nan() || largest < 1e-32 {
+        return [0; 4];
+    }
+    // Exponent such that largest / 2^exponent lies in 0.5 - 1.0.
+    let mut exponent = largest.log2().floor() as i32 + 1;
+    if largest * 2f32.powi(8 - exponent) >= 256.0 {
+        exponent += 1;
+    }
+    // Exponent byte holds at most 127, mantissas of larger values saturate instead.
+    let exponent = exponent.min(127);
+    let scale = 2f32.powi(8 - exponent);
+    let mantissa = |channel: f32| (channel.max(0.0) * scale) as u8;
+    [mantissa(color.red), mantissa(color.green), mantissa(color.blue), (exponent + 128).clamp(0, 255) as u8]
+}
+
+
+/// Test suite for tone mapping and HDR export.
+#[cfg(test)]
+mod test_hdr {
+    use super::*;
+    use crate::Bitmap;
+    use crate::Pixel;
+
+    const OPERATORS: [ToneMapping; 4] = [ToneMapping::Clamp, ToneMapping::Reinhard { white: f32::INFINITY }, ToneMapping::Reinhard { white: 4.0 }, ToneMapping::AcesFilmic];
+
+    #[test]
+    fn operators_stay_in_range() {
+        for operator in OPERATORS {
+            let mut previous = -1.0;
+            for step in 0..=100 {
+                let value = step as f32 * 0.5;
+                let mapped = operator.map(LinearColor::new(value, value, value));
+                assert!((0.0..=1.0).contains(&mapped.red), "{:?} {}", operator, value);
+                assert!(mapped.red >= previous - 1e-6, "{:?} is not monotonic", operator);
+                previous = mapped.red;
+            }
+        }
+        assert!((ToneMapping::Reinhard { white: 4.0 }.map(LinearColor::new(4.0, 4.0, 4.0)).red - 1.0).abs() < 1e-5);
+        assert_eq!(ToneMapping::Reinhard { white: f32::INFINITY }.map(LinearColor::new(1.0, 1.0, 1.0)).red, 0.5);
+
+        // Reinhard keeps the ratio of channels.
+        let orange = ToneMapping::Reinhard { white: f32::INFINITY }.map(LinearColor::new(0.8, 0.4, 0.0));
+        assert!((orange.red / orange.green - 2.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn exposure() {
+        let mapper = ToneMapper::new(ToneMapping::Clamp, -2.0);
+        assert_eq!(mapper.map(LinearColor::new(2.0, 1.0, 0.5)), LinearColor::new(0.5, 0.25, 0.125));
+
+        let mut hdr = HdrBuffer::filled(3, 2, RgbaF32::new(3.0, 1.0, 0.0));
+        let mut bitmap = Bitmap::new(2, 4);
+        ToneMapper::new(ToneMapping::Clamp, 0.0).apply(&hdr, &mut bitmap);
+        assert_eq!(bitmap.get_pixel(1, 1), Some(Pixel::new(255, 255, 0)));
+        assert_eq!(bitmap.get_pixel(1, 2), Some(Pixel::default()));
+
+        hdr.resize(1, 1);
+        assert_eq!(hdr.pixels(), &[RgbaF32::default()]);
+    }
+
+    #[test]
+    fn export() {
+        let mut hdr = HdrBuffer::new(2, 1);
+        hdr.pixels_mut().copy_from_slice(&[RgbaF32::new(1.0, 0.5, 0.25), RgbaF32::new(1000.0, 0.0, 0.0)]);
+
+        let mut radiance = Vec::new();
+        hdr.write_hdr(&mut radiance).unwrap();
+        let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 2\n";
+        assert_eq!(&radiance[..header.len()], header);
+        assert_eq!(&radiance[header.len()..], &[128, 64, 32, 129, 250, 0, 0, 138]);
+
+        let mut pfm = Vec::new();
+        hdr.write_pfm(&mut pfm).unwrap();
+        let header = b"PF\n2 1\n-1.0\n";
+        assert_eq!(&pfm[..header.len()], header);
+        assert_eq!(pfm.len(), header.len() + 2 * 12);
+        assert_eq!(&pfm[header.len() + 12..header.len() + 16], &1000.0f32.to_le_bytes());
+
+        // Unbounded effects may produce infinite values.
+        hdr.pixels_mut().copy_from_slice(&[RgbaF32::new(f32::INFINITY, 1.0, 0.0), RgbaF32::new(f32::MAX, f32::MAX / 2.0, f32::NAN)]);
+        let mut radiance = Vec::new();
+        hdr.write_hdr(&mut radiance).unwrap();
+        assert_eq!(&radiance[radiance.len() - 8..], &[255, 0, 0, 255, 255, 255, 0, 255]);
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::Pixel;
+use crate::bitmap::Rect;
+use crate::pipeline::PipelineStats;
+use crate::text::{Font, TextStyle, draw_text_clipped, measure_text};
+use crate::view::RenderTarget;
+
+type Rgb = (u8, u8, u8);
+
+/// Number of past frames shown in the graphs.
+const HISTORY: usize = 120;
+/// Distance of the overlay from the edges of the bitmap and between its parts, in pixels.
+const MARGIN: i32 = 6;
+const GRAPH_HEIGHT: i32 = 40;
+
+const PANEL_COLOR: Pixel = Pixel::new(0, 0, 0).with_alfa(160);
+const TEXT_COLOR: Rgb = (255, 255, 255);
+const FRAME_TIME_COLOR: Rgb = (80, 220, 80);
+const SLOW_FRAME_COLOR: Rgb = (230, 70, 50);
+const PIXELS_COLOR: Rgb = (90, 160, 255);
+const REFERENCE_COLOR: Rgb = (140, 140, 140);
+
+/// Frame time of 60 frames per second, frames taking longer are drawn as slow.
+const TARGET_FRAME_TIME: f32 = 1000.0 / 60.0;
+
+
+/// Statistics of a single frame.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct FrameStats {
+    /// Time since the start of the previous frame.
+    pub frame_time: Duration,
+    /// Sum of all draw calls of the frame.
+    pub pipeline: PipelineStats,
+}
+
+
+/// Immediate mode debug overlay showing frame time and pipeline statistics as text and graphs.
+///
+/// Every frame call `begin_frame`, `record` statistics of all draw calls and `draw` the overlay on top of the rendered image.
+pub struct DebugHud {
+    pub visible: bool,
+    font: Font,
+    /// Statistics of the last frames, the newest at the back.
+    history: VecDeque<FrameStats>,
+    frame_start: Instant,
+}
+
+
+impl Default for DebugHud {
+    fn default() -> Self {
+        Self { visible: true, font: Font::embedded(), history: VecDeque::with_capacity(HISTORY), frame_start: Instant::now() }
+    }
+}
+
+
+impl DebugHud {
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    /// Starts collecting statistics of a new frame.
+    pub fn begin_frame(&mut self) {
+        let now = Instant::now();
+        if self.history.len() == HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(FrameStats { frame_time: now - self.frame_start, ..Default::default() });
+        self.frame_start = now;
+    }
+
+    /// Adds statistics of a draw call to the current frame.
+    pub fn record(&mut self, stats: PipelineStats) {
+        if let Some(frame) = self.history.back_mut() {
+            frame.pipeline += stats;
+        }
+    }
+
+    /// Statistics of the current frame.
+    pub fn current(&self) -> FrameStats {
+        self.history.back().copied().unwrap_or_default()
+    }
+
+    /// Average number of frames per second over the history.
+    pub fn frames_per_second(&self) -> f32 {
+        let total: Duration = self.history.iter().map(|frame| frame.frame_time).sum();
+        if total.is_zero() {
+            0.0
+        } else {
+            self.history.len() as f32 / total.as_secs_f32()
+        }
+    }
+
+    /// Draws the overlay in the top left corner of the bitmap, if it is visible.
+    pub fn draw<T: RenderTarget>(&self, bitmap: &mut T) {
+        if !self.visible {
+            return;
+        }
+        let text = self.text();
+        let style = TextStyle { color: TEXT_COLOR, ..Default::default() };
+        let (text_width, text_height) = measure_text(&self.font, &text, &style);
+        let graph_width = (HISTORY as i32).max(text_width as i32);
+
+        let panel = Rect::new(MARGIN, MARGIN, graph_width + 2 * MARGIN, text_height as i32 + 2 * GRAPH_HEIGHT + 4 * MARGIN);
+        let panel = panel.intersection(&bitmap.rect());
+        for y in panel.y..panel.bottom() {
+            for x in panel.x..panel.right() {
+                bitmap.blend_pixel(x as usize, y as usize, PANEL_COLOR);
+            }
+        }
+
+        let (left, top) = (panel.x + MARGIN, panel.y + MARGIN);
+        draw_text_clipped(bitmap, &self.font, &text, left, top, &style, &panel);
+
+        let frame_times: Vec<f32> = self.history.iter().map(|frame| frame.frame_time.as_secs_f32() * 1000.0).collect();
+        let frame_graph = Rect::new(left, top + text_height as i32 + MARGIN, graph_width, GRAPH_HEIGHT).intersection(&panel);
+        draw_graph(bitmap, &frame_graph, &frame_times, 2.0 * TARGET_FRAME_TIME, TARGET_FRAME_TIME, |time| {
+            if time > TARGET_FRAME_TIME { SLOW_FRAME_COLOR } else { FRAME_TIME_COLOR }
+        });
+
+        let pixels: Vec<f32> = self.history.iter().map(|frame| frame.pipeline.pixels_shaded as f32).collect();
+        let most_pixels = pixels.iter().copied().fold(1.0, f32::max);
+        let pixels_graph = Rect::new(left, frame_graph.bottom() + MARGIN, graph_width, GRAPH_HEIGHT).intersection(&panel);
+        draw_graph(bitmap, &pixels_graph, &pixels, most_pixels, most_pixels / 2.0, |_| PIXELS_COLOR);
+    }
+
+    fn text(&self) -> String {
+        let frame = self.current();
+        let stats = &frame.pipeline;
+        let milliseconds = |duration: Duration| duration.as_secs_f32() * 1000.0;
+        format!(
+            "FPS {:.1} frame {:.2} ms\n\
+             triangles {} submitted\n\
+             {} clipped {} culled {} drawn\n\
+             pixels shaded {}\n\
+             vertex {:.2} ms sort {:.2} ms\n\
+             raster {:.2} ms",
+            self.frames_per_second(),
+            milliseconds(frame.frame_time),
+            stats.triangles_submitted,
+            stats.triangles_clipped,
+            stats.triangles_culled,
+            stats.triangles_drawn,
+            stats.pixels_shaded,
+            milliseconds(stats.vertex_time),
+            milliseconds(stats.sort_time),
+            milliseconds(stats.raster_time),
+        )
+    }
+}
+
+
+/// Draws values as vertical bars, the newest at the right edge of the area.
+///
+/// Bars are scaled so that `max` fills the whole height, a line marks the `reference` value.
+fn draw_graph<T: RenderTarget, F: Fn(f32) -> Rgb>(bitmap: &mut T, area: &Rect, values: &[f32], max: f32, reference: f32, color: F) {
+    if area.is_empty() || max <= 0.0 {
+        return;
+    }
+    let height = |value: f32| ((value / max).clamp(0.0, 1.0) * area.height as f32).round() as i32;
+
+    for (index, &value) in values.iter().rev().enumerate().take(area.width as usize) {
+        let x = area.right() - 1 - index as i32;
+        let pixel = Pixel::from_rgb_tuple(color(value));
+        for y in area.bottom() - height(value)..area.bottom() {
+            bitmap.set_pixel(x as usize, y as usize, pixel);
+        }
+    }
+
+    let reference_y = (area.bottom() - height(reference)).min(area.bottom() - 1);
+    let pixel = Pixel::from_rgb_tuple(REFERENCE_COLOR);
+    for x in (area.x..area.right()).step_by(2) {
+        bitmap.set_pixel(x as usize, reference_y as usize, pixel);
+    }
+}
+
+
+/// Test suite for the debug overlay.
+#[cfg(test)]
+mod test_hud {
+    use super::*;
+    use crate::Bitmap;
+
+    #[test]
+    fn history() {
+        let mut hud = DebugHud::default();
+        for _ in 0..HISTORY + 10 {
+            hud.begin_frame();
+            hud.record(PipelineStats { pixels_shaded: 10, ..Default::default() });
+            hud.record(PipelineStats { pixels_shaded: 5, ..Default::default() });
+        }
+        assert_eq!(hud.history.len(), HISTORY);
+        assert_eq!(hud.current().pipeline.pixels_shaded, 15);
+
+        // Drawing must stay inside of bitmaps too small for the whole overlay.
+        hud.draw(&mut Bitmap::new(50, 30));
+    }
+}
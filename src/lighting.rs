@@ -0,0 +1,307 @@
+use nalgebra as na;
+
+use crate::color::{linear_to_srgb, srgb_to_linear};
+
+type P3 = na::Point3<f32>;
+type V3 = na::Vector3<f32>;
+type Rgb = (u8, u8, u8);
+
+
+/// Distance attenuation of positional lights.
+///
+/// Intensity at distance `d` is divided by `constant + linear * d + quadratic * d^2`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Attenuation {
+    pub constant: f32,
+    pub linear: f32,
+    pub quadratic: f32,
+}
+
+
+impl Attenuation {
+    pub const fn new(constant: f32, linear: f32, quadratic: f32) -> Self {
+        Self { constant, linear, quadratic }
+    }
+
+    /// Attenuation that does not depend on distance.
+    pub const fn none() -> Self {
+        Self::new(1.0, 0.0, 0.0)
+    }
+
+    /// Returns the factor by which light intensity is scaled at given distance.
+    pub fn factor(&self, distance: f32) -> f32 {
+        let denominator = self.constant + self.linear * distance + self.quadratic * distance * distance;
+        if denominator > 0.0 { 1.0 / denominator } else { 1.0 }
+    }
+}
+
+
+impl Default for Attenuation {
+    fn default() -> Self {
+        Self::new(1.0, 0.09, 0.032)
+    }
+}
+
+
+/// Light source.
+///
+/// Colors are linear RGB triples in range 0.0 - 1.0, scaled by `intensity`.
+/// Directions point from the light towards the scene.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Light {
+    /// Constant light that reaches every surface regardless of its orientation.
+    Ambient {
+        color: V3,
+        intensity: f32,
+    },
+    /// Light infinitely far away, like the sun.
+    Directional {
+        direction: V3,
+        color: V3,
+        intensity: f32,
+    },
+    /// Light emitted equally in all directions from a single point.
+    Point {
+        position: P3,
+        color: V3,
+        intensity: f32,
+        attenuation: Attenuation,
+    },
+    /// Point light restricted to a cone.
+    ///
+    /// Angles are half-angles of the cone in radians. Light is at full strength inside `inner_angle`
+    /// and fades out smoothly until `outer_angle`.
+    Spot {
+        position: P3,
+        direction: V3,
+        color: V3,
+        intensity: f32,
+        attenuation: Attenuation,
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+}
+
+
+impl Light {
+    pub fn ambient(color: V3, intensity: f32) -> Self {
+        Self::Ambient { color, intensity }
+    }
+
+    pub fn directional(direction: V3, color: V3, intensity: f32) -> Self {
+        Self::Directional { direction: direction.normalize(), color, intensity }
+    }
+
+    pub fn point(position: P3, color: V3, intensity: f32, attenuation: Attenuation) -> Self {
+        Self::Point { position, color, intensity, attenuation }
+    }
+
+    pub fn spot(position: P3, direction: V3, color: V3, intensity: f32, attenuation: Attenuation, inner_angle: f32, outer_angle: f32) -> Self {
+        Self::Spot { position, direction: direction.normalize(), color, intensity, attenuation, inner_angle, outer_angle }
+    }
+
+    /// Returns unit vector pointing from the surface towards the light and the light's radiance at that point.
+    ///
+    /// Ambient lights have no direction, for them `None` is returned. So is it for point and spot lights
+    /// placed exactly at the surface, where the direction is undefined and the light does not contribute.
+    fn incident(&self, position: &P3) -> Option<(V3, V3)> {
+        match *self {
+            Light::Ambient { .. } => None,
+            Light::Directional { direction, color, intensity } => {
+                Some((-direction, color * intensity))
+            },
+            Light::Point { position: light_position, color, intensity, attenuation } => {
+                let to_light = light_position - position;
+                let distance = to_light.norm();
+                if distance <= f32::EPSILON {
+                    return None;
+                }
+                Some((to_light / distance, color * intensity * attenuation.factor(distance)))
+            },
+            Light::Spot { position: light_position, direction, color, intensity, attenuation, inner_angle, outer_angle } => {
+                let to_light = light_position - position;
+                let distance = to_light.norm();
+                if distance <= f32::EPSILON {
+                    return None;
+                }
+                let to_light = to_light / distance;
+
+                let cos_theta = (-to_light).dot(&direction);
+                let (cos_inner, cos_outer) = (inner_angle.cos(), outer_angle.cos());
+                let cone = if cos_inner - cos_outer > f32::EPSILON {
+                    smoothstep(cos_outer, cos_inner, cos_theta)
+                } else if cos_theta >= cos_outer { 1.0 } else { 0.0 };
+
+                Some((to_light, color * intensity * attenuation.factor(distance) * cone))
+            },
+        }
+    }
+}
+
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+
+/// Where the lighting model is evaluated.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Shading {
+    /// Once per triangle using the face normal.
+    Flat,
+    /// Once per pixel using interpolated vertex normals.
+    Phong,
+}
+
+
+/// Surface properties used by the Blinn-Phong lighting model.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Material {
+    /// Color of diffusely reflected light, also used for the ambient term.
+    pub diffuse: V3,
+    /// Color of specular highlights.
+    pub specular: V3,
+    /// Specular exponent, the bigger the value the smaller and sharper the highlight.
+    pub shininess: f32,
+    pub shading: Shading,
+}
+
+
+impl Material {
+    pub fn new(diffuse: V3, specular: V3, shininess: f32) -> Self {
+        Self { diffuse, specular, shininess, shading: Shading::Flat }
+    }
+
+    pub fn with_shading(self, shading: Shading) -> Self {
+        Self { shading, ..self }
+    }
+
+    /// Creates material with diffuse color specified as 8 bit sRGB values and a dim white highlight.
+    pub fn from_rgb(color: Rgb) -> Self {
+        Self::new(rgb_to_vector(color), V3::repeat(0.5), 32.0)
+    }
+}
+
+
+impl Default for Material {
+    fn default() -> Self {
+        Self::from_rgb((200, 200, 200))
+    }
+}
+
+
+/// Set of lights illuminating the scene.
+///
+/// All positions and directions, both of the lights and of the shaded surfaces,
+/// have to be expressed in the same space. The renderer uses world space.
+#[derive(Clone, Debug, Default)]
+pub struct Lighting {
+    pub lights: Vec<Light>,
+}
+
+
+impl Lighting {
+    pub fn new(lights: Vec<Light>) -> Self {
+        Self { lights }
+    }
+
+    pub fn add(&mut self, light: Light) {
+        self.lights.push(light);
+    }
+
+    /// Computes color of the surface point using Lambert diffuse and Blinn-Phong specular terms.
+    ///
+    /// `normal` has to be normalized. Result is linear RGB and may exceed 1.0.
+    pub fn shade(&self, material: &Material, position: &P3, normal: &V3, eye: &P3) -> V3 {
+        let to_eye = (eye - position).normalize();
+        let mut color = V3::zeros();
+
+        for light in &self.lights {
+            match light.incident(position) {
+                None => {
+                    if let Light::Ambient { color: light_color, intensity } = light {
+                        color += material.diffuse.component_mul(light_color) * *intensity;
+                    }
+                },
+                Some((to_light, radiance)) => {
+                    let lambert = normal.dot(&to_light);
+                    if lambert <= 0.0 {
+                        continue;
+                    }
+                    let half_vector = (to_light + to_eye).normalize();
+                    let specular = normal.dot(&half_vector).max(0.0).powf(material.shininess);
+
+                    color += material.diffuse.component_mul(&radiance) * lambert;
+                    color += material.specular.component_mul(&radiance) * specular;
+                },
+            }
+        }
+        color
+    }
+}
+
+
+/// Decodes 8 bit sRGB triple into vector of linear components in range 0.0 - 1.0.
+pub fn rgb_to_vector(color: Rgb) -> V3 {
+    V3::new(srgb_to_linear(color.0), srgb_to_linear(color.1), srgb_to_linear(color.2))
+}
+
+
+/// Encodes vector of linear components in range 0.0 - 1.0 into 8 bit sRGB triple, clamping out of range values.
+pub fn vector_to_rgb(color: &V3) -> Rgb {
+    (linear_to_srgb(color.x), linear_to_srgb(color.y), linear_to_srgb(color.z))
+}
+
+
+/// Test suite for lighting model.
+#[cfg(test)]
+mod test_lighting {
+    use super::*;
+
+    #[test]
+    fn attenuation_none_is_constant() {
+        assert_eq!(Attenuation::none().factor(0.0), 1.0);
+        assert_eq!(Attenuation::none().factor(100.0), 1.0);
+    }
+
+    #[test]
+    fn directional_light_lambert() {
+        let lighting = Lighting::new(vec![Light::directional(-V3::z(), V3::repeat(1.0), 1.0)]);
+        let material = Material::new(V3::repeat(1.0), V3::zeros(), 1.0);
+        let eye = P3::new(0.0, 0.0, 10.0);
+
+        let facing = lighting.shade(&material, &P3::origin(), &V3::z(), &eye);
+        assert!((facing.x - 1.0).abs() < 1e-6);
+
+        let away = lighting.shade(&material, &P3::origin(), &-V3::z(), &eye);
+        assert_eq!(away, V3::zeros());
+    }
+
+    #[test]
+    fn spot_light_cone() {
+        let spot = Light::spot(P3::new(0.0, 0.0, 1.0), -V3::z(), V3::repeat(1.0), 1.0, Attenuation::none(), 0.1, 0.2);
+        let lighting = Lighting::new(vec![spot]);
+        let material = Material::new(V3::repeat(1.0), V3::zeros(), 1.0);
+        let eye = P3::new(0.0, 0.0, 10.0);
+
+        let inside = lighting.shade(&material, &P3::origin(), &V3::z(), &eye);
+        assert!((inside.x - 1.0).abs() < 1e-6);
+
+        let outside = lighting.shade(&material, &P3::new(1.0, 0.0, 0.0), &V3::z(), &eye);
+        assert_eq!(outside, V3::zeros());
+    }
+
+    #[test]
+    fn light_at_surface() {
+        let attenuation = Attenuation::default();
+        let lighting = Lighting::new(vec![
+            Light::point(P3::origin(), V3::repeat(1.0), 1.0, attenuation),
+            Light::spot(P3::origin(), -V3::z(), V3::repeat(1.0), 1.0, attenuation, 0.1, 0.2),
+            Light::ambient(V3::repeat(1.0), 0.5),
+        ]);
+        let material = Material::new(V3::repeat(1.0), V3::repeat(1.0), 8.0);
+        let color = lighting.shade(&material, &P3::origin(), &V3::z(), &P3::new(0.0, 0.0, 10.0));
+        assert_eq!(color, V3::repeat(0.5));
+    }
+}
@@ -0,0 +1,602 @@
+use crate::Pixel;
+use crate::view::RenderTarget;
+use crate::bitmap::Rect;
+
+type Rgb = (u8, u8, u8);
+
+
+/// Shape of the line ends.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LineCap {
+    /// Line ends exactly at its end points.
+    Butt,
+    /// Line is extended past its end points by half of its width with a semicircle.
+    Round,
+    /// Line is extended past its end points by half of its width.
+    Square,
+}
+
+
+/// Describes how lines are stroked.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Stroke {
+    pub width: f32,
+    pub cap: LineCap,
+    /// Whether edges of the line are smoothed by blending partially covered pixels.
+    pub antialiased: bool,
+}
+
+
+impl Stroke {
+    pub fn new(width: f32, cap: LineCap, antialiased: bool) -> Self {
+        Self { width, cap, antialiased }
+    }
+}
+
+
+impl Default for Stroke {
+    fn default() -> Self {
+        Self::new(1.0, LineCap::Butt, false)
+    }
+}
+
+
+/// Blends color into the pixel with given coverage, pixels outside of the bitmap are skipped.
+fn plot<T: RenderTarget>(bitmap: &mut T, x: i32, y: i32, color: &Rgb, coverage: f32) {
+    if !bitmap.rect().contains(x, y) || coverage <= 0.0 {
+        return;
+    }
+    let alfa = (coverage.min(1.0) * 255.0).round() as u8;
+    bitmap.blend_pixel(x as usize, y as usize, Pixel::from_rgb_tuple(*color).with_alfa(alfa));
+}
+
+
+/// Fractional part of the number.
+fn fpart(x: f32) -> f32 {
+    x - x.floor()
+}
+
+
+fn rfpart(x: f32) -> f32 {
+    1.0 - fpart(x)
+}
+
+
+/// Clips segment to the rectangle using Liang-Barsky algorithm.
+///
+/// Rectangle spans from its `min` to its `max` corner. Returns range of the segment parameter,
+/// 0.0 being the `start` and 1.0 the `end` point, that lies inside the rectangle.
+pub(crate) fn clip_segment(start: (f64, f64), end: (f64, f64), min: (f64, f64), max: (f64, f64)) -> Option<(f64, f64)> {
+    let ((x0, y0), (x1, y1)) = (start, end);
+    let ((x_min, y_min), (x_max, y_max)) = (min, max);
+    let (dx, dy) = (x1 - x0, y1 - y0);
+    let (mut t0, mut t1) = (0.0f64, 1.0f64);
+    for (p, q) in [(-dx, x0 - x_min), (dx, x_max - x0), (-dy, y0 - y_min), (dy, y_max - y0)] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let t = q / p;
+            if p < 0.0 {
+                t0 = t0.max(t);
+            } else {
+                t1 = t1.min(t);
+            }
+        }
+    }
+    if t0 <= t1 { Some((t0, t1)) } else { None }
+}
+
+
+/// Calls `visit` with coordinates and coverage of every pixel of one pixel wide anti-aliased line.
+///
+/// Implementation of Xiaolin Wu's algorithm. Like everywhere else pixel centers lie at half integer coordinates,
+/// so line from (0.0, 0.5) to (10.0, 0.5) fully covers first ten pixels of the first row. Pixels outside of `clip` are skipped.
+pub fn rasterize_line_aa<F: FnMut(i32, i32, f32)>(x0: f32, y0: f32, x1: f32, y1: f32, clip: &Rect, mut visit: F) {
+    // Wu's algorithm places pixel centers at integer coordinates.
+    let (x0, y0, x1, y1) = (x0 - 0.5, y0 - 0.5, x1 - 0.5, y1 - 0.5);
+
+    // Skip the parts of the line far outside of the clipping rectangle, margin keeps the end points' blending intact.
+    let min = (clip.x as f64 - 2.0, clip.y as f64 - 2.0);
+    let max = (clip.right() as f64 + 1.0, clip.bottom() as f64 + 1.0);
+    let (t0, t1) = match clip_segment((x0 as f64, y0 as f64), (x1 as f64, y1 as f64), min, max) {
+        Some((t0, t1)) => (t0 as f32, t1 as f32),
+        None => return,
+    };
+    let (dx, dy) = (x1 - x0, y1 - y0);
+    let (mut x0, mut y0, mut x1, mut y1) = (x0 + dx * t0, y0 + dy * t0, x0 + dx * t1, y0 + dy * t1);
+
+    let steep = (y1 - y0).abs() > (x1 - x0).abs();
+    if steep {
+        std::mem::swap(&mut x0, &mut y0);
+        std::mem::swap(&mut x1, &mut y1);
+    }
+    if x0 > x1 {
+        std::mem::swap(&mut x0, &mut x1);
+        std::mem::swap(&mut y0, &mut y1);
+    }
+
+    let mut plot_steep = |x: i32, y: i32, coverage: f32| {
+        let (x, y) = if steep { (y, x) } else { (x, y) };
+        if coverage > 0.0 && clip.contains(x, y) {
+            visit(x, y, coverage);
+        }
+    };
+
+    let dx = x1 - x0;
+    let dy = y1 - y0;
+    let gradient = if dx == 0.0 { 1.0 } else { dy / dx };
+
+    // Both end points fall into the same column, which is covered only along the length of the line.
+    if (x0 + 0.5).floor() == (x1 + 0.5).floor() {
+        let x_end = (x0 + 0.5).floor();
+        let y_end = y0 + gradient * (x_end - x0);
+        let x_gap = x1 - x0;
+        plot_steep(x_end as i32, y_end.floor() as i32, rfpart(y_end) * x_gap);
+        plot_steep(x_end as i32, y_end.floor() as i32 + 1, fpart(y_end) * x_gap);
+        return;
+    }
+
+    // First end point.
+    let x_end = (x0 + 0.5).floor();
+    let y_end = y0 + gradient * (x_end - x0);
+    let x_gap = rfpart(x0 + 0.5);
+    let x_pixel1 = x_end as i32;
+    let y_pixel1 = y_end.floor() as i32;
+    plot_steep(x_pixel1, y_pixel1, rfpart(y_end) * x_gap);
+    plot_steep(x_pixel1, y_pixel1 + 1, fpart(y_end) * x_gap);
+    let mut intersection_y = y_end + gradient;
+
+    // Second end point.
+    let x_end = (x1 + 0.5).floor();
+    let y_end = y1 + gradient * (x_end - x1);
+    let x_gap = fpart(x1 + 0.5);
+    let x_pixel2 = x_end as i32;
+    let y_pixel2 = y_end.floor() as i32;
+    plot_steep(x_pixel2, y_pixel2, rfpart(y_end) * x_gap);
+    plot_steep(x_pixel2, y_pixel2 + 1, fpart(y_end) * x_gap);
+
+    for x in (x_pixel1 + 1)..x_pixel2 {
+        plot_steep(x, intersection_y.floor() as i32, rfpart(intersection_y));
+        plot_steep(x, intersection_y.floor() as i32 + 1, fpart(intersection_y));
+        intersection_y += gradient;
+    }
+}
+
+
+/// Calls `visit` with coordinates and coverage of every pixel of line of arbitrary width.
+///
+/// Every pixel whose center lies close enough to the segment is covered, with anti-aliasing
+/// coverage falls off linearly over one pixel at the edges. Pixels outside of `clip` are skipped.
+pub fn rasterize_thick_line<F: FnMut(i32, i32, f32)>(x0: f32, y0: f32, x1: f32, y1: f32, stroke: &Stroke, clip: &Rect, mut visit: F) {
+    let half_width = stroke.width.max(0.0) / 2.0;
+    let extension = match stroke.cap {
+        LineCap::Butt => 0.0,
+        LineCap::Round | LineCap::Square => half_width,
+    };
+
+    let (dx, dy) = (x1 - x0, y1 - y0);
+    let length = dx.hypot(dy);
+    if length == 0.0 && extension == 0.0 {
+        return;
+    }
+    // Direction of a zero length line is arbitrary, caps turn it into a dot or a square.
+    let (ux, uy) = if length > 0.0 { (dx / length, dy / length) } else { (1.0, 0.0) };
+
+    let margin = half_width + extension + 1.0;
+    let x_min = ((x0.min(x1) - margin).floor() as i32).max(clip.x);
+    let y_min = ((y0.min(y1) - margin).floor() as i32).max(clip.y);
+    let x_max = ((x0.max(x1) + margin).ceil() as i32).min(clip.right() - 1);
+    let y_max = ((y0.max(y1) + margin).ceil() as i32).min(clip.bottom() - 1);
+
+    for y in y_min..=y_max {
+        for x in x_min..=x_max {
+            // Pixel center relative to the first end point, in line aligned coordinates.
+            let (px, py) = (x as f32 + 0.5 - x0, y as f32 + 0.5 - y0);
+            let along = px * ux + py * uy;
+            let across = (px * uy - py * ux).abs();
+
+            // Signed distance from the edge of the stroke, negative inside.
+            let distance = match stroke.cap {
+                LineCap::Round => {
+                    let closest = along.clamp(0.0, length);
+                    (along - closest).hypot(across) - half_width
+                },
+                LineCap::Butt | LineCap::Square => {
+                    (across - half_width).max(-extension - along).max(along - length - extension)
+                },
+            };
+
+            let coverage = if stroke.antialiased {
+                (0.5 - distance).clamp(0.0, 1.0)
+            } else if distance <= 0.0 {
+                1.0
+            } else {
+                0.0
+            };
+            if coverage > 0.0 {
+                visit(x, y, coverage);
+            }
+        }
+    }
+}
+
+
+/// Draws one pixel wide anti-aliased line between points specified.
+///
+/// Coverage of every pixel is blended into the bitmap, parts of the line outside of the bitmap are skipped.
+pub fn draw_line_aa<T: RenderTarget>(bitmap: &mut T, x0: f32, y0: f32, x1: f32, y1: f32, color: &Rgb) {
+    let clip = bitmap.rect();
+    rasterize_line_aa(x0, y0, x1, y1, &clip, |x, y, coverage| plot(bitmap, x, y, color, coverage));
+}
+
+
+/// Draws line of arbitrary width between points specified.
+///
+/// Parts of the line outside of the bitmap are skipped.
+pub fn draw_thick_line<T: RenderTarget>(bitmap: &mut T, x0: f32, y0: f32, x1: f32, y1: f32, stroke: &Stroke, color: &Rgb) {
+    let clip = bitmap.rect();
+    rasterize_thick_line(x0, y0, x1, y1, stroke, &clip, |x, y, coverage| plot(bitmap, x, y, color, coverage));
+}
+
+
+/// Draws line between points specified using given stroke.
+///
+/// One pixel wide aliased strokes use Bresenham's algorithm between pixels containing the end points,
+/// one pixel wide anti-aliased strokes use Wu's algorithm.
+pub fn draw_stroke<T: RenderTarget>(bitmap: &mut T, x0: f32, y0: f32, x1: f32, y1: f32, stroke: &Stroke, color: &Rgb) {
+    if stroke.width == 1.0 && stroke.cap == LineCap::Butt {
+        if stroke.antialiased {
+            draw_line_aa(bitmap, x0, y0, x1, y1, color);
+        } else {
+            crate::graphics::draw_line(bitmap, x0.floor() as i32, y0.floor() as i32, x1.floor() as i32, y1.floor() as i32, color);
+        }
+        return;
+    }
+    draw_thick_line(bitmap, x0, y0, x1, y1, stroke, color);
+}
+
+
+/// Repeating pattern of dashes and gaps.
+///
+/// Lengths are measured in pixels along the line, the pattern starts with a dash.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DashPattern {
+    lengths: Vec<f32>,
+    /// Distance by which the pattern is shifted at the start of the line.
+    pub phase: f32,
+}
+
+
+impl DashPattern {
+    /// Creates pattern from alternating dash and gap lengths.
+    ///
+    /// Odd number of lengths is repeated twice, so `[4.0]` means four pixel dashes separated by four pixel gaps.
+    pub fn new(lengths: &[f32], phase: f32) -> Self {
+        let mut lengths = lengths.iter().map(|length| length.max(0.0)).collect::<Vec<_>>();
+        if lengths.len() % 2 == 1 {
+            lengths.extend_from_within(..);
+        }
+        Self { lengths, phase }
+    }
+
+    /// Creates pattern from 16 bit stipple mask, least significant bit first, every bit covering `factor` pixels.
+    pub fn from_stipple(mask: u16, factor: u32) -> Self {
+        let mut lengths = Vec::new();
+        let mut on = true;
+        let mut run = 0.0;
+        for bit in 0..16 {
+            if ((mask >> bit) & 1 == 1) != on {
+                lengths.push(run);
+                on = !on;
+                run = 0.0;
+            }
+            run += factor as f32;
+        }
+        lengths.push(run);
+        if lengths.len() % 2 == 1 {
+            lengths.push(0.0);
+        }
+        Self { lengths, phase: 0.0 }
+    }
+
+    /// Length after which the pattern repeats.
+    pub fn period(&self) -> f32 {
+        self.lengths.iter().sum()
+    }
+
+    /// Returns whether the point at given distance along the line lies on a dash.
+    pub fn is_on(&self, distance: f32) -> bool {
+        let period = self.period();
+        if period <= 0.0 {
+            return true;
+        }
+        let mut offset = (distance + self.phase).rem_euclid(period);
+        for (index, length) in self.lengths.iter().enumerate() {
+            if offset < *length {
+                return index % 2 == 0;
+            }
+            offset -= length;
+        }
+        false
+    }
+
+    /// Returns dashes overlapping distance range from `start` to `end` as pairs of distances, clipped to the range.
+    pub fn dashes(&self, start: f32, end: f32) -> Vec<(f32, f32)> {
+        let period = self.period();
+        if period <= 0.0 {
+            return vec![(start, end)];
+        }
+
+        // Find the part of the pattern the range starts in and how much of it is left.
+        let mut offset = (start + self.phase).rem_euclid(period);
+        let mut index = 0;
+        while offset >= self.lengths[index] {
+            offset -= self.lengths[index];
+            index = (index + 1) % self.lengths.len();
+        }
+        let mut remaining = self.lengths[index] - offset;
+
+        let mut dashes = Vec::new();
+        let mut position = start;
+        while position < end {
+            let part_end = (position + remaining).min(end);
+            if index % 2 == 0 && part_end > position {
+                dashes.push((position, part_end));
+            }
+            position += remaining;
+            index = (index + 1) % self.lengths.len();
+            remaining = self.lengths[index];
+        }
+        dashes
+    }
+}
+
+
+/// Draws connected line segments with dash pattern applied, using given stroke for every dash.
+///
+/// Pattern is continuous across the vertices of the polyline.
+pub fn draw_dashed_polyline<T: RenderTarget>(bitmap: &mut T, points: &[(f32, f32)], stroke: &Stroke, pattern: &DashPattern, color: &Rgb) {
+    let mut travelled = 0.0;
+    for segment in points.windows(2) {
+        let [(x0, y0), (x1, y1)] = [segment[0], segment[1]];
+        let (dx, dy) = (x1 - x0, y1 - y0);
+        let length = dx.hypot(dy);
+        if length == 0.0 {
+            continue;
+        }
+        for (start, end) in pattern.dashes(travelled, travelled + length) {
+            let (t0, t1) = ((start - travelled) / length, (end - travelled) / length);
+            draw_stroke(bitmap, x0 + dx * t0, y0 + dy * t0, x0 + dx * t1, y0 + dy * t1, stroke, color);
+        }
+        travelled += length;
+    }
+}
+
+
+/// Draws line between points specified with dash pattern applied.
+pub fn draw_dashed_line<T: RenderTarget>(bitmap: &mut T, start: (f32, f32), end: (f32, f32), stroke: &Stroke, pattern: &DashPattern, color: &Rgb) {
+    draw_dashed_polyline(bitmap, &[start, end], stroke, pattern, color);
+}
+
+
+/// Visits every pixel of the line from first to last point, both inclusive.
+///
+/// Implementation of general bresenham algorithm walking in the direction of the line.
+/// `visit` receives coordinates of the pixel and its distance from the first point. Pixels outside of `clip` are skipped.
+pub fn walk_line<F: FnMut(i32, i32, f32)>(x0: i32, y0: i32, x1: i32, y1: i32, clip: &Rect, mut visit: F) {
+    let ((x0, y0), (x1, y1), offset) = if clip.contains(x0, y0) && clip.contains(x1, y1) {
+        ((x0, y0), (x1, y1), 0.0)
+    } else {
+        // Far end points are moved next to the clip, one pixel margin keeps the clipped line on the same pixels.
+        let (min, max) = ((clip.x as f64 - 1.0, clip.y as f64 - 1.0), (clip.right() as f64, clip.bottom() as f64));
+        let (fx0, fy0, fx1, fy1) = (x0 as f64, y0 as f64, x1 as f64, y1 as f64);
+        let (t0, t1) = match clip_segment((fx0, fy0), (fx1, fy1), min, max) {
+            Some(range) => range,
+            None => return,
+        };
+        let at = |t: f64| ((fx0 + (fx1 - fx0) * t).round() as i32, (fy0 + (fy1 - fy0) * t).round() as i32);
+        let (start, end) = (at(t0), at(t1));
+        (start, end, (start.0 as f64 - fx0).hypot(start.1 as f64 - fy0) as f32)
+    };
+
+    let dx = (x1 as i64 - x0 as i64).abs();
+    let dy = -(y1 as i64 - y0 as i64).abs();
+    let step_x = if x0 < x1 { 1 } else { -1 };
+    let step_y = if y0 < y1 { 1 } else { -1 };
+
+    // Every step advances along the major axis, so distance grows uniformly.
+    let steps = dx.max(-dy);
+    let step_length = if steps == 0 { 0.0 } else { (dx as f32).hypot(dy as f32) / steps as f32 };
+
+    let (mut x, mut y) = (x0, y0);
+    let mut diff = dx + dy;
+    for step in 0..=steps {
+        if clip.contains(x, y) {
+            visit(x, y, offset + step as f32 * step_length);
+        }
+        let doubled = 2 * diff;
+        if doubled >= dy {
+            diff += dy;
+            x += step_x;
+        }
+        if doubled <= dx {
+            diff += dx;
+            y += step_y;
+        }
+    }
+}
+
+
+/// Draws one pixel wide line, color of every pixel is decided by `shade`.
+///
+/// `shade` receives coordinates of the pixel and its distance from the first point,
+/// and returns color of the pixel or `None` to leave it unchanged. Pixels outside of the bitmap are skipped.
+pub fn draw_line_with<T: RenderTarget, F: FnMut(i32, i32, f32) -> Option<Rgb>>(bitmap: &mut T, x0: i32, y0: i32, x1: i32, y1: i32, mut shade: F) {
+    walk_line(x0, y0, x1, y1, &bitmap.rect(), |x, y, distance| {
+        if let Some(color) = shade(x, y, distance) {
+            plot(bitmap, x, y, &color, 1.0);
+        }
+    });
+}
+
+
+/// Draws connected one pixel wide line segments, color of every pixel is decided by `shade`.
+///
+/// Distance passed to `shade` is measured along the whole polyline, shared vertices are visited once.
+pub fn draw_polyline_with<T: RenderTarget, F: FnMut(i32, i32, f32) -> Option<Rgb>>(bitmap: &mut T, points: &[(i32, i32)], mut shade: F) {
+    let mut travelled = 0.0;
+    for (index, segment) in points.windows(2).enumerate() {
+        let [(x0, y0), (x1, y1)] = [segment[0], segment[1]];
+        walk_line(x0, y0, x1, y1, &bitmap.rect(), |x, y, distance| {
+            if index > 0 && distance == 0.0 {
+                return;
+            }
+            if let Some(color) = shade(x, y, travelled + distance) {
+                plot(bitmap, x, y, &color, 1.0);
+            }
+        });
+        travelled += (x1 as f64 - x0 as f64).hypot(y1 as f64 - y0 as f64) as f32;
+    }
+}
+
+
+/// Test suite for dash patterns.
+#[cfg(test)]
+mod test_dash_pattern {
+    use super::*;
+
+    #[test]
+    fn odd_lengths_are_repeated() {
+        let pattern = DashPattern::new(&[2.0], 0.0);
+        assert_eq!(pattern.period(), 4.0);
+        assert!(pattern.is_on(0.0));
+        assert!(pattern.is_on(1.5));
+        assert!(!pattern.is_on(2.0));
+        assert!(pattern.is_on(4.5));
+    }
+
+    #[test]
+    fn phase_shifts_pattern() {
+        let pattern = DashPattern::new(&[2.0, 2.0], 2.0);
+        assert!(!pattern.is_on(0.0));
+        assert!(pattern.is_on(2.0));
+    }
+
+    #[test]
+    fn dashes_continue_across_ranges() {
+        let pattern = DashPattern::new(&[3.0, 1.0], 0.0);
+        assert_eq!(pattern.dashes(0.0, 5.0), vec![(0.0, 3.0), (4.0, 5.0)]);
+        assert_eq!(pattern.dashes(5.0, 8.0), vec![(5.0, 7.0)]);
+    }
+
+    #[test]
+    fn stipple_mask() {
+        let pattern = DashPattern::from_stipple(0b0000_0000_1111_0000, 1);
+        assert_eq!(pattern.period(), 16.0);
+        assert!(!pattern.is_on(0.0));
+        assert!(pattern.is_on(4.0));
+        assert!(pattern.is_on(7.0));
+        assert!(!pattern.is_on(8.0));
+    }
+}
+
+
+/// Test suite for anti-aliased and thick line coverage.
+#[cfg(test)]
+mod test_line_coverage {
+    use super::*;
+    use std::collections::HashMap;
+
+    const CLIP: Rect = Rect::new(0, 0, 16, 16);
+
+    fn coverage_aa(x0: f32, y0: f32, x1: f32, y1: f32) -> HashMap<(i32, i32), f32> {
+        let mut coverage = HashMap::new();
+        rasterize_line_aa(x0, y0, x1, y1, &CLIP, |x, y, value| *coverage.entry((x, y)).or_insert(0.0) += value);
+        coverage
+    }
+
+    fn covered_thick(stroke: &Stroke) -> Vec<(i32, i32)> {
+        let mut covered = Vec::new();
+        rasterize_thick_line(2.0, 5.0, 8.0, 5.0, stroke, &CLIP, |x, y, coverage| {
+            assert_eq!(coverage, 1.0);
+            covered.push((x, y));
+        });
+        covered
+    }
+
+    #[test]
+    fn wu_coverage() {
+        // Line between pixel edges covers whole pixels.
+        let coverage = coverage_aa(0.0, 0.5, 10.0, 0.5);
+        assert_eq!(coverage.len(), 10);
+        assert!(coverage.values().all(|value| (value - 1.0).abs() < 1e-6));
+
+        // End points lying at pixel centers cover only half of their pixels.
+        let coverage = coverage_aa(0.5, 0.5, 10.5, 0.5);
+        assert_eq!(coverage.len(), 11);
+        assert!((coverage[&(0, 0)] - 0.5).abs() < 1e-6 && (coverage[&(10, 0)] - 0.5).abs() < 1e-6);
+
+        // Coverage of every column of a sloped line sums up to one.
+        let coverage = coverage_aa(0.5, 1.0, 8.5, 4.0);
+        for x in 1..8 {
+            let column = coverage.iter().filter(|((px, _), _)| *px == x).map(|(_, value)| value).sum::<f32>();
+            assert!((column - 1.0).abs() < 1e-5, "{} {}", x, column);
+        }
+    }
+
+    #[test]
+    fn wu_short_line() {
+        // Both end points lie in the same column, which is visited only once and covered by the length of the line.
+        let mut visits = Vec::new();
+        rasterize_line_aa(2.2, 0.5, 2.6, 0.5, &CLIP, |x, y, value| visits.push((x, y, value)));
+        assert_eq!(visits.len(), 1);
+        let (x, y, value) = visits[0];
+        assert_eq!((x, y), (2, 0));
+        assert!((value - 0.4).abs() < 1e-5, "{}", value);
+    }
+
+    #[test]
+    fn far_end_points() {
+        // Only pixels inside of the clip are walked, distances are still measured from the first point.
+        let mut visits = Vec::new();
+        walk_line(-1_000_000_000, 4, i32::MAX, 4, &CLIP, |x, y, distance| visits.push((x, y, distance)));
+        assert_eq!(visits.len(), 16);
+        assert!(visits.iter().enumerate().all(|(index, &(x, y, distance))| (x, y) == (index as i32, 4) && distance == 1e9 + index as f32));
+
+        let mut visits = Vec::new();
+        walk_line(i32::MIN, i32::MIN, i32::MAX, i32::MAX, &CLIP, |x, y, _| visits.push((x, y)));
+        assert_eq!(visits, (0..16).map(|index| (index, index)).collect::<Vec<_>>());
+
+        let mut distances = Vec::new();
+        walk_line(-10, 2, 30, 2, &CLIP, |_, _, distance| distances.push(distance));
+        assert_eq!(distances, (10..26).map(|distance| distance as f32).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn caps() {
+        // Four pixels wide line, so rows 3 to 6 are covered.
+        let butt = covered_thick(&Stroke::new(4.0, LineCap::Butt, false));
+        assert_eq!(butt.len(), 6 * 4);
+        assert!(butt.iter().all(|(x, y)| (2..8).contains(x) && (3..7).contains(y)));
+
+        // Square caps extend the line by half of its width on both sides.
+        let square = covered_thick(&Stroke::new(4.0, LineCap::Square, false));
+        assert_eq!(square.len(), 10 * 4);
+        assert!(square.iter().all(|(x, y)| (0..10).contains(x) && (3..7).contains(y)));
+
+        // Round caps miss the outermost corners of the square ones.
+        let round = covered_thick(&Stroke::new(4.0, LineCap::Round, false));
+        assert_eq!(round.len(), 10 * 4 - 4);
+        for corner in [(0, 3), (0, 6), (9, 3), (9, 6)] {
+            assert!(!round.contains(&corner));
+        }
+        assert!(round.contains(&(0, 4)) && round.contains(&(9, 5)));
+
+        // Anti-aliased edges lying on pixel boundaries leave the coverage unchanged.
+        let mut area = 0.0;
+        rasterize_thick_line(2.0, 5.0, 8.0, 5.0, &Stroke::new(4.0, LineCap::Butt, true), &CLIP, |_, _, coverage| area += coverage);
+        assert!((area - 24.0).abs() < 1e-5, "{}", area);
+    }
+}
@@ -1,7 +1,40 @@
 #[allow(dead_code)]
 
 mod bitmap;
+#[allow(dead_code)]
+mod canvas;
+#[allow(dead_code)]
+mod color;
+#[allow(dead_code)]
+mod format;
+#[allow(dead_code)]
+mod gif;
 mod graphics;
+#[allow(dead_code)]
+mod hdr;
+mod hud;
+#[allow(dead_code)]
+mod lighting;
+#[allow(dead_code)]
+mod line;
+#[allow(dead_code)]
+mod palette;
+#[allow(dead_code)]
+mod path;
+#[allow(dead_code)]
+mod pipeline;
+#[allow(dead_code)]
+mod polygon;
+#[allow(dead_code)]
+mod postprocess;
+#[allow(dead_code)]
+mod resample;
+#[allow(dead_code)]
+mod shader;
+#[allow(dead_code)]
+mod text;
+#[allow(dead_code)]
+mod view;
 
 // External imports
 use windows::{
@@ -13,11 +46,46 @@ use windows::{
 };
 
 // Internal imports
-use bitmap::{ Bitmap, Pixel };
+use bitmap::{ Bitmap, Pixel, Rect, ResizePolicy };
+use format::{ PixelFormat, RgbaF32 };
+use gif::GifEncoder;
+use hdr::{ HdrBuffer, ToneMapper };
+use lighting::{ Attenuation, Light, Lighting, Shading };
+use palette::{ Dithering, Palette };
+use postprocess::{ Effect, PostProcess };
+use resample::Filter;
+use nalgebra as na;
 
 // static mut naming convention: https://github.com/rust-lang/rust/pull/37162
 static mut GLOBAL_BITMAP : Option<Bitmap> = None;
 static mut ITER_COUNT: u8 = 0;
+static mut RESIZE_POLICY: ResizePolicy = ResizePolicy::Native;
+
+/// Virtual key code of the key cycling through render modes - R.
+const RENDER_MODE_KEY: usize = 0x52;
+/// Virtual key code of the key toggling debug overlay - F3.
+const HUD_KEY: usize = 0x72;
+/// Virtual key code of the key cycling through resize policies - P.
+const RESIZE_POLICY_KEY: usize = 0x50;
+/// Virtual key code of the key cycling through tone mapping operators - T.
+const TONE_MAPPING_KEY: usize = 0x54;
+/// Virtual key codes of the keys increasing and decreasing exposure by half a stop - plus and minus.
+const EXPOSURE_UP_KEY: usize = 0xBB;
+const EXPOSURE_DOWN_KEY: usize = 0xBD;
+/// Virtual key code of the key cycling through post-processing presets - O.
+const POST_PROCESS_KEY: usize = 0x4F;
+/// Virtual key code of the key starting capture of an animated GIF - G.
+const GIF_CAPTURE_KEY: usize = 0x47;
+/// Number of frames of the captured GIF, frames are stored at half of the render resolution.
+const GIF_FRAMES: usize = 50;
+
+/// Resize policies cycled through with RESIZE_POLICY_KEY, fixed resolution ones render at a quarter of the default size.
+const RESIZE_POLICIES: [ResizePolicy; 4] = [
+    ResizePolicy::Native,
+    ResizePolicy::Stretch { width: graphics::WIDTH as usize / 2, height: graphics::HEIGHT as usize / 2 },
+    ResizePolicy::Letterbox { width: graphics::WIDTH as usize / 2, height: graphics::HEIGHT as usize / 2 },
+    ResizePolicy::IntegerScale { width: graphics::WIDTH as usize / 2, height: graphics::HEIGHT as usize / 2 },
+];
 
 
 extern "system" fn window_procedure(window: HWND, message: u32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
@@ -43,13 +111,16 @@ extern "system" fn window_procedure(window: HWND, message: u32, wparam: WPARAM,
                 let device_context = Gdi::BeginPaint(window, &mut paint_struct);
                 let mut rect = RECT::default();
                 GetClientRect(window, &mut rect);
-                GLOBAL_BITMAP.as_mut().unwrap().blit(&device_context, &rect);
+                GLOBAL_BITMAP.as_mut().unwrap().blit(&device_context, &rect, &RESIZE_POLICY);
                 Gdi::EndPaint(window, &paint_struct);
                 LRESULT(0)
             },
             WM_SIZE => {
                 //println!("WM_SIZE!");
-
+                // NOTE: low and high words of lparam hold new width and height of the client area
+                let width = (lparam.0 & 0xFFFF) as usize;
+                let height = ((lparam.0 >> 16) & 0xFFFF) as usize;
+                resize_render_target(width, height);
                 LRESULT(0)
             }
             _ => { DefWindowProcA(window, message, wparam, lparam) }
@@ -57,6 +128,53 @@ extern "system" fn window_procedure(window: HWND, message: u32, wparam: WPARAM,
     }
 }
 
+/// Resizes GLOBAL_BITMAP for client area of given size according to RESIZE_POLICY.
+///
+/// # Safety
+///
+/// This function uses global static buffer GLOBAL_BITMAP
+pub unsafe fn resize_render_target(client_width: usize, client_height: usize) {
+    // NOTE: minimized window has empty client area, the last frame is kept
+    if client_width == 0 || client_height == 0 {
+        return;
+    }
+    if let Some(bitmap) = GLOBAL_BITMAP.as_mut() {
+        let (width, height) = RESIZE_POLICY.bitmap_size(client_width, client_height);
+        bitmap.resize(width, height);
+    }
+}
+
+/// Post-processing chains cycled through with POST_PROCESS_KEY, the first one leaves the image unchanged.
+///
+/// Every preset is a pair of chains, the first one runs on the HDR buffer before tone mapping,
+/// so that only light brighter than the display can show blooms, the second one on the final image.
+fn post_process_presets() -> Vec<(PostProcess, PostProcess)> {
+    let mut bloom = PostProcess::new();
+    bloom.push(Effect::Bloom { threshold: 1.0, intensity: 1.5, sigma: 6.0 });
+    let mut vignette = PostProcess::new();
+    vignette.push(Effect::Vignette { radius: 0.5, strength: 0.6 });
+    let mut old_photo = PostProcess::new();
+    old_photo.push(Effect::GaussianBlur { sigma: 0.8 }).push(Effect::Sepia).push(Effect::Vignette { radius: 0.3, strength: 0.9 });
+    let mut retro = PostProcess::new();
+    retro.push(Effect::Pixelate { size: 4 }).push(Effect::Sharpen { amount: 0.5 });
+    let mut sketch = PostProcess::new();
+    sketch.push(Effect::EdgeDetect);
+    let mut game_boy = PostProcess::new();
+    game_boy.push(Effect::Pixelate { size: 3 }).push(Effect::Quantize { palette: Palette::game_boy(), dithering: Dithering::Bayer { size: 4 } });
+    let mut ega = PostProcess::new();
+    ega.push(Effect::Quantize { palette: Palette::ega(), dithering: Dithering::FloydSteinberg });
+    let display = |chain: PostProcess| (PostProcess::new(), chain);
+    vec![
+        display(PostProcess::new()),
+        (bloom, vignette),
+        display(old_photo),
+        display(retro),
+        display(sketch),
+        display(game_boy),
+        display(ega),
+    ]
+}
+
 /// # Safety
 ///
 /// This function uses global static buffer GLOBAL_BITMAP
@@ -73,7 +191,7 @@ pub unsafe fn render_wierd_gradient(blue_offset: u8, green_offset: u8) {
 
 
 fn main() -> Result<()> {
-    let window_style = WS_OVERLAPPED | WS_CAPTION | WS_SYSMENU | WS_MINIMIZEBOX | WS_MAXIMIZEBOX | WS_VISIBLE;
+    let window_style = WS_OVERLAPPED | WS_CAPTION | WS_SYSMENU | WS_THICKFRAME | WS_MINIMIZEBOX | WS_MAXIMIZEBOX | WS_VISIBLE;
     unsafe {
         let instance = GetModuleHandleA(None);
         assert!(!instance.is_invalid(), "Invalid instance handle.");
@@ -122,6 +240,20 @@ fn main() -> Result<()> {
         let mut message = MSG::default();
 
         let mut mesh = graphics::Mesh::default();
+        mesh.set_material(mesh.material().with_shading(Shading::Phong));
+        let mut pipeline = pipeline::Pipeline::default();
+        let lighting = Lighting::new(vec![
+            Light::ambient(na::Vector3::repeat(1.0), 0.1),
+            Light::directional(na::Vector3::new(1.0, -1.0, -1.0), na::Vector3::new(1.0, 0.95, 0.8), 0.8),
+            Light::point(na::Point3::new(-2.0, 1.0, 2.0), na::Vector3::new(0.3, 0.5, 1.0), 1.5, Attenuation::default()),
+        ]);
+        let mut hud = hud::DebugHud::default();
+        let mut hdr = HdrBuffer::new(0, 0);
+        let mut tone_mapper = ToneMapper::default();
+        let post_processes = post_process_presets();
+        let mut post_process = 0;
+        let mut capture: Vec<Bitmap> = Vec::new();
+        let mut capturing = false;
         GLOBAL_BITMAP = Some(Bitmap::default());
 
 
@@ -130,6 +262,35 @@ fn main() -> Result<()> {
                 if message.message == WM_QUIT {
                     running = false;
                 }
+                if message.message == WM_KEYDOWN && message.wParam.0 == RENDER_MODE_KEY {
+                    pipeline.render_mode = pipeline.render_mode.next();
+                }
+                if message.message == WM_KEYDOWN && message.wParam.0 == HUD_KEY {
+                    hud.toggle();
+                }
+                if message.message == WM_KEYDOWN && message.wParam.0 == TONE_MAPPING_KEY {
+                    tone_mapper.operator = tone_mapper.operator.next();
+                }
+                if message.message == WM_KEYDOWN && message.wParam.0 == EXPOSURE_UP_KEY {
+                    tone_mapper.exposure += 0.5;
+                }
+                if message.message == WM_KEYDOWN && message.wParam.0 == EXPOSURE_DOWN_KEY {
+                    tone_mapper.exposure -= 0.5;
+                }
+                if message.message == WM_KEYDOWN && message.wParam.0 == POST_PROCESS_KEY {
+                    post_process = (post_process + 1) % post_processes.len();
+                }
+                if message.message == WM_KEYDOWN && message.wParam.0 == GIF_CAPTURE_KEY {
+                    capturing = true;
+                    capture.clear();
+                }
+                if message.message == WM_KEYDOWN && message.wParam.0 == RESIZE_POLICY_KEY {
+                    let index = RESIZE_POLICIES.iter().position(|policy| *policy == RESIZE_POLICY).unwrap_or(0);
+                    RESIZE_POLICY = RESIZE_POLICIES[(index + 1) % RESIZE_POLICIES.len()];
+                    let mut rect = RECT::default();
+                    GetClientRect(handle, &mut rect);
+                    resize_render_target((rect.right - rect.left) as usize, (rect.bottom - rect.top) as usize);
+                }
                 TranslateMessage(&message);
                 DispatchMessageA(&message);
             }
@@ -139,11 +300,38 @@ fn main() -> Result<()> {
             let mut rect = RECT::default();
             GetClientRect(handle, &mut rect);
 
-            GLOBAL_BITMAP.as_mut().unwrap().clear_buffer();
-            // mesh.update(GLOBAL_BITMAP.as_mut().unwrap());
-            graphics::draw_elipsis(GLOBAL_BITMAP.as_mut().unwrap(), 100, 132, graphics::WIDTH / 2, graphics::HEIGHT / 2);
+            hud.begin_frame();
+            let client = Rect::new(rect.left, rect.top, rect.right - rect.left, rect.bottom - rect.top);
+            mesh.set_aspect_ratio(RESIZE_POLICY.aspect_ratio(GLOBAL_BITMAP.as_ref().unwrap(), &client));
+            // Scene is lit into the float buffer, so that bright highlights are tone mapped instead of clipped.
+            let bitmap = GLOBAL_BITMAP.as_mut().unwrap();
+            hdr.resize(bitmap.width, bitmap.height);
+            hdr.fill(RgbaF32::from_pixel(Pixel::default()));
+            hud.record(mesh.update(&mut hdr, &pipeline, &lighting));
+            let (hdr_effects, display_effects) = &post_processes[post_process];
+            hdr_effects.apply_hdr(&mut hdr);
+            tone_mapper.apply(&hdr, bitmap);
+            // Display effects run on the final image, before the overlay so that it stays readable.
+            display_effects.apply(bitmap);
+            if capturing {
+                // All frames need the size of the first one, even if the window is resized during capture.
+                let (width, height) = capture.first().map_or((bitmap.width / 2, bitmap.height / 2), |frame| (frame.width, frame.height));
+                capture.push(bitmap.resampled(width, height, Filter::Bilinear));
+                if capture.len() == GIF_FRAMES {
+                    capturing = false;
+                    // The window has no console, so the outcome is reported in its title.
+                    let title = match GifEncoder::new().save(&capture, "capture.gif") {
+                        Ok(()) => String::from("3DEngine - capture.gif saved"),
+                        Err(error) => format!("3DEngine - saving capture.gif failed: {}", error),
+                    };
+                    SetWindowTextA(handle, title);
+                    capture.clear();
+                }
+            }
+            // graphics::draw_elipsis(GLOBAL_BITMAP.as_mut().unwrap(), 100, 132, graphics::WIDTH / 2, graphics::HEIGHT / 2, &(255, 255, 255));
+            hud.draw(GLOBAL_BITMAP.as_mut().unwrap());
 
-            GLOBAL_BITMAP.as_mut().unwrap().blit(&device_context, &rect);
+            GLOBAL_BITMAP.as_mut().unwrap().blit(&device_context, &rect, &RESIZE_POLICY);
             Gdi::ReleaseDC(handle, device_context);
             ITER_COUNT = ITER_COUNT.overflowing_add(2).0;
         }
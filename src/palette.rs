@@ -0,0 +1,403 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use crate::{Bitmap, Pixel};
+
+
+/// Limited set of at most 256 colors, images quantized to it store only indices of the colors.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    colors: Vec<Pixel>,
+}
+
+
+impl Palette {
+    /// Creates palette of opaque colors, there has to be between 1 and 256 of them.
+    pub fn new(colors: Vec<Pixel>) -> Self {
+        assert!(!colors.is_empty() && colors.len() <= 256, "Palette has to have between 1 and 256 colors, got {}.", colors.len());
+        Self { colors: colors.into_iter().map(|color| color.with_alfa(255)).collect() }
+    }
+
+    fn from_hex(colors: &[u32]) -> Self {
+        Self::new(colors.iter().map(|color| Pixel::new((color >> 16) as u8, (color >> 8) as u8, *color as u8)).collect())
+    }
+
+    /// Four colors of the CGA graphics mode 4 with the high intensity palette 1: black, cyan, magenta and white.
+    pub fn cga() -> Self {
+        Self::from_hex(&[0x000000, 0x55FFFF, 0xFF55FF, 0xFFFFFF])
+    }
+
+    /// Sixteen colors of the CGA text modes, also the default palette of EGA.
+    pub fn cga16() -> Self {
+        Self::from_hex(&[
+            0x000000, 0x0000AA, 0x00AA00, 0x00AAAA, 0xAA0000, 0xAA00AA, 0xAA5500, 0xAAAAAA,
+            0x555555, 0x5555FF, 0x55FF55, 0x55FFFF, 0xFF5555, 0xFF55FF, 0xFFFF55, 0xFFFFFF,
+        ])
+    }
+
+    /// All 64 colors EGA can display, with four levels of each channel.
+    pub fn ega() -> Self {
+        Self::new((0..64u8).map(|index| Pixel::new((index >> 4 & 3) * 85, (index >> 2 & 3) * 85, (index & 3) * 85)).collect())
+    }
+
+    /// Four shades of green of the original Game Boy screen.
+    pub fn game_boy() -> Self {
+        Self::from_hex(&[0x0F380F, 0x306230, 0x8BAC0F, 0x9BBC0F])
+    }
+
+    /// Evenly spaced shades of gray from black to white.
+    pub fn grayscale(levels: usize) -> Self {
+        let levels = levels.clamp(2, 256);
+        Self::new((0..levels).map(|level| (level * 255 / (levels - 1)) as u8).map(|value| Pixel::new(value, value, value)).collect())
+    }
+
+    /// Generates palette of at most `count` colors fitting the pixels with the median cut algorithm.
+    ///
+    /// Colors are split into boxes, the box with the widest range of a channel is repeatedly divided
+    /// at the median of that channel, colors of the palette are averages of the boxes.
+    pub fn median_cut(pixels: &[Pixel], count: usize) -> Self {
+        let mut histogram: HashMap<[u8; 3], u64> = HashMap::new();
+        for pixel in pixels {
+            *histogram.entry([pixel.red, pixel.green, pixel.blue]).or_insert(0) += 1;
+        }
+        if histogram.is_empty() {
+            return Self::new(vec![Pixel::new(0, 0, 0)]);
+        }
+        let mut colors: Vec<([u8; 3], u64)> = histogram.into_iter().collect();
+        // Hash map order is random, sorting keeps the result the same for the same image.
+        colors.sort_unstable();
+
+        let mut boxes = vec![colors];
+        while boxes.len() < count.clamp(1, 256) {
+            let widest = boxes
+                .iter()
+                .enumerate()
+                .filter(|(_, colors)| colors.len() > 1)
+                .map(|(index, colors)| {
+                    let (channel, range) = (0..3)
+                        .map(|channel| {
+                            let (minimum, maximum) = colors.iter().fold((u8::MAX, 0), |(minimum, maximum), (color, _)| (minimum.min(color[channel]), maximum.max(color[channel])));
+                            (channel, maximum - minimum)
+                        })
+                        .max_by_key(|&(_, range)| range)
+                        .unwrap();
+                    (index, channel, range)
+                })
+                .max_by_key(|&(_, _, range)| range);
+            let Some((index, channel, _)) = widest else {
+                break;
+            };
+
+            let mut lower = boxes.swap_remove(index);
+            lower.sort_by_key(|(color, _)| color[channel]);
+            let total: u64 = lower.iter().map(|(_, count)| count).sum();
+            let mut accumulated = 0;
+            let median = lower.iter().position(|(_, count)| {
+                accumulated += count;
+                accumulated * 2 >= total
+            }).unwrap_or(0);
+            // Both halves have to keep at least one color.
+            let upper = lower.split_off((median + 1).clamp(1, lower.len() - 1));
+            boxes.push(lower);
+            boxes.push(upper);
+        }
+
+        Self::new(boxes.iter().map(|colors| {
+            let total: u64 = colors.iter().map(|(_, count)| count).sum();
+            let average = |channel: usize| ((colors.iter().map(|(color, count)| color[channel] as u64 * count).sum::<u64>() + total / 2) / total) as u8;
+            Pixel::new(average(0), average(1), average(2))
+        }).collect())
+    }
+
+    pub fn colors(&self) -> &[Pixel] {
+        &self.colors
+    }
+
+    pub fn len(&self) -> usize {
+        self.colors.len()
+    }
+
+    /// Returns index of the palette color closest to the sRGB color with channels in range 0.0 - 255.0.
+    pub fn nearest(&self, color: [f32; 3]) -> usize {
+        let distance = |pixel: &Pixel| {
+            let difference = [pixel.red as f32 - color[0], pixel.green as f32 - color[1], pixel.blue as f32 - color[2]];
+            difference.iter().map(|value| value * value).sum::<f32>()
+        };
+        self.colors
+            .iter()
+            .map(distance)
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, _)| index)
+            .unwrap()
+    }
+
+    /// Average distance between colors of the palette and their closest neighbours, used as the amplitude of ordered dithering.
+    fn spacing(&self) -> f32 {
+        if self.colors.len() < 2 {
+            return 0.0;
+        }
+        let channels = |pixel: &Pixel| [pixel.red as f32, pixel.green as f32, pixel.blue as f32];
+        let total: f32 = self.colors.iter().enumerate().map(|(index, color)| {
+            self.colors
+                .iter()
+                .enumerate()
+                .filter(|&(other, _)| other != index)
+                .map(|(_, other)| channels(color).iter().zip(channels(other)).map(|(a, b)| (a - b) * (a - b)).sum::<f32>())
+                .fold(f32::INFINITY, f32::min)
+                .sqrt()
+        }).sum();
+        total / self.colors.len() as f32
+    }
+}
+
+
+/// Method hiding the banding caused by reducing the number of colors.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Dithering {
+    /// Every pixel takes the closest palette color.
+    None,
+    /// Ordered dithering with a Bayer threshold matrix of `size` x `size`, rounded up to a power of two.
+    ///
+    /// Produces regular cross-hatch patterns and does not depend on neighbouring pixels.
+    Bayer { size: usize },
+    /// Error diffusion spreading the whole quantization error to four neighbouring pixels.
+    FloydSteinberg,
+    /// Error diffusion used by early Macintosh computers, spreads only three quarters of the error,
+    /// which keeps more contrast at the cost of losing detail in highlights and shadows.
+    Atkinson,
+}
+
+
+impl Dithering {
+    /// Returns the next dithering method, used for cycling through all of them.
+    pub fn next(&self) -> Self {
+        match self {
+            Dithering::None => Dithering::Bayer { size: 4 },
+            Dithering::Bayer { .. } => Dithering::FloydSteinberg,
+            Dithering::FloydSteinberg => Dithering::Atkinson,
+            Dithering::Atkinson => Dithering::None,
+        }
+    }
+}
+
+
+/// Image storing indices into its palette instead of colors, the result of quantization, ready for export.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexedImage {
+    pub width: usize,
+    pub height: usize,
+    pub palette: Palette,
+    pub indices: Vec<u8>,
+}
+
+
+impl IndexedImage {
+    pub fn to_bitmap(&self) -> Bitmap {
+        let mut bitmap = Bitmap::new(self.width, self.height);
+        for (pixel, &index) in bitmap.pixels_mut().iter_mut().zip(&self.indices) {
+            *pixel = self.palette.colors[index as usize];
+        }
+        bitmap
+    }
+
+    /// Saves the image in binary ppm format.
+    pub fn save(&self, path: &str) -> Result<(), std::io::Error> {
+        let mut file_handle = std::io::BufWriter::new(std::fs::File::create(path)?);
+        write!(file_handle, "P6\n{} {}\n255\n", self.width, self.height)?;
+        let bytes: Vec<u8> = self.indices.iter().map(|&index| self.palette.colors[index as usize]).flat_map(|color| [color.red, color.green, color.blue]).collect();
+        file_handle.write_all(&bytes)
+    }
+}
+
+
+/// Maps pixels of an image of given dimensions to indices of the palette colors.
+///
+/// Colors are compared and errors diffused in sRGB space, alfa is ignored.
+pub fn quantize(pixels: &[Pixel], width: usize, height: usize, palette: &Palette, dithering: Dithering) -> Vec<u8> {
+    let channels = |pixel: &Pixel| [pixel.red as f32, pixel.green as f32, pixel.blue as f32];
+    let mut cache = NearestCache::new(palette);
+    match dithering {
+        Dithering::None => pixels.iter().map(|pixel| cache.nearest(channels(pixel)) as u8).collect(),
+        Dithering::Bayer { size } => {
+            let matrix = bayer_matrix(size);
+            let size = (matrix.len() as f32).sqrt() as usize;
+            let spread = palette.spacing();
+            pixels.iter().enumerate().map(|(index, pixel)| {
+                let (x, y) = (index % width, index / width);
+                // Threshold in range -0.5 - 0.5 with zero mean.
+                let threshold = (matrix[y % size * size + x % size] as f32 + 0.5) / matrix.len() as f32 - 0.5;
+                cache.nearest(channels(pixel).map(|channel| channel + threshold * spread)) as u8
+            }).collect()
+        },
+        Dithering::FloydSteinberg => diffuse_error(pixels, width, height, palette, &[(1, 0, 7.0 / 16.0), (-1, 1, 3.0 / 16.0), (0, 1, 5.0 / 16.0), (1, 1, 1.0 / 16.0)]),
+        Dithering::Atkinson => diffuse_error(pixels, width, height, palette, &[(1, 0, 0.125), (2, 0, 0.125), (-1, 1, 0.125), (0, 1, 0.125), (1, 1, 0.125), (0, 2, 0.125)]),
+    }
+}
+
+
+/// Remembers closest palette colors of already searched colors.
+///
+/// Colors are compared by exact value, so results are the same as of `Palette::nearest`.
+/// Only ordered dithering benefits, colors with diffused errors rarely repeat.
+struct NearestCache<'a> {
+    palette: &'a Palette,
+    indices: HashMap<[u32; 3], usize>,
+}
+
+
+impl<'a> NearestCache<'a> {
+    fn new(palette: &'a Palette) -> Self {
+        Self { palette, indices: HashMap::new() }
+    }
+
+    fn nearest(&mut self, color: [f32; 3]) -> usize {
+        let palette = self.palette;
+        *self.indices.entry(color.map(f32::to_bits)).or_insert_with(|| palette.nearest(color))
+    }
+}
+
+
+/// Replaces pixels with the palette colors they are quantized to, alfa is kept.
+pub fn dither(pixels: &mut [Pixel], width: usize, height: usize, palette: &Palette, dithering: Dithering) {
+    let indices = quantize(pixels, width, height, palette, dithering);
+    for (pixel, index) in pixels.iter_mut().zip(indices) {
+        *pixel = palette.colors[index as usize].with_alfa(pixel.alfa);
+    }
+}
+
+
+/// Returns Bayer threshold matrix with values 0 to size² - 1, stored row by row.
+fn bayer_matrix(size: usize) -> Vec<usize> {
+    let size = size.clamp(1, 64).next_power_of_two();
+    let mut matrix = vec![0];
+    let mut current = 1;
+    // Every step builds matrix twice as large from four copies: 4M, 4M + 2 / 4M + 3, 4M + 1.
+    while current < size {
+        let next = current * 2;
+        matrix = (0..next * next).map(|index| {
+            let (x, y) = (index % next, index / next);
+            let offset = [[0, 2], [3, 1]][y / current][x / current];
+            4 * matrix[y % current * current + x % current] + offset
+        }).collect();
+        current = next;
+    }
+    matrix
+}
+
+
+/// Quantizes pixels in scanline order, pushing the error of each pixel to the not yet visited neighbours with given weights.
+fn diffuse_error(pixels: &[Pixel], width: usize, height: usize, palette: &Palette, weights: &[(isize, usize, f32)]) -> Vec<u8> {
+    let mut colors: Vec<[f32; 3]> = pixels.iter().map(|pixel| [pixel.red as f32, pixel.green as f32, pixel.blue as f32]).collect();
+    let mut indices = vec![0; pixels.len()];
+    for y in 0..height {
+        for x in 0..width {
+            // Accumulated error can push colors far outside of the range, which makes the dithering unstable.
+            let color = colors[y * width + x].map(|channel| channel.clamp(0.0, 255.0));
+            let index = palette.nearest(color);
+            indices[y * width + x] = index as u8;
+            let chosen = palette.colors[index];
+            let error = [color[0] - chosen.red as f32, color[1] - chosen.green as f32, color[2] - chosen.blue as f32];
+            for &(dx, dy, weight) in weights {
+                let (neighbour_x, neighbour_y) = (x as isize + dx, y + dy);
+                if neighbour_x < 0 || neighbour_x >= width as isize || neighbour_y >= height {
+                    continue;
+                }
+                let neighbour = &mut colors[neighbour_y * width + neighbour_x as usize];
+                for channel in 0..3 {
+                    neighbour[channel] += error[channel] * weight;
+                }
+            }
+        }
+    }
+    indices
+}
+
+
+/// Test suite for palettes and dithering.
+#[cfg(test)]
+mod test_palette {
+    use super::*;
+
+    fn gray(width: usize, height: usize, value: u8) -> Vec<Pixel> {
+        vec![Pixel::new(value, value, value); width * height]
+    }
+
+    fn white_count(indices: &[u8]) -> usize {
+        indices.iter().filter(|&&index| index == 1).count()
+    }
+
+    #[test]
+    fn fixed_palettes() {
+        assert_eq!(Palette::cga().len(), 4);
+        assert_eq!(Palette::cga16().len(), 16);
+        assert_eq!(Palette::ega().len(), 64);
+        assert_eq!(Palette::game_boy().colors()[3], Pixel::new(0x9B, 0xBC, 0x0F));
+        assert!(Palette::cga16().colors().iter().all(|color| Palette::ega().colors().contains(color)));
+        assert_eq!(Palette::grayscale(3).colors(), &[Pixel::new(0, 0, 0), Pixel::new(127, 127, 127), Pixel::new(255, 255, 255)]);
+        assert_eq!(Palette::cga16().nearest([250.0, 90.0, 80.0]), 12);
+    }
+
+    #[test]
+    fn median_cut() {
+        let mut pixels = vec![Pixel::new(255, 0, 0); 10];
+        pixels.extend([Pixel::new(0, 0, 255); 5]);
+        pixels.extend([Pixel::new(0, 250, 0), Pixel::new(0, 255, 0)]);
+
+        let palette = Palette::median_cut(&pixels, 3);
+        assert_eq!(palette.len(), 3);
+        for color in [Pixel::new(255, 0, 0), Pixel::new(0, 0, 255), Pixel::new(0, 253, 0)] {
+            assert!(palette.colors().contains(&color), "{:?} missing in {:?}", color, palette);
+        }
+        // There are not enough different colors to fill a larger palette.
+        assert_eq!(Palette::median_cut(&pixels, 16).len(), 4);
+        assert_eq!(Palette::median_cut(&gray(2, 2, 10), 1).colors(), &[Pixel::new(10, 10, 10)]);
+    }
+
+    #[test]
+    fn dithering_keeps_average() {
+        let palette = Palette::grayscale(2);
+        let pixels = gray(16, 16, 128);
+        assert_eq!(white_count(&quantize(&pixels, 16, 16, &palette, Dithering::None)), 256);
+
+        let bayer = quantize(&pixels, 16, 16, &palette, Dithering::Bayer { size: 4 });
+        assert_eq!(white_count(&bayer), 128);
+        // The pattern repeats every four pixels.
+        assert!((0..256).all(|index| bayer[index] == bayer[index % 4 + index / 16 % 4 * 16]));
+
+        let floyd_steinberg = white_count(&quantize(&pixels, 16, 16, &palette, Dithering::FloydSteinberg));
+        assert!((120..=136).contains(&floyd_steinberg), "{}", floyd_steinberg);
+        let atkinson = white_count(&quantize(&pixels, 16, 16, &palette, Dithering::Atkinson));
+        assert!((100..=156).contains(&atkinson), "{}", atkinson);
+    }
+
+    #[test]
+    fn cached_search_matches_nearest() {
+        let pixels: Vec<Pixel> = (0..64 * 32).map(|index| Pixel::new((index % 64 * 4) as u8, (index / 64 * 8) as u8, (index % 7 * 36) as u8)).collect();
+        let palette = Palette::median_cut(&pixels, 16);
+        let channels = |pixel: &Pixel| [pixel.red as f32, pixel.green as f32, pixel.blue as f32];
+
+        let direct: Vec<u8> = pixels.iter().map(|pixel| palette.nearest(channels(pixel)) as u8).collect();
+        assert_eq!(quantize(&pixels, 64, 32, &palette, Dithering::None), direct);
+
+        let (matrix, spread) = (bayer_matrix(4), palette.spacing());
+        let direct: Vec<u8> = pixels.iter().enumerate().map(|(index, pixel)| {
+            let threshold = (matrix[index / 64 % 4 * 4 + index % 4] as f32 + 0.5) / 16.0 - 0.5;
+            palette.nearest(channels(pixel).map(|channel| channel + threshold * spread)) as u8
+        }).collect();
+        assert_eq!(quantize(&pixels, 64, 32, &palette, Dithering::Bayer { size: 4 }), direct);
+    }
+
+    #[test]
+    fn indexed_image() {
+        let mut bitmap = Bitmap::new(3, 2);
+        bitmap.fill(&(0, 0, 0));
+        bitmap.set_pixel(1, 1, Pixel::new(200, 180, 20));
+        let image = bitmap.quantized(&Palette::game_boy(), Dithering::None);
+        assert_eq!(image.indices, vec![0, 0, 0, 0, 3, 0]);
+        assert_eq!(image.to_bitmap().get_pixel(1, 1), Some(Pixel::new(0x9B, 0xBC, 0x0F)));
+
+        bitmap.dither(&Palette::cga(), Dithering::FloydSteinberg);
+        assert!(bitmap.pixels().iter().all(|pixel| Palette::cga().colors().contains(pixel)));
+    }
+}
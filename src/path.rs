@@ -0,0 +1,247 @@
+type Point = (f32, f32);
+
+/// Maximal depth of recursive subdivision, a curve is split into at most 2^MAX_DEPTH segments.
+const MAX_DEPTH: u32 = 16;
+
+
+/// Single drawing command of a `Path`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PathCommand {
+    /// Starts new contour at the point.
+    MoveTo(Point),
+    /// Straight line to the point.
+    LineTo(Point),
+    /// Quadratic Bézier curve with a control point, to the end point.
+    QuadTo(Point, Point),
+    /// Cubic Bézier curve with two control points, to the end point.
+    CubicTo(Point, Point, Point),
+    /// Closes current contour with a straight line to its first point.
+    Close,
+}
+
+
+/// Polyline approximating one contour of a path.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Contour {
+    pub points: Vec<Point>,
+    /// Whether the last point connects back to the first one.
+    pub closed: bool,
+}
+
+
+/// Vector shape made of straight lines and Bézier curves.
+///
+/// Path can consist of several contours, each started with `move_to`.
+/// Drawing commands issued before the first `move_to` start at the origin.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Path {
+    commands: Vec<PathCommand>,
+}
+
+
+impl Path {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn commands(&self) -> &[PathCommand] {
+        &self.commands
+    }
+
+    pub fn move_to(&mut self, x: f32, y: f32) -> &mut Self {
+        self.commands.push(PathCommand::MoveTo((x, y)));
+        self
+    }
+
+    pub fn line_to(&mut self, x: f32, y: f32) -> &mut Self {
+        self.commands.push(PathCommand::LineTo((x, y)));
+        self
+    }
+
+    pub fn quad_to(&mut self, control_x: f32, control_y: f32, x: f32, y: f32) -> &mut Self {
+        self.commands.push(PathCommand::QuadTo((control_x, control_y), (x, y)));
+        self
+    }
+
+    pub fn cubic_to(&mut self, control1_x: f32, control1_y: f32, control2_x: f32, control2_y: f32, x: f32, y: f32) -> &mut Self {
+        self.commands.push(PathCommand::CubicTo((control1_x, control1_y), (control2_x, control2_y), (x, y)));
+        self
+    }
+
+    pub fn close(&mut self) -> &mut Self {
+        self.commands.push(PathCommand::Close);
+        self
+    }
+
+    /// Approximates the path with polylines, no point of the curves is farther than `tolerance` from them.
+    pub fn flatten(&self, tolerance: f32) -> Vec<Contour> {
+        let mut contours = Vec::new();
+        let mut current = Contour { points: vec![(0.0, 0.0)], closed: false };
+
+        for command in &self.commands {
+            let last = *current.points.last().unwrap();
+            match *command {
+                PathCommand::MoveTo(point) => {
+                    finish_contour(&mut contours, &mut current, point);
+                },
+                PathCommand::LineTo(point) => current.points.push(point),
+                PathCommand::QuadTo(control, end) => flatten_quadratic(last, control, end, tolerance, &mut current.points),
+                PathCommand::CubicTo(control1, control2, end) => flatten_cubic(last, control1, control2, end, tolerance, &mut current.points),
+                PathCommand::Close => {
+                    current.closed = true;
+                    let start = current.points[0];
+                    finish_contour(&mut contours, &mut current, start);
+                },
+            }
+        }
+        finish_contour(&mut contours, &mut current, (0.0, 0.0));
+        contours
+    }
+}
+
+
+/// Stores current contour unless it is empty and starts new one at `start`.
+fn finish_contour(contours: &mut Vec<Contour>, current: &mut Contour, start: Point) {
+    let next = Contour { points: vec![start], closed: false };
+    let contour = std::mem::replace(current, next);
+    if contour.points.len() > 1 {
+        contours.push(contour);
+    }
+}
+
+
+/// Appends points approximating quadratic Bézier curve, except for the starting point `p0`.
+pub fn flatten_quadratic(p0: Point, p1: Point, p2: Point, tolerance: f32, points: &mut Vec<Point>) {
+    subdivide_quadratic(p0, p1, p2, tolerance, MAX_DEPTH, points);
+}
+
+
+fn subdivide_quadratic(p0: Point, p1: Point, p2: Point, tolerance: f32, depth: u32, points: &mut Vec<Point>) {
+    // Curve point at t lies no farther from the chord point at t than half the distance of the control point
+    // from the middle of the chord, which also holds for control points lying on the chord line outside of it.
+    if depth == 0 || distance(p1, midpoint(p0, p2)) / 2.0 <= tolerance {
+        points.push(p2);
+        return;
+    }
+    // De Casteljau's algorithm splitting the curve in half.
+    let (p01, p12) = (midpoint(p0, p1), midpoint(p1, p2));
+    let middle = midpoint(p01, p12);
+    subdivide_quadratic(p0, p01, middle, tolerance, depth - 1, points);
+    subdivide_quadratic(middle, p12, p2, tolerance, depth - 1, points);
+}
+
+
+/// Appends points approximating cubic Bézier curve, except for the starting point `p0`.
+pub fn flatten_cubic(p0: Point, p1: Point, p2: Point, p3: Point, tolerance: f32, points: &mut Vec<Point>) {
+    subdivide_cubic(p0, p1, p2, p3, tolerance, MAX_DEPTH, points);
+}
+
+
+fn subdivide_cubic(p0: Point, p1: Point, p2: Point, p3: Point, tolerance: f32, depth: u32, points: &mut Vec<Point>) {
+    // Curve point at t lies no farther from the chord point at t than 3/4 of the larger distance
+    // of a control point from the chord point at 1/3 or 2/3 respectively.
+    let third = |a: Point, b: Point| ((2.0 * a.0 + b.0) / 3.0, (2.0 * a.1 + b.1) / 3.0);
+    let deviation = distance(p1, third(p0, p3)).max(distance(p2, third(p3, p0)));
+    if depth == 0 || deviation * 0.75 <= tolerance {
+        points.push(p3);
+        return;
+    }
+    let (p01, p12, p23) = (midpoint(p0, p1), midpoint(p1, p2), midpoint(p2, p3));
+    let (p012, p123) = (midpoint(p01, p12), midpoint(p12, p23));
+    let middle = midpoint(p012, p123);
+    subdivide_cubic(p0, p01, p012, middle, tolerance, depth - 1, points);
+    subdivide_cubic(middle, p123, p23, p3, tolerance, depth - 1, points);
+}
+
+
+fn midpoint(a: Point, b: Point) -> Point {
+    ((a.0 + b.0) / 2.0, (a.1 + b.1) / 2.0)
+}
+
+
+fn distance(a: Point, b: Point) -> f32 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+
+/// Test suite for path flattening.
+#[cfg(test)]
+mod test_path {
+    use super::*;
+
+    fn cubic(p0: Point, p1: Point, p2: Point, p3: Point, t: f32) -> Point {
+        let s = 1.0 - t;
+        let weights = [s * s * s, 3.0 * s * s * t, 3.0 * s * t * t, t * t * t];
+        let x = weights[0] * p0.0 + weights[1] * p1.0 + weights[2] * p2.0 + weights[3] * p3.0;
+        let y = weights[0] * p0.1 + weights[1] * p1.1 + weights[2] * p2.1 + weights[3] * p3.1;
+        (x, y)
+    }
+
+    fn distance_to_polyline(point: Point, polyline: &[Point]) -> f32 {
+        polyline
+            .windows(2)
+            .map(|segment| {
+                let [a, b] = [segment[0], segment[1]];
+                let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+                let t = (((point.0 - a.0) * dx + (point.1 - a.1) * dy) / (dx * dx + dy * dy)).clamp(0.0, 1.0);
+                (point.0 - a.0 - t * dx).hypot(point.1 - a.1 - t * dy)
+            })
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    #[test]
+    fn straight_curves_are_single_segments() {
+        let mut points = Vec::new();
+        flatten_quadratic((0.0, 0.0), (5.0, 5.0), (10.0, 10.0), 0.25, &mut points);
+        assert_eq!(points, vec![(10.0, 10.0)]);
+
+        points.clear();
+        flatten_cubic((0.0, 0.0), (3.0, 0.0), (6.0, 0.0), (9.0, 0.0), 0.25, &mut points);
+        assert_eq!(points, vec![(9.0, 0.0)]);
+    }
+
+    #[test]
+    fn cubic_within_tolerance() {
+        let (p0, p1, p2, p3) = ((0.0, 0.0), (0.0, 100.0), (200.0, -50.0), (100.0, 100.0));
+        let mut points = vec![p0];
+        flatten_cubic(p0, p1, p2, p3, 0.25, &mut points);
+
+        assert!(points.len() > 4);
+        for step in 0..=100 {
+            let point = cubic(p0, p1, p2, p3, step as f32 / 100.0);
+            assert!(distance_to_polyline(point, &points) <= 0.25);
+        }
+    }
+
+    #[test]
+    fn collinear_control_points() {
+        // Control points lie on the chord line but outside of the chord, the curves overshoot their end points.
+        let (p0, p1, p2) = ((0.0, 0.0), (20.0, 0.0), (10.0, 0.0));
+        let mut points = vec![p0];
+        flatten_quadratic(p0, p1, p2, 0.25, &mut points);
+        assert!(points.iter().any(|point| point.0 >= 13.33 - 0.25), "{:?}", points);
+
+        let (p0, p1, p2, p3) = ((0.0, 0.0), (30.0, 0.0), (-20.0, 0.0), (10.0, 0.0));
+        let mut points = vec![p0];
+        flatten_cubic(p0, p1, p2, p3, 0.25, &mut points);
+        for step in 0..=100 {
+            let point = cubic(p0, p1, p2, p3, step as f32 / 100.0);
+            assert!(distance_to_polyline(point, &points) <= 0.25, "{:?}", point);
+        }
+    }
+
+    #[test]
+    fn contours() {
+        let mut path = Path::new();
+        path.move_to(0.0, 0.0).line_to(10.0, 0.0).line_to(10.0, 10.0).close()
+            .move_to(20.0, 0.0).quad_to(25.0, 10.0, 30.0, 0.0)
+            .move_to(50.0, 50.0);
+        let contours = path.flatten(0.25);
+
+        assert_eq!(contours.len(), 2);
+        assert_eq!(contours[0], Contour { points: vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)], closed: true });
+        assert!(!contours[1].closed);
+        assert_eq!(contours[1].points.first(), Some(&(20.0, 0.0)));
+        assert_eq!(contours[1].points.last(), Some(&(30.0, 0.0)));
+    }
+}
@@ -0,0 +1,491 @@
+use std::cmp::Ordering::Equal;
+use std::time::{Duration, Instant};
+
+use nalgebra as na;
+
+use crate::graphics::{draw_point, draw_triangle_outline, perspective_correct, walk_triangle};
+use crate::shader::{FragmentShader, Varying, VertexShader};
+use crate::view::RenderTarget;
+
+type Rgb = (u8, u8, u8);
+type P3 = na::Point3<f32>;
+type V4 = na::Vector4<f32>;
+
+/// Side length of squares drawn in place of vertices in points render mode.
+const POINT_SIZE: i32 = 3;
+
+
+/// Triangle after vertex processing, ready to be rasterized.
+struct Primitive<T> {
+    /// Vertices in screen space, `z` holds normalized depth.
+    screen: [P3; 3],
+    /// Reciprocals of clip space `w`, used for perspective correct interpolation.
+    inverse_w: [f32; 3],
+    varyings: [T; 3],
+}
+
+
+impl<T> Primitive<T> {
+    fn depth(&self) -> f32 {
+        (self.screen[0].z + self.screen[1].z + self.screen[2].z) / 3.0
+    }
+}
+
+
+/// Order in which vertices of a triangle appear on screen, as seen with the y axis pointing up.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Winding {
+    Clockwise,
+    CounterClockwise,
+}
+
+
+impl Winding {
+    /// Returns winding of a triangle given in screen space (y axis pointing down), `None` for degenerate triangles.
+    pub fn of(vertices: &[P3; 3]) -> Option<Self> {
+        let area = signed_area(vertices);
+        if area < 0.0 {
+            Some(Winding::CounterClockwise)
+        } else if area > 0.0 {
+            Some(Winding::Clockwise)
+        } else {
+            None
+        }
+    }
+}
+
+
+/// Which faces are discarded before rasterization.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CullMode {
+    /// All triangles are drawn.
+    None,
+    /// Triangles facing away from the camera are discarded.
+    Back,
+    /// Triangles facing the camera are discarded.
+    Front,
+}
+
+
+impl CullMode {
+    /// Returns whether triangle with given winding should be discarded when `front_face` winding marks faces facing the camera.
+    pub fn culls(&self, winding: Winding, front_face: Winding) -> bool {
+        match self {
+            CullMode::None => false,
+            CullMode::Back => winding != front_face,
+            CullMode::Front => winding == front_face,
+        }
+    }
+}
+
+
+/// What is drawn for every triangle.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RenderMode {
+    /// Only triangle edges.
+    Wireframe,
+    /// Only filled triangles.
+    Solid,
+    /// Filled triangles with their edges drawn on top.
+    SolidWireframe,
+    /// Only vertices.
+    Points,
+}
+
+
+impl RenderMode {
+    /// Returns the next mode, used for cycling through all of them.
+    pub fn next(&self) -> Self {
+        match self {
+            RenderMode::Wireframe => RenderMode::Solid,
+            RenderMode::Solid => RenderMode::SolidWireframe,
+            RenderMode::SolidWireframe => RenderMode::Points,
+            RenderMode::Points => RenderMode::Wireframe,
+        }
+    }
+}
+
+
+/// Counters and timings collected by the pipeline during draw calls.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct PipelineStats {
+    /// Triangles passed to the pipeline.
+    pub triangles_submitted: usize,
+    /// Triangles cut by the near plane, including the ones lying wholly behind it.
+    pub triangles_clipped: usize,
+    /// Triangles discarded by face culling, including degenerate ones.
+    pub triangles_culled: usize,
+    /// Triangles that reached rasterization, a triangle cut by the near plane may turn into two.
+    pub triangles_drawn: usize,
+    /// Fragment shader invocations.
+    pub pixels_shaded: usize,
+    /// Vertex shading, primitive assembly and culling.
+    pub vertex_time: Duration,
+    /// Depth sorting.
+    pub sort_time: Duration,
+    /// Rasterization with fragment shading, edges and points.
+    pub raster_time: Duration,
+}
+
+
+impl std::ops::AddAssign for PipelineStats {
+    fn add_assign(&mut self, other: Self) {
+        self.triangles_submitted += other.triangles_submitted;
+        self.triangles_clipped += other.triangles_clipped;
+        self.triangles_culled += other.triangles_culled;
+        self.triangles_drawn += other.triangles_drawn;
+        self.pixels_shaded += other.pixels_shaded;
+        self.vertex_time += other.vertex_time;
+        self.sort_time += other.sort_time;
+        self.raster_time += other.raster_time;
+    }
+}
+
+
+/// Software rendering pipeline.
+///
+/// Runs the vertex shader on every vertex, assembles triangles, clips them by the near plane, culls faces according to `cull_mode`,
+/// sorts triangles back to front (painter's algorithm) and rasterizes them running the fragment shader on every covered pixel.
+#[derive(Copy, Clone, Debug)]
+pub struct Pipeline {
+    pub render_mode: RenderMode,
+    /// Color of edges and points in wireframe and points render modes.
+    pub wireframe_color: Rgb,
+    pub cull_mode: CullMode,
+    /// Winding of the triangles facing the camera.
+    pub front_face: Winding,
+    /// Clip space `w` of the near plane, parts of triangles closer to the camera are cut off.
+    ///
+    /// Perspective projections make `w` the distance from the camera, so it should match their near plane.
+    pub near: f32,
+}
+
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Self { render_mode: RenderMode::SolidWireframe, wireframe_color: (0, 0, 0), cull_mode: CullMode::Back, front_face: Winding::CounterClockwise, near: 0.1 }
+    }
+}
+
+
+impl Pipeline {
+    /// Draws triangle list, every consecutive three vertices form a triangle.
+    ///
+    /// Triangles crossing the near plane are cut along it, parts behind it are skipped. Returns statistics of the draw call.
+    pub fn draw<T, U, V, F>(&self, bitmap: &mut T, uniforms: &U, vertex_shader: &V, fragment_shader: &F, vertices: &[V::Vertex]) -> PipelineStats
+    where
+        T: RenderTarget,
+        V: VertexShader<U>,
+        F: FragmentShader<U, Varying = V::Varying>,
+    {
+        let (width, height) = (bitmap.width() as f32, bitmap.height() as f32);
+        let mut primitives = Vec::with_capacity(vertices.len() / 3);
+        let mut stats = PipelineStats { triangles_submitted: vertices.len() / 3, ..Default::default() };
+        let start = Instant::now();
+
+        for triangle in vertices.chunks_exact(3) {
+            let shaded = [0, 1, 2].map(|index| vertex_shader.vertex(uniforms, &triangle[index]));
+            if shaded.iter().any(|(clip, _)| clip.w < self.near) {
+                stats.triangles_clipped += 1;
+            }
+
+            // The clipped polygon is convex, so it is drawn as a fan of triangles sharing its first vertex.
+            let polygon = clip_near(&shaded, self.near);
+            for index in 1..polygon.len().saturating_sub(1) {
+                let fan = [0, index, index + 1].map(|index| polygon[index]);
+
+                // Perspective division followed by viewport transform,
+                // normalized device coordinates grow upwards, bitmap rows grow downwards.
+                let screen = fan.map(|(clip, _)| P3::new(
+                    (clip.x / clip.w + 1.0) * width / 2.0,
+                    (1.0 - clip.y / clip.w) * height / 2.0,
+                    clip.z / clip.w,
+                ));
+
+                // Facing is decided after projection, so it is correct for any projection.
+                match Winding::of(&screen) {
+                    Some(winding) if !self.cull_mode.culls(winding, self.front_face) => {},
+                    _ => {
+                        stats.triangles_culled += 1;
+                        continue;
+                    },
+                }
+
+                primitives.push(Primitive {
+                    screen,
+                    inverse_w: fan.map(|(clip, _)| 1.0 / clip.w),
+                    varyings: fan.map(|(_, varying)| varying),
+                });
+            }
+        }
+
+        let sorting = Instant::now();
+        stats.vertex_time = sorting - start;
+
+        // Painter's algorithm, the farthest triangles are drawn first.
+        primitives.sort_unstable_by(|primitive1, primitive2| {
+            primitive2.depth().partial_cmp(&primitive1.depth()).unwrap_or(Equal)
+        });
+
+        let rasterization = Instant::now();
+        stats.sort_time = rasterization - sorting;
+        stats.triangles_drawn = primitives.len();
+
+        for primitive in &primitives {
+            let [v0, v1, v2] = &primitive.screen;
+            if let RenderMode::Solid | RenderMode::SolidWireframe = self.render_mode {
+                walk_triangle(&bitmap.rect(), v0, v1, v2, |x, y, barycentric| {
+                    stats.pixels_shaded += 1;
+                    let weights = perspective_correct(barycentric, &primitive.inverse_w);
+                    if let Some(color) = fragment_shader.fragment(uniforms, &V::Varying::interpolate(&primitive.varyings, &weights)) {
+                        bitmap.set_color(x, y, color);
+                    }
+                });
+            }
+            // Edges are drawn right after the fill, so that triangles closer to the camera still cover them.
+            if let RenderMode::Wireframe | RenderMode::SolidWireframe = self.render_mode {
+                draw_triangle_outline(bitmap,
+                                      v0.x as i32,
+                                      v0.y as i32,
+                                      v1.x as i32,
+                                      v1.y as i32,
+                                      v2.x as i32,
+                                      v2.y as i32,
+                                      &self.wireframe_color);
+            }
+            if let RenderMode::Points = self.render_mode {
+                for vertex in &primitive.screen {
+                    draw_point(bitmap, vertex.x as i32, vertex.y as i32, POINT_SIZE, &self.wireframe_color);
+                }
+            }
+        }
+
+        stats.raster_time = rasterization.elapsed();
+        stats
+    }
+}
+
+
+/// Cuts off parts of the clip space triangle with `w` below `near` (Sutherland-Hodgman).
+///
+/// Returns vertices of the remaining convex polygon in the original order, three or four of them, or none at all.
+/// Varyings of the new vertices are interpolated along the cut edges.
+fn clip_near<T: Varying>(triangle: &[(V4, T); 3], near: f32) -> Vec<(V4, T)> {
+    let varyings = triangle.map(|(_, varying)| varying);
+    let mut polygon = Vec::with_capacity(4);
+    for index in 0..3 {
+        let next = (index + 1) % 3;
+        let (current, following) = (triangle[index].0, triangle[next].0);
+        if current.w >= near {
+            polygon.push(triangle[index]);
+        }
+        if (current.w >= near) != (following.w >= near) {
+            let t = (near - current.w) / (following.w - current.w);
+            let mut weights = [0.0; 3];
+            weights[index] = 1.0 - t;
+            weights[next] = t;
+            polygon.push((current.lerp(&following, t), T::interpolate(&varyings, &weights)));
+        }
+    }
+    polygon
+}
+
+
+/// Twice the signed area of the triangle in screen space.
+fn signed_area(vertices: &[P3; 3]) -> f32 {
+    let [v0, v1, v2] = vertices;
+    (v1.x - v0.x) * (v2.y - v0.y) - (v1.y - v0.y) * (v2.x - v0.x)
+}
+
+
+/// Test suite for face culling.
+#[cfg(test)]
+mod test_culling {
+    use super::*;
+
+    #[test]
+    fn winding_in_screen_space() {
+        // Screen y axis points down, so this triangle is counter clockwise when seen with y pointing up.
+        let counter_clockwise = [P3::new(0.0, 0.0, 0.0), P3::new(0.0, 1.0, 0.0), P3::new(1.0, 0.0, 0.0)];
+        let clockwise = [P3::new(0.0, 0.0, 0.0), P3::new(1.0, 0.0, 0.0), P3::new(0.0, 1.0, 0.0)];
+        let degenerate = [P3::new(0.0, 0.0, 0.0), P3::new(1.0, 1.0, 0.0), P3::new(2.0, 2.0, 0.0)];
+
+        assert_eq!(Winding::of(&counter_clockwise), Some(Winding::CounterClockwise));
+        assert_eq!(Winding::of(&clockwise), Some(Winding::Clockwise));
+        assert_eq!(Winding::of(&degenerate), None);
+    }
+
+    #[test]
+    fn cull_modes() {
+        use Winding::*;
+
+        assert!(!CullMode::None.culls(Clockwise, CounterClockwise));
+        assert!(!CullMode::None.culls(CounterClockwise, CounterClockwise));
+
+        assert!(CullMode::Back.culls(Clockwise, CounterClockwise));
+        assert!(!CullMode::Back.culls(CounterClockwise, CounterClockwise));
+        assert!(!CullMode::Back.culls(Clockwise, Clockwise));
+
+        assert!(CullMode::Front.culls(CounterClockwise, CounterClockwise));
+        assert!(!CullMode::Front.culls(Clockwise, CounterClockwise));
+    }
+}
+
+
+/// Test suite for pipeline statistics and render modes.
+#[cfg(test)]
+mod test_stats {
+    use super::*;
+    use crate::{Bitmap, Pixel};
+    use crate::color::LinearColor;
+
+    type V4 = na::Vector4<f32>;
+
+    /// Passes clip space positions through and colors everything white.
+    struct Passthrough;
+
+    impl VertexShader<()> for Passthrough {
+        type Vertex = V4;
+        type Varying = ();
+
+        fn vertex(&self, _uniforms: &(), vertex: &V4) -> (V4, ()) {
+            (*vertex, ())
+        }
+    }
+
+    impl FragmentShader<()> for Passthrough {
+        type Varying = ();
+
+        fn fragment(&self, _uniforms: &(), _varying: &()) -> Option<LinearColor> {
+            Some(LinearColor::new(1.0, 1.0, 1.0))
+        }
+    }
+
+    #[test]
+    fn triangle_counts() {
+        let mut bitmap = Bitmap::new(10, 10);
+        let pipeline = Pipeline { render_mode: RenderMode::Solid, ..Default::default() };
+        let (bottom_left, bottom_right, top_left) = (V4::new(-1.0, -1.0, 0.0, 1.0), V4::new(1.0, -1.0, 0.0, 1.0), V4::new(-1.0, 1.0, 0.0, 1.0));
+        let behind = V4::new(0.0, 0.0, 0.0, -1.0);
+        let vertices = [
+            bottom_left, bottom_right, top_left,
+            bottom_left, top_left, bottom_right,
+            bottom_left, bottom_right, behind,
+        ];
+
+        let stats = pipeline.draw(&mut bitmap, &(), &Passthrough, &Passthrough, &vertices);
+        assert_eq!(stats.triangles_submitted, 3);
+        assert_eq!(stats.triangles_clipped, 1);
+        // The second triangle and both halves of the clipped one face away.
+        assert_eq!(stats.triangles_culled, 3);
+        assert_eq!(stats.triangles_drawn, 1);
+        // Half of the bitmap, pixels centered on the diagonal lie on its right edge and are left out.
+        assert_eq!(stats.pixels_shaded, 45);
+
+        let mut total = stats;
+        total += stats;
+        assert_eq!(total.pixels_shaded, 90);
+    }
+
+    #[test]
+    fn partly_off_screen() {
+        // First vertex lies left of the target, the last one is almost on the camera plane,
+        // its edges are cut by the near plane and still end far to the right.
+        let vertices = [V4::new(-3.0, -0.5, 0.0, 1.0), V4::new(0.4, 0.2, 0.0, 1.0), V4::new(1000.0, 0.0, 0.0, 1e-6)];
+        let black = Some(Pixel::from_rgb_tuple((0, 0, 0)));
+        for render_mode in [RenderMode::Wireframe, RenderMode::SolidWireframe, RenderMode::Points] {
+            let mut bitmap = Bitmap::new(10, 10);
+            let pipeline = Pipeline { render_mode, cull_mode: CullMode::None, ..Default::default() };
+            let stats = pipeline.draw(&mut bitmap, &(), &Passthrough, &Passthrough, &vertices);
+            assert_eq!(stats.triangles_drawn, 2);
+
+            // Vertex inside of the target and the edge crossing the whole target.
+            assert_eq!(bitmap.get_pixel(7, 4), black, "{:?}", render_mode);
+            assert_eq!(bitmap.get_pixel(9, 7) == black, render_mode != RenderMode::Points, "{:?}", render_mode);
+        }
+    }
+
+    #[test]
+    fn near_plane() {
+        let mut bitmap = Bitmap::new(10, 10);
+        let pipeline = Pipeline { render_mode: RenderMode::Solid, ..Default::default() };
+        // The top vertex lies behind the camera, the part in front of the near plane covers the whole target.
+        let vertices = [V4::new(-1.0, -1.0, 0.0, 1.0), V4::new(1.0, -1.0, 0.0, 1.0), V4::new(0.0, 2.0, 0.0, -1.0)];
+        let stats = pipeline.draw(&mut bitmap, &(), &Passthrough, &Passthrough, &vertices);
+        assert_eq!(stats.triangles_clipped, 1);
+        assert_eq!(stats.triangles_drawn, 2);
+        assert_eq!(stats.pixels_shaded, 100);
+        assert!(bitmap.pixels().iter().all(|pixel| *pixel == Pixel::from_rgb_tuple((255, 255, 255))));
+
+        // Whole triangle behind the near plane.
+        let behind = vertices.map(|vertex| V4::new(vertex.x, vertex.y, 0.0, 0.05));
+        let stats = pipeline.draw(&mut bitmap, &(), &Passthrough, &Passthrough, &behind);
+        assert_eq!((stats.triangles_clipped, stats.triangles_drawn), (1, 0));
+    }
+
+    #[test]
+    fn clipped_varyings() {
+        let triangle = [(V4::new(0.0, 0.0, 0.0, 1.0), 0.0), (V4::new(1.0, 0.0, 0.0, 1.0), 1.0), (V4::new(0.0, 1.0, 0.0, -1.0), 2.0)];
+        let polygon = clip_near(&triangle, 0.5);
+        assert_eq!(polygon.len(), 4);
+        assert_eq!(polygon[..2], triangle[..2]);
+        // New vertices lie a quarter of the way towards the vertex behind the camera.
+        assert_eq!(polygon[2], (V4::new(0.75, 0.25, 0.0, 0.5), 1.25));
+        assert_eq!(polygon[3], (V4::new(0.0, 0.25, 0.0, 0.5), 0.5));
+        assert!(clip_near(&triangle, 2.0).is_empty());
+    }
+}
+
+
+/// Test suite for custom shaders driven by the pipeline.
+#[cfg(test)]
+mod test_varyings {
+    use super::*;
+    use crate::color::LinearColor;
+    use crate::format::{RgbaF32, Surface};
+
+    type V4 = na::Vector4<f32>;
+
+    /// Passes clip space positions through and writes the interpolated attribute to the red channel.
+    struct Attribute;
+
+    impl VertexShader<()> for Attribute {
+        type Vertex = (V4, f32);
+        type Varying = f32;
+
+        fn vertex(&self, _uniforms: &(), vertex: &(V4, f32)) -> (V4, f32) {
+            *vertex
+        }
+    }
+
+    impl FragmentShader<()> for Attribute {
+        type Varying = f32;
+
+        fn fragment(&self, _uniforms: &(), varying: &f32) -> Option<LinearColor> {
+            Some(LinearColor::new(*varying, 0.0, 0.0))
+        }
+    }
+
+    #[test]
+    fn interpolated_attributes() {
+        let pipeline = Pipeline { render_mode: RenderMode::Solid, cull_mode: CullMode::None, ..Default::default() };
+        // Triangle covering the whole 4 x 4 target, the attribute grows from 0 on the left to 1 at x = 8.
+        for far_w in [1.0, 2.0] {
+            let vertices = [
+                (V4::new(-1.0, -1.0, 0.0, 1.0), 0.0),
+                (V4::new(3.0, -1.0, 0.0, 1.0) * far_w, 1.0),
+                (V4::new(-1.0, 3.0, 0.0, 1.0), 0.0),
+            ];
+            let mut target = Surface::<RgbaF32>::new(4, 4);
+            let stats = pipeline.draw(&mut target, &(), &Attribute, &Attribute, &vertices);
+            assert_eq!(stats.pixels_shaded, 16);
+
+            for (index, color) in target.pixels().iter().enumerate() {
+                // Screen space weight of the second vertex, corrected for its depth.
+                let screen = ((index % 4) as f32 + 0.5) / 8.0;
+                let expected = (screen / far_w) / (1.0 - screen + screen / far_w);
+                assert!((color.red - expected).abs() < 1e-5, "{} {} {}", far_w, color.red, expected);
+            }
+        }
+    }
+}
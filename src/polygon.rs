@@ -0,0 +1,192 @@
+use crate::Pixel;
+use crate::view::RenderTarget;
+use crate::bitmap::Rect;
+
+type Rgb = (u8, u8, u8);
+type Point = (f32, f32);
+
+
+/// Decides which parts of a self-intersecting polygon, or polygon made of several contours, are inside.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FillRule {
+    /// Point is inside when a ray from it crosses the outline odd number of times.
+    /// Nested contours alternate between filled and holes regardless of their direction.
+    EvenOdd,
+    /// Point is inside when the outline winds around it at least once.
+    /// Holes have to go in the direction opposite to the contour they are cut from.
+    NonZero,
+}
+
+
+/// Edge of the polygon in the edge tables.
+#[derive(Copy, Clone, Debug)]
+struct Edge {
+    /// First row whose pixel centers the edge crosses, rows above the clipping rectangle are left out.
+    first_row: i32,
+    /// Row after the last one whose pixel centers the edge crosses.
+    end_row: i32,
+    /// Horizontal position of the edge at the center of the current row.
+    x: f32,
+    /// Change of `x` between consecutive rows.
+    inverse_slope: f32,
+    /// +1 for edges going down, -1 for edges going up.
+    winding: i32,
+}
+
+
+/// Returns range of pixels whose centers lie between `start` and `end`, clamped to `min` - `max`.
+pub fn pixel_range(start: f32, end: f32, min: i32, max: i32) -> std::ops::Range<i32> {
+    let first = ((start - 0.5).ceil() as i32).max(min);
+    let last = ((end - 0.5).ceil() as i32).min(max);
+    first..last.max(first)
+}
+
+
+/// Calls `span(y, x_range)` for every horizontal run of pixels inside the polygon.
+///
+/// Polygon may consist of several closed contours, which can intersect each other and themselves.
+/// Implementation of scanline algorithm with an active edge table, pixels are sampled at their centers.
+/// Pixels outside of `clip` are skipped.
+pub fn rasterize_polygon<F: FnMut(i32, std::ops::Range<i32>)>(contours: &[&[Point]], rule: FillRule, clip: &Rect, mut span: F) {
+    // Edge table, all edges sorted by the row they start at.
+    let mut edges = Vec::new();
+    for contour in contours {
+        for index in 0..contour.len() {
+            let (a, b) = (contour[index], contour[(index + 1) % contour.len()]);
+            let (top, bottom, winding) = if a.1 < b.1 { (a, b, 1) } else { (b, a, -1) };
+            // Rows above the clip are never visited, so far away vertices do not make the row distances overflow.
+            let first_row = ((top.1 - 0.5).ceil() as i32).max(clip.y);
+            let end_row = (bottom.1 - 0.5).ceil() as i32;
+            if first_row >= end_row {
+                // Horizontal edges, edges between two pixel centers and edges above the clip never cross a row.
+                continue;
+            }
+            let inverse_slope = (bottom.0 - top.0) / (bottom.1 - top.1);
+            let x = top.0 + (first_row as f32 + 0.5 - top.1) * inverse_slope;
+            edges.push(Edge { first_row, end_row, x, inverse_slope, winding });
+        }
+    }
+    if edges.is_empty() {
+        return;
+    }
+    edges.sort_unstable_by_key(|edge| edge.first_row);
+
+    let first_row = edges[0].first_row.max(clip.y);
+    let end_row = edges.iter().map(|edge| edge.end_row).max().unwrap_or(first_row).min(clip.bottom());
+
+    let mut active: Vec<Edge> = Vec::new();
+    let mut next_edge = 0;
+    for y in first_row..end_row {
+        // Move edges starting at this row to the active edge table.
+        while next_edge < edges.len() && edges[next_edge].first_row <= y {
+            let mut edge = edges[next_edge];
+            edge.x += edge.inverse_slope * (y - edge.first_row) as f32;
+            active.push(edge);
+            next_edge += 1;
+        }
+        active.retain(|edge| edge.end_row > y);
+        active.sort_unstable_by(|edge1, edge2| edge1.x.total_cmp(&edge2.x));
+
+        let mut winding = 0;
+        let mut span_start = 0.0;
+        for edge in &active {
+            let was_inside = inside(winding, rule);
+            winding += edge.winding;
+            match (was_inside, inside(winding, rule)) {
+                (false, true) => span_start = edge.x,
+                (true, false) => span(y, pixel_range(span_start, edge.x, clip.x, clip.right())),
+                _ => {},
+            }
+        }
+
+        for edge in &mut active {
+            edge.x += edge.inverse_slope;
+        }
+    }
+}
+
+
+fn inside(winding: i32, rule: FillRule) -> bool {
+    match rule {
+        FillRule::EvenOdd => winding % 2 != 0,
+        FillRule::NonZero => winding != 0,
+    }
+}
+
+
+/// Fills polygon made of one or more closed contours.
+///
+/// Pixels outside of the bitmap are skipped.
+pub fn fill_polygon<T: RenderTarget>(bitmap: &mut T, contours: &[&[Point]], rule: FillRule, color: &Rgb) {
+    let clip = bitmap.rect();
+    let pixel = Pixel::from_rgb_tuple(*color);
+    rasterize_polygon(contours, rule, &clip, |y, range| {
+        for x in range {
+            bitmap.set_pixel(x as usize, y as usize, pixel);
+        }
+    });
+}
+
+
+/// Test suite for polygon filling.
+#[cfg(test)]
+mod test_polygon {
+    use super::*;
+
+    fn covered(contours: &[&[Point]], rule: FillRule) -> Vec<(i32, i32)> {
+        let mut pixels = Vec::new();
+        rasterize_polygon(contours, rule, &Rect::new(0, 0, 100, 100), |y, range| {
+            pixels.extend(range.map(|x| (x, y)));
+        });
+        pixels
+    }
+
+    #[test]
+    fn square_covers_pixel_centers() {
+        let square: &[Point] = &[(1.0, 1.0), (4.0, 1.0), (4.0, 4.0), (1.0, 4.0)];
+        assert_eq!(covered(&[square], FillRule::EvenOdd).len(), 9);
+        assert_eq!(covered(&[square], FillRule::NonZero).len(), 9);
+    }
+
+    #[test]
+    fn hole() {
+        let outer: &[Point] = &[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)];
+        let same_direction: &[Point] = &[(2.0, 2.0), (8.0, 2.0), (8.0, 8.0), (2.0, 8.0)];
+        let opposite_direction: &[Point] = &[(2.0, 2.0), (2.0, 8.0), (8.0, 8.0), (8.0, 2.0)];
+
+        assert_eq!(covered(&[outer, same_direction], FillRule::EvenOdd).len(), 64);
+        assert_eq!(covered(&[outer, opposite_direction], FillRule::EvenOdd).len(), 64);
+        assert_eq!(covered(&[outer, same_direction], FillRule::NonZero).len(), 100);
+        assert_eq!(covered(&[outer, opposite_direction], FillRule::NonZero).len(), 64);
+    }
+
+    #[test]
+    fn self_intersecting_star() {
+        let star: &[Point] = &[(50.0, 0.0), (80.0, 90.0), (0.0, 35.0), (100.0, 35.0), (20.0, 90.0)];
+        let center = (50, 50);
+        assert!(!covered(&[star], FillRule::EvenOdd).contains(&center));
+        assert!(covered(&[star], FillRule::NonZero).contains(&center));
+    }
+
+    #[test]
+    fn clipping() {
+        let square: &[Point] = &[(-10.0, -10.0), (200.0, -10.0), (200.0, 200.0), (-10.0, 200.0)];
+        let pixels = covered(&[square], FillRule::NonZero);
+        assert_eq!(pixels.len(), 100 * 100);
+        assert!(pixels.iter().all(|&(x, y)| (0..100).contains(&x) && (0..100).contains(&y)));
+    }
+
+    #[test]
+    fn far_vertices() {
+        // Edges ending far away are almost vertical inside of the clipping rectangle.
+        for (apex, base) in [(-1e10, 50.0), (1e10, 50.0)] {
+            let triangle: &[Point] = &[(5.0, apex), (10.0, base), (0.0, base)];
+            let pixels = covered(&[triangle], FillRule::NonZero);
+            assert_eq!(pixels.len(), 10 * 50);
+            assert!(pixels.iter().all(|&(x, y)| (0..10).contains(&x) && (y < 50) == (apex < 0.0)));
+        }
+
+        let wide: &[Point] = &[(-1e10, 2.0), (1e10, 2.0), (1e10, 4.0), (-1e10, 4.0)];
+        assert_eq!(covered(&[wide], FillRule::EvenOdd).len(), 2 * 100);
+    }
+}
@@ -0,0 +1,407 @@
+use crate::Bitmap;
+use crate::color::{LinearColor, srgb_decode, srgb_encode};
+use crate::hdr::HdrBuffer;
+use crate::palette::{Dithering, Palette, dither};
+use crate::view::RenderTarget;
+
+
+/// 3D color lookup table used for color grading.
+///
+/// Maps sRGB encoded colors to new ones, the table has `size` entries on each axis
+/// with red changing the fastest, colors between the entries are interpolated trilinearly.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lut3d {
+    size: usize,
+    table: Vec<[f32; 3]>,
+}
+
+
+impl Lut3d {
+    /// Creates table by sampling the function at `size` points on each axis, values are sRGB encoded in range 0.0 - 1.0.
+    pub fn from_fn<F: Fn([f32; 3]) -> [f32; 3]>(size: usize, function: F) -> Self {
+        let size = size.max(2);
+        let coordinate = |index: usize| index as f32 / (size - 1) as f32;
+        let table = (0..size * size * size)
+            .map(|index| function([coordinate(index % size), coordinate(index / size % size), coordinate(index / (size * size))]))
+            .collect();
+        Self { size, table }
+    }
+
+    /// Table which leaves colors unchanged.
+    pub fn identity(size: usize) -> Self {
+        Self::from_fn(size, |color| color)
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Parses table in the Adobe / Resolve `.cube` format.
+    pub fn from_cube(text: &str) -> Result<Self, std::io::Error> {
+        let error = |message: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("Invalid cube LUT: {}", message));
+        let mut size = None;
+        let mut table = Vec::new();
+
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("LUT_3D_SIZE") => {
+                    size = Some(words.next().and_then(|word| word.parse::<usize>().ok()).ok_or_else(|| error("bad LUT_3D_SIZE"))?);
+                },
+                Some("LUT_1D_SIZE") => return Err(error("1D tables are not supported")),
+                // Colors are looked up in range 0.0 - 1.0, tables made for other ranges would be applied wrongly.
+                Some(keyword @ ("DOMAIN_MIN" | "DOMAIN_MAX")) => {
+                    let expected = if keyword == "DOMAIN_MIN" { 0.0 } else { 1.0 };
+                    let values = words.map(str::parse::<f32>).collect::<Result<Vec<f32>, _>>().map_err(|_| error(line))?;
+                    if values != [expected; 3] {
+                        return Err(error("domain other than 0.0 - 1.0 is not supported"));
+                    }
+                },
+                // Titles carry no information.
+                Some("TITLE") => {},
+                Some(_) => {
+                    let values = line.split_whitespace().map(str::parse::<f32>).collect::<Result<Vec<f32>, _>>().map_err(|_| error(line))?;
+                    match values[..] {
+                        [red, green, blue] => table.push([red, green, blue]),
+                        _ => return Err(error(line)),
+                    }
+                },
+                None => {},
+            }
+        }
+
+        let size = size.ok_or_else(|| error("missing LUT_3D_SIZE"))?;
+        if size < 2 || size.checked_pow(3) != Some(table.len()) {
+            return Err(error("wrong number of entries"));
+        }
+        Ok(Self { size, table })
+    }
+
+    pub fn load(path: &str) -> Result<Self, std::io::Error> {
+        Self::from_cube(&std::fs::read_to_string(path)?)
+    }
+
+    /// Looks up sRGB encoded color, values outside of 0.0 - 1.0 are clamped.
+    pub fn sample(&self, color: [f32; 3]) -> [f32; 3] {
+        let last = (self.size - 1) as f32;
+        let mut base = [0; 3];
+        let mut fraction = [0.0; 3];
+        for axis in 0..3 {
+            let position = color[axis].clamp(0.0, 1.0) * last;
+            base[axis] = (position.floor() as usize).min(self.size - 2);
+            fraction[axis] = position - base[axis] as f32;
+        }
+
+        let mut result = [0.0; 3];
+        for corner in 0..8 {
+            let offset = [corner & 1, corner >> 1 & 1, corner >> 2 & 1];
+            let weight: f32 = (0..3).map(|axis| if offset[axis] == 1 { fraction[axis] } else { 1.0 - fraction[axis] }).product();
+            if weight == 0.0 {
+                continue;
+            }
+            let index = (base[0] + offset[0]) + (base[1] + offset[1]) * self.size + (base[2] + offset[2]) * self.size * self.size;
+            for (channel, value) in result.iter_mut().zip(self.table[index]) {
+                *channel += weight * value;
+            }
+        }
+        result
+    }
+}
+
+
+/// Single step of the post-processing chain.
+///
+/// Effects work with linear colors, color grading ones with their sRGB encoded values.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Effect {
+    /// Average of the square of pixels `radius` pixels around.
+    BoxBlur { radius: usize },
+    /// Blur with gaussian weights of given standard deviation in pixels.
+    GaussianBlur { sigma: f32 },
+    /// Unsharp masking, adds `amount` times the difference between the image and its slightly blurred copy.
+    Sharpen { amount: f32 },
+    /// Glow around bright areas, parts of colors above `threshold` are blurred and added back with `intensity`.
+    Bloom { threshold: f32, intensity: f32, sigma: f32 },
+    /// Darkens the corners, `radius` is the distance from the center where darkening starts,
+    /// relative to the distance of the corners, `strength` of 1.0 makes the corners black.
+    Vignette { radius: f32, strength: f32 },
+    /// Remaps colors with a 3D lookup table.
+    ColorGrade(Lut3d),
+    /// Replaces colors with their luminance.
+    Grayscale,
+    /// Brownish tint of old photographs.
+    Sepia,
+    /// Sobel edge detector, edges are white on black background.
+    EdgeDetect,
+    /// Replaces squares of `size` pixels with their average color.
+    Pixelate { size: usize },
+    /// Reduces colors to the palette, usually as the last effect for the look of old hardware.
+    Quantize { palette: Palette, dithering: Dithering },
+}
+
+
+impl Effect {
+    /// Applies the effect to linear colors of an image of given dimensions stored row by row.
+    pub fn apply(&self, pixels: &mut [LinearColor], width: usize, height: usize) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        match self {
+            Effect::BoxBlur { radius } => {
+                let kernel = vec![1.0 / (2 * radius + 1) as f32; 2 * radius + 1];
+                convolve_separable(pixels, width, height, &kernel);
+            },
+            Effect::GaussianBlur { sigma } => convolve_separable(pixels, width, height, &gaussian_kernel(*sigma)),
+            Effect::Sharpen { amount } => {
+                let mut blurred = pixels.to_vec();
+                convolve_separable(&mut blurred, width, height, &gaussian_kernel(1.0));
+                for (pixel, blurred) in pixels.iter_mut().zip(&blurred) {
+                    let sharpened = *pixel + (*pixel + *blurred * -1.0) * *amount;
+                    *pixel = clamp_negative(sharpened.with_alfa(pixel.alfa));
+                }
+            },
+            Effect::Bloom { threshold, intensity, sigma } => {
+                let mut bright: Vec<LinearColor> = pixels
+                    .iter()
+                    .map(|pixel| clamp_negative(*pixel + LinearColor::new(-threshold, -threshold, -threshold)).with_alfa(0.0))
+                    .collect();
+                convolve_separable(&mut bright, width, height, &gaussian_kernel(*sigma));
+                for (pixel, glow) in pixels.iter_mut().zip(&bright) {
+                    *pixel += *glow * *intensity;
+                }
+            },
+            Effect::Vignette { radius, strength } => {
+                let (center_x, center_y) = (width as f32 / 2.0, height as f32 / 2.0);
+                let corner = center_x.hypot(center_y);
+                for (index, pixel) in pixels.iter_mut().enumerate() {
+                    let (x, y) = ((index % width) as f32 + 0.5, (index / width) as f32 + 0.5);
+                    let distance = (x - center_x).hypot(y - center_y) / corner;
+                    let falloff = ((distance - radius) / (1.0 - radius).max(f32::EPSILON)).clamp(0.0, 1.0);
+                    // Smoothstep keeps the transition soft at both ends.
+                    let darkening = strength * falloff * falloff * (3.0 - 2.0 * falloff);
+                    *pixel = (*pixel * (1.0 - darkening)).with_alfa(pixel.alfa);
+                }
+            },
+            Effect::ColorGrade(lut) => map_encoded(pixels, |color| lut.sample(color)),
+            Effect::Grayscale => {
+                for pixel in pixels.iter_mut() {
+                    let luminance = pixel.luminance();
+                    *pixel = LinearColor::new(luminance, luminance, luminance).with_alfa(pixel.alfa);
+                }
+            },
+            Effect::Sepia => map_encoded(pixels, |[red, green, blue]| [
+                0.393 * red + 0.769 * green + 0.189 * blue,
+                0.349 * red + 0.686 * green + 0.168 * blue,
+                0.272 * red + 0.534 * green + 0.131 * blue,
+            ]),
+            Effect::EdgeDetect => {
+                let luminance: Vec<f32> = pixels.iter().map(|pixel| srgb_encode(pixel.luminance().clamp(0.0, 1.0))).collect();
+                let at = |x: isize, y: isize| luminance[y.clamp(0, height as isize - 1) as usize * width + x.clamp(0, width as isize - 1) as usize];
+                for (index, pixel) in pixels.iter_mut().enumerate() {
+                    let (x, y) = ((index % width) as isize, (index / width) as isize);
+                    let horizontal = at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1) - at(x - 1, y - 1) - 2.0 * at(x - 1, y) - at(x - 1, y + 1);
+                    let vertical = at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1) - at(x - 1, y - 1) - 2.0 * at(x, y - 1) - at(x + 1, y - 1);
+                    let edge = srgb_decode(horizontal.hypot(vertical).min(1.0));
+                    *pixel = LinearColor::new(edge, edge, edge).with_alfa(pixel.alfa);
+                }
+            },
+            Effect::Pixelate { size } => {
+                let size = (*size).max(1);
+                for block_y in (0..height).step_by(size) {
+                    for block_x in (0..width).step_by(size) {
+                        let (rows, columns) = (block_y..(block_y + size).min(height), block_x..(block_x + size).min(width));
+                        let count = (rows.len() * columns.len()) as f32;
+                        let mut sum = LinearColor::default();
+                        for y in rows.clone() {
+                            for x in columns.clone() {
+                                sum += pixels[y * width + x];
+                            }
+                        }
+                        let average = sum * (1.0 / count);
+                        for y in rows.clone() {
+                            pixels[y * width + columns.start..y * width + columns.end].fill(average);
+                        }
+                    }
+                }
+            },
+            Effect::Quantize { palette, dithering } => {
+                let mut encoded: Vec<_> = pixels.iter().map(|pixel| pixel.to_srgb()).collect();
+                dither(&mut encoded, width, height, palette, *dithering);
+                for (pixel, encoded) in pixels.iter_mut().zip(encoded) {
+                    *pixel = LinearColor::from_srgb(encoded);
+                }
+            },
+        }
+    }
+}
+
+
+/// Ordered chain of effects run on the rendered image before it is presented or saved.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PostProcess {
+    pub effects: Vec<Effect>,
+}
+
+
+impl PostProcess {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends effect to the end of the chain.
+    pub fn push(&mut self, effect: Effect) -> &mut Self {
+        self.effects.push(effect);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+
+    /// Runs all effects in order on linear colors of the HDR buffer, before tone mapping.
+    pub fn apply_hdr(&self, hdr: &mut HdrBuffer) {
+        let (width, height) = (hdr.width(), hdr.height());
+        for effect in &self.effects {
+            effect.apply(hdr.pixels_mut(), width, height);
+        }
+    }
+
+    /// Runs all effects in order on the Bitmap, which is converted to linear colors and back only once.
+    pub fn apply(&self, bitmap: &mut Bitmap) {
+        if self.is_empty() {
+            return;
+        }
+        let mut pixels: Vec<LinearColor> = bitmap.pixels().iter().map(|&pixel| LinearColor::from_srgb(pixel)).collect();
+        for effect in &self.effects {
+            effect.apply(&mut pixels, bitmap.width, bitmap.height);
+        }
+        for (destination, color) in bitmap.pixels_mut().iter_mut().zip(&pixels) {
+            *destination = color.to_srgb();
+        }
+    }
+}
+
+
+/// Normalized weights of gaussian with given standard deviation, cut off at three deviations.
+fn gaussian_kernel(sigma: f32) -> Vec<f32> {
+    if sigma <= 0.0 {
+        return vec![1.0];
+    }
+    let radius = (3.0 * sigma).ceil() as isize;
+    let weights: Vec<f32> = (-radius..=radius).map(|offset| (-(offset * offset) as f32 / (2.0 * sigma * sigma)).exp()).collect();
+    let sum: f32 = weights.iter().sum();
+    weights.into_iter().map(|weight| weight / sum).collect()
+}
+
+
+/// Convolves image with the kernel horizontally and then vertically, pixels beyond the edges repeat the edge ones.
+fn convolve_separable(pixels: &mut [LinearColor], width: usize, height: usize, kernel: &[f32]) {
+    let radius = (kernel.len() / 2) as isize;
+    let convolve = |source: &[LinearColor], destination: &mut [LinearColor], length: usize, stride: usize, lines: usize, line_stride: usize| {
+        for line in 0..lines {
+            for position in 0..length {
+                let mut sum = LinearColor::default();
+                for (tap, weight) in kernel.iter().enumerate() {
+                    let sample = (position as isize + tap as isize - radius).clamp(0, length as isize - 1) as usize;
+                    sum += source[line * line_stride + sample * stride] * *weight;
+                }
+                destination[line * line_stride + position * stride] = sum;
+            }
+        }
+    };
+    let mut temporary = pixels.to_vec();
+    convolve(pixels, &mut temporary, width, 1, height, width);
+    convolve(&temporary, pixels, height, width, width, 1);
+}
+
+
+/// Replaces colors with results of a function of their sRGB encoded values, alfa is kept.
+fn map_encoded<F: Fn([f32; 3]) -> [f32; 3]>(pixels: &mut [LinearColor], function: F) {
+    for pixel in pixels.iter_mut() {
+        let encoded = [pixel.red, pixel.green, pixel.blue].map(|channel| srgb_encode(channel.clamp(0.0, 1.0)));
+        let [red, green, blue] = function(encoded).map(|channel| srgb_decode(channel.clamp(0.0, 1.0)));
+        *pixel = LinearColor::new(red, green, blue).with_alfa(pixel.alfa);
+    }
+}
+
+
+fn clamp_negative(color: LinearColor) -> LinearColor {
+    LinearColor::from_rgba(color.to_rgba().map(|channel| channel.max(0.0)))
+}
+
+
+/// Test suite for post-processing effects.
+#[cfg(test)]
+mod test_postprocess {
+    use super::*;
+    use crate::Pixel;
+
+    fn impulse(width: usize, height: usize) -> Vec<LinearColor> {
+        let mut pixels = vec![LinearColor::new(0.0, 0.0, 0.0); width * height];
+        pixels[height / 2 * width + width / 2] = LinearColor::new(1.0, 1.0, 1.0);
+        pixels
+    }
+
+    #[test]
+    fn blurs_keep_energy() {
+        for effect in [Effect::BoxBlur { radius: 2 }, Effect::GaussianBlur { sigma: 1.5 }] {
+            let mut pixels = impulse(15, 15);
+            effect.apply(&mut pixels, 15, 15);
+            let total: f32 = pixels.iter().map(|pixel| pixel.red).sum();
+            assert!((total - 1.0).abs() < 1e-4, "{:?}", effect);
+            assert!(pixels[7 * 15 + 7].red < 1.0 && pixels[7 * 15 + 8].red > 0.0);
+        }
+        let mut pixels = impulse(15, 15);
+        Effect::BoxBlur { radius: 1 }.apply(&mut pixels, 15, 15);
+        assert_eq!(pixels.iter().filter(|pixel| pixel.red > 0.0).count(), 9);
+    }
+
+    #[test]
+    fn lut() {
+        let identity = Lut3d::identity(17);
+        assert!(identity.sample([0.3, 0.6, 0.9]).iter().zip([0.3, 0.6, 0.9]).all(|(value, expected)| (value - expected).abs() < 1e-5));
+
+        let cube = "TITLE \"invert\"\n# comment\nLUT_3D_SIZE 2\n1 1 1\n0 1 1\n1 0 1\n0 0 1\n1 1 0\n0 1 0\n1 0 0\n0 0 0\n";
+        let invert = Lut3d::from_cube(cube).unwrap();
+        assert_eq!(invert.size(), 2);
+        assert_eq!(invert.sample([0.25, 0.5, 1.0]), [0.75, 0.5, 0.0]);
+        assert!(Lut3d::from_cube("LUT_3D_SIZE 2\n0 0 0\n").is_err());
+
+        // Sizes whose cube overflows and domains other than 0.0 - 1.0 are rejected.
+        assert!(Lut3d::from_cube("LUT_3D_SIZE 4000000\n0 0 0\n").is_err());
+        assert!(Lut3d::from_cube(&format!("LUT_3D_SIZE {}\n", usize::MAX)).is_err());
+        let with_domain = |min: &str, max: &str| Lut3d::from_cube(&format!("DOMAIN_MIN {}\nDOMAIN_MAX {}\n{}", min, max, cube));
+        assert!(with_domain("0 0 0", "1.0 1.0 1.0").is_ok());
+        assert!(with_domain("0 0 0", "2 2 2").is_err());
+        assert!(with_domain("-1 0 0", "1 1 1").is_err());
+        assert!(with_domain("0 0", "1 1 1").is_err());
+        assert!(with_domain("0 0 zero", "1 1 1").is_err());
+    }
+
+    #[test]
+    fn chain() {
+        let mut bitmap = Bitmap::new(4, 4);
+        for (x, _, pixel) in bitmap.enumerate_pixels_mut() {
+            *pixel = if x < 2 { Pixel::new(255, 0, 0) } else { Pixel::new(0, 0, 255) };
+        }
+        let mut chain = PostProcess::new();
+        chain.push(Effect::Pixelate { size: 4 }).push(Effect::Grayscale);
+        chain.apply(&mut bitmap);
+
+        // Average of red and blue light, converted to gray.
+        let expected = LinearColor::new(0.5, 0.0, 0.5).luminance();
+        assert!(bitmap.pixels().iter().all(|&pixel| pixel == LinearColor::new(expected, expected, expected).to_srgb()));
+    }
+
+    #[test]
+    fn edges_and_vignette() {
+        let mut pixels: Vec<LinearColor> = (0..64).map(|index| if index % 8 < 4 { LinearColor::new(0.0, 0.0, 0.0) } else { LinearColor::new(1.0, 1.0, 1.0) }).collect();
+        Effect::EdgeDetect.apply(&mut pixels, 8, 8);
+        assert!(pixels.iter().enumerate().all(|(index, pixel)| (pixel.red > 0.01) == (index % 8 == 3 || index % 8 == 4)));
+
+        let mut pixels = vec![LinearColor::new(1.0, 1.0, 1.0); 100];
+        Effect::Vignette { radius: 0.5, strength: 1.0 }.apply(&mut pixels, 10, 10);
+        assert_eq!(pixels[4 * 10 + 4], LinearColor::new(1.0, 1.0, 1.0));
+        assert!(pixels[0].red < 0.15 && pixels[0].red < pixels[10 + 1].red && pixels[10 + 1].red < 1.0);
+    }
+}
@@ -0,0 +1,246 @@
+use std::f32::consts::PI;
+
+use crate::Pixel;
+use crate::color::LinearColor;
+
+
+/// Reconstruction filter used when changing resolution of an image.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Filter {
+    /// Every pixel takes the value of the closest source pixel, keeps hard edges.
+    Nearest,
+    /// Linear interpolation between the two closest source pixels on each axis.
+    Bilinear,
+    /// Catmull-Rom cubic spline, sharper than bilinear.
+    Bicubic,
+    /// Windowed sinc with three lobes, the sharpest of the filters, may ring around hard edges.
+    Lanczos,
+}
+
+
+impl Filter {
+    /// Distance from the center beyond which the filter has no weight, in source pixels when upscaling.
+    fn support(&self) -> f32 {
+        match self {
+            Filter::Nearest => 0.5,
+            Filter::Bilinear => 1.0,
+            Filter::Bicubic => 2.0,
+            Filter::Lanczos => 3.0,
+        }
+    }
+
+    fn weight(&self, x: f32) -> f32 {
+        let x = x.abs();
+        match self {
+            Filter::Nearest => if x < 0.5 { 1.0 } else { 0.0 },
+            Filter::Bilinear => (1.0 - x).max(0.0),
+            Filter::Bicubic => {
+                // Catmull-Rom spline, cubic convolution with a = -0.5.
+                if x < 1.0 {
+                    1.5 * x * x * x - 2.5 * x * x + 1.0
+                } else if x < 2.0 {
+                    -0.5 * x * x * x + 2.5 * x * x - 4.0 * x + 2.0
+                } else {
+                    0.0
+                }
+            },
+            Filter::Lanczos => if x < 3.0 { sinc(x) * sinc(x / 3.0) } else { 0.0 },
+        }
+    }
+}
+
+
+fn sinc(x: f32) -> f32 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+
+/// Source pixels contributing to a single target pixel along one axis.
+struct Contribution {
+    first: usize,
+    weights: Vec<f32>,
+}
+
+
+/// Computes contributions of source pixels for every target pixel along one axis.
+///
+/// When downscaling the filter is widened to cover all source pixels falling into a target pixel,
+/// which prevents aliasing.
+fn contributions(source_size: usize, target_size: usize, filter: Filter) -> Vec<Contribution> {
+    let scale = source_size as f32 / target_size as f32;
+    let filter_scale = scale.max(1.0);
+    let support = filter.support() * filter_scale;
+
+    (0..target_size)
+        .map(|index| {
+            // Pixel centers are at half integer coordinates in both images.
+            let center = (index as f32 + 0.5) * scale;
+            let first = ((center - support).floor().max(0.0) as usize).min(source_size - 1);
+            let last = ((center + support).ceil() as usize).clamp(first + 1, source_size);
+            let mut weights: Vec<f32> = (first..last)
+                .map(|source| filter.weight((source as f32 + 0.5 - center) / filter_scale))
+                .collect();
+            let sum: f32 = weights.iter().sum();
+            if sum.abs() > f32::EPSILON {
+                weights.iter_mut().for_each(|weight| *weight /= sum);
+            } else {
+                // Nearest filter with center exactly between two pixels, take the first of them.
+                weights.iter_mut().for_each(|weight| *weight = 0.0);
+                let closest = ((center - 0.5).round().max(0.0) as usize).clamp(first, last - 1);
+                weights[closest - first] = 1.0;
+            }
+            Contribution { first, weights }
+        })
+        .collect()
+}
+
+
+/// Resamples image of given dimensions stored row by row to new dimensions.
+///
+/// Filtering is done in two separable passes on linear colors premultiplied by alfa,
+/// so transparent pixels do not bleed their color into the neighbours and averaged colors keep their brightness.
+pub fn resample(source: &[Pixel], width: usize, height: usize, new_width: usize, new_height: usize, filter: Filter) -> Vec<Pixel> {
+    if width == 0 || height == 0 || new_width == 0 || new_height == 0 {
+        return vec![Pixel::default(); new_width * new_height];
+    }
+    if filter == Filter::Nearest {
+        return resample_nearest(source, width, height, new_width, new_height);
+    }
+
+    let premultiplied: Vec<[f32; 4]> = source
+        .iter()
+        .map(|&pixel| {
+            let color = LinearColor::from_srgb(pixel);
+            [color.red * color.alfa, color.green * color.alfa, color.blue * color.alfa, color.alfa]
+        })
+        .collect();
+
+    // Horizontal pass, new_width x height.
+    let columns = contributions(width, new_width, filter);
+    let mut horizontal = vec![[0.0; 4]; new_width * height];
+    for y in 0..height {
+        let row = &premultiplied[y * width..(y + 1) * width];
+        for (x, contribution) in columns.iter().enumerate() {
+            horizontal[y * new_width + x] = weighted_sum(contribution, |index| row[index]);
+        }
+    }
+
+    // Vertical pass, new_width x new_height.
+    let rows = contributions(height, new_height, filter);
+    let mut result = Vec::with_capacity(new_width * new_height);
+    for contribution in &rows {
+        for x in 0..new_width {
+            let [red, green, blue, alfa] = weighted_sum(contribution, |index| horizontal[index * new_width + x]);
+            let alfa = alfa.clamp(0.0, 1.0);
+            let unpremultiply = |channel: f32| if alfa > 0.0 { channel / alfa } else { 0.0 };
+            result.push(LinearColor::new(unpremultiply(red), unpremultiply(green), unpremultiply(blue)).with_alfa(alfa).to_srgb());
+        }
+    }
+    result
+}
+
+
+fn weighted_sum<F: Fn(usize) -> [f32; 4]>(contribution: &Contribution, value: F) -> [f32; 4] {
+    let mut sum = [0.0; 4];
+    for (offset, weight) in contribution.weights.iter().enumerate() {
+        let value = value(contribution.first + offset);
+        for channel in 0..4 {
+            sum[channel] += value[channel] * weight;
+        }
+    }
+    sum
+}
+
+
+fn resample_nearest(source: &[Pixel], width: usize, height: usize, new_width: usize, new_height: usize) -> Vec<Pixel> {
+    // Source pixel whose area contains the center of the target pixel.
+    let nearest = |index: usize, size: usize, new_size: usize| ((2 * index + 1) * size / (2 * new_size)).min(size - 1);
+    let columns: Vec<usize> = (0..new_width).map(|x| nearest(x, width, new_width)).collect();
+
+    let mut result = Vec::with_capacity(new_width * new_height);
+    for y in 0..new_height {
+        let row = &source[nearest(y, height, new_height) * width..][..width];
+        result.extend(columns.iter().map(|&x| row[x]));
+    }
+    result
+}
+
+
+/// Enlarges image by an integer factor, every pixel becomes a `factor` x `factor` square.
+///
+/// Equivalent to nearest filter resampling, but copies whole rows at once.
+pub fn upscale(source: &[Pixel], width: usize, height: usize, factor: usize) -> Vec<Pixel> {
+    let new_width = width * factor;
+    let mut result = Vec::with_capacity(new_width * height * factor);
+    for row in source.chunks_exact(width.max(1)).take(height) {
+        let start = result.len();
+        result.extend(row.iter().flat_map(|&pixel| std::iter::repeat_n(pixel, factor)));
+        for _ in 1..factor {
+            result.extend_from_within(start..start + new_width);
+        }
+    }
+    result
+}
+
+
+/// Test suite for resampling.
+#[cfg(test)]
+mod test_resample {
+    use super::*;
+
+    const FILTERS: [Filter; 4] = [Filter::Nearest, Filter::Bilinear, Filter::Bicubic, Filter::Lanczos];
+
+    fn checkerboard(width: usize, height: usize) -> Vec<Pixel> {
+        (0..width * height)
+            .map(|index| if (index % width + index / width) & 1 == 0 { Pixel::new(0, 0, 0) } else { Pixel::new(255, 255, 255) })
+            .collect()
+    }
+
+    #[test]
+    fn constant_image_stays_constant() {
+        let source = vec![Pixel::new(10, 100, 200); 7 * 5];
+        for filter in FILTERS {
+            for (width, height) in [(3, 2), (7, 5), (20, 13)] {
+                assert!(resample(&source, 7, 5, width, height, filter).iter().all(|&pixel| pixel == Pixel::new(10, 100, 200)));
+            }
+        }
+    }
+
+    #[test]
+    fn nearest_matches_upscale() {
+        let source = checkerboard(5, 3);
+        assert_eq!(resample(&source, 5, 3, 15, 9, Filter::Nearest), upscale(&source, 5, 3, 3));
+        assert_eq!(resample(&source, 5, 3, 5, 3, Filter::Nearest), source);
+    }
+
+    #[test]
+    fn downscaling_averages() {
+        // Every 2x2 block of a checkerboard averages to half of the white light, which is encoded as 188,
+        // filters widened for downscaling must not alias.
+        let source = checkerboard(16, 16);
+        for filter in [Filter::Bilinear, Filter::Bicubic, Filter::Lanczos] {
+            let result = resample(&source, 16, 16, 8, 8, filter);
+            assert!(result.iter().all(|pixel| (183..=193).contains(&pixel.red)), "{:?}", filter);
+        }
+    }
+
+    #[test]
+    fn transparent_pixels_do_not_bleed() {
+        let source = [Pixel::new(255, 0, 0), Pixel::new(0, 255, 0).with_alfa(0)];
+        let result = resample(&source, 2, 1, 4, 1, Filter::Bilinear);
+        assert!(result.iter().filter(|pixel| pixel.alfa > 0).all(|pixel| pixel.green == 0 && pixel.red == 255));
+    }
+
+    #[test]
+    fn upscale_factor() {
+        let source = [Pixel::new(1, 1, 1), Pixel::new(2, 2, 2)];
+        let result = upscale(&source, 2, 1, 2);
+        assert_eq!(result.len(), 8);
+        assert_eq!(result[..4], [source[0], source[0], source[1], source[1]]);
+        assert_eq!(result[4..], result[..4]);
+    }
+}
@@ -0,0 +1,287 @@
+use nalgebra as na;
+
+use crate::color::LinearColor;
+use crate::lighting::{Lighting, Material};
+
+type P3 = na::Point3<f32>;
+type V3 = na::Vector3<f32>;
+type V4 = na::Vector4<f32>;
+type M3 = na::Matrix3<f32>;
+type M4 = na::Matrix4<f32>;
+
+
+/// Values produced by the vertex shader and interpolated across the triangle for the fragment shader.
+pub trait Varying: Copy {
+    /// Combines values from three vertices of the triangle.
+    ///
+    /// `weights` are (perspective corrected) barycentric coordinates, they sum up to 1.
+    fn interpolate(values: &[Self; 3], weights: &[f32; 3]) -> Self;
+}
+
+
+impl Varying for () {
+    fn interpolate(_values: &[Self; 3], _weights: &[f32; 3]) -> Self {}
+}
+
+
+impl Varying for f32 {
+    fn interpolate(values: &[Self; 3], weights: &[f32; 3]) -> Self {
+        values[0] * weights[0] + values[1] * weights[1] + values[2] * weights[2]
+    }
+}
+
+
+impl<const D: usize> Varying for na::SVector<f32, D> {
+    fn interpolate(values: &[Self; 3], weights: &[f32; 3]) -> Self {
+        values[0] * weights[0] + values[1] * weights[1] + values[2] * weights[2]
+    }
+}
+
+
+impl<const D: usize> Varying for na::Point<f32, D> {
+    fn interpolate(values: &[Self; 3], weights: &[f32; 3]) -> Self {
+        Self::from(na::SVector::interpolate(&values.map(|point| point.coords), weights))
+    }
+}
+
+
+impl<A: Varying, B: Varying> Varying for (A, B) {
+    fn interpolate(values: &[Self; 3], weights: &[f32; 3]) -> Self {
+        (
+            A::interpolate(&values.map(|value| value.0), weights),
+            B::interpolate(&values.map(|value| value.1), weights),
+        )
+    }
+}
+
+
+impl<A: Varying, B: Varying, C: Varying> Varying for (A, B, C) {
+    fn interpolate(values: &[Self; 3], weights: &[f32; 3]) -> Self {
+        (
+            A::interpolate(&values.map(|value| value.0), weights),
+            B::interpolate(&values.map(|value| value.1), weights),
+            C::interpolate(&values.map(|value| value.2), weights),
+        )
+    }
+}
+
+
+/// Varying that is not interpolated, whole triangle gets the value of its first (provoking) vertex.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Flat<T>(pub T);
+
+
+impl<T: Copy> Varying for Flat<T> {
+    fn interpolate(values: &[Self; 3], _weights: &[f32; 3]) -> Self {
+        values[0]
+    }
+}
+
+
+/// Program run for every vertex of the drawn triangles.
+///
+/// `U` is the type of uniforms, values shared by all vertices and fragments of a single draw call.
+pub trait VertexShader<U> {
+    /// Per vertex input attributes.
+    type Vertex;
+    /// Output passed to the fragment shader.
+    type Varying: Varying;
+
+    /// Returns position of the vertex in homogeneous clip space along with its varyings.
+    fn vertex(&self, uniforms: &U, vertex: &Self::Vertex) -> (V4, Self::Varying);
+}
+
+
+/// Program run for every pixel covered by the drawn triangles.
+pub trait FragmentShader<U> {
+    type Varying: Varying;
+
+    /// Returns linear color of the fragment or `None` if it should be discarded.
+    ///
+    /// Channels above 1.0 are kept by float render targets and clipped by the others.
+    fn fragment(&self, uniforms: &U, varying: &Self::Varying) -> Option<LinearColor>;
+}
+
+
+/// Vertex attributes of a `Mesh`.
+#[derive(Copy, Clone, Debug)]
+pub struct MeshVertex {
+    /// Position in model space.
+    pub position: P3,
+    /// Vertex normal in model space.
+    pub normal: V3,
+    /// Normal of the triangle the vertex belongs to in model space.
+    pub face_normal: V3,
+    /// Centroid of the triangle the vertex belongs to in model space, where flat shading evaluates lighting.
+    pub face_centroid: P3,
+}
+
+
+/// Uniforms used by the built in shaders.
+pub struct SceneUniforms<'a> {
+    /// Model to world space transform.
+    pub model: M4,
+    /// Transforms normals from model to world space.
+    pub normal_matrix: M3,
+    /// World to clip space transform.
+    pub view_projection: M4,
+    /// Camera position in world space.
+    pub eye: P3,
+    pub material: Material,
+    pub lighting: &'a Lighting,
+}
+
+
+impl<'a> SceneUniforms<'a> {
+    pub fn new(model: M4, view_projection: M4, eye: P3, material: Material, lighting: &'a Lighting) -> Self {
+        let normal_matrix = model
+            .fixed_slice::<3, 3>(0, 0)
+            .try_inverse()
+            .map(|inverse| inverse.transpose())
+            .unwrap_or_else(M3::identity);
+        Self { model, normal_matrix, view_projection, eye, material, lighting }
+    }
+
+    /// Transforms vertex to world space, returns its clip space position, world position and world normal.
+    fn transform(&self, position: &P3, normal: &V3) -> (V4, P3, V3) {
+        let world = self.model.transform_point(position);
+        let clip = self.view_projection * world.to_homogeneous();
+        (clip, world, (self.normal_matrix * normal).normalize())
+    }
+
+    fn shade(&self, position: &P3, normal: &V3) -> V3 {
+        self.lighting.shade(&self.material, position, normal, &self.eye)
+    }
+}
+
+
+/// Lighting evaluated once per triangle at its centroid using its face normal.
+pub struct FlatShader;
+
+
+impl<'a> VertexShader<SceneUniforms<'a>> for FlatShader {
+    type Vertex = MeshVertex;
+    type Varying = Flat<V3>;
+
+    fn vertex(&self, uniforms: &SceneUniforms<'a>, vertex: &MeshVertex) -> (V4, Self::Varying) {
+        let (clip, _, _) = uniforms.transform(&vertex.position, &vertex.face_normal);
+        // All vertices of the triangle light its centroid, so the color does not depend on which vertex is provoking.
+        let (_, centroid, normal) = uniforms.transform(&vertex.face_centroid, &vertex.face_normal);
+        (clip, Flat(uniforms.shade(&centroid, &normal)))
+    }
+}
+
+
+impl<'a> FragmentShader<SceneUniforms<'a>> for FlatShader {
+    type Varying = Flat<V3>;
+
+    fn fragment(&self, _uniforms: &SceneUniforms<'a>, varying: &Self::Varying) -> Option<LinearColor> {
+        Some(LinearColor::from_vector(&varying.0))
+    }
+}
+
+
+/// Lighting evaluated once per pixel using interpolated world position and normal.
+pub struct PhongShader;
+
+
+impl<'a> VertexShader<SceneUniforms<'a>> for PhongShader {
+    type Vertex = MeshVertex;
+    type Varying = (P3, V3);
+
+    fn vertex(&self, uniforms: &SceneUniforms<'a>, vertex: &MeshVertex) -> (V4, Self::Varying) {
+        let (clip, world, normal) = uniforms.transform(&vertex.position, &vertex.normal);
+        (clip, (world, normal))
+    }
+}
+
+
+impl<'a> FragmentShader<SceneUniforms<'a>> for PhongShader {
+    type Varying = (P3, V3);
+
+    fn fragment(&self, uniforms: &SceneUniforms<'a>, varying: &Self::Varying) -> Option<LinearColor> {
+        let (position, normal) = varying;
+        Some(LinearColor::from_vector(&uniforms.shade(position, &normal.normalize())))
+    }
+}
+
+
+/// Per pixel lighting quantized to a fixed number of bands, giving a cartoon look.
+pub struct ToonShader {
+    pub bands: u32,
+}
+
+
+impl<'a> VertexShader<SceneUniforms<'a>> for ToonShader {
+    type Vertex = MeshVertex;
+    type Varying = (P3, V3);
+
+    fn vertex(&self, uniforms: &SceneUniforms<'a>, vertex: &MeshVertex) -> (V4, Self::Varying) {
+        PhongShader.vertex(uniforms, vertex)
+    }
+}
+
+
+impl<'a> FragmentShader<SceneUniforms<'a>> for ToonShader {
+    type Varying = (P3, V3);
+
+    fn fragment(&self, uniforms: &SceneUniforms<'a>, varying: &Self::Varying) -> Option<LinearColor> {
+        let (position, normal) = varying;
+        let bands = self.bands.max(1) as f32;
+        let color = uniforms.shade(position, &normal.normalize());
+        Some(LinearColor::from_vector(&color.map(|channel| (channel * bands).ceil() / bands)))
+    }
+}
+
+
+/// Visualises world space normals, each axis is mapped to one of the color channels.
+pub struct NormalShader;
+
+
+impl<'a> VertexShader<SceneUniforms<'a>> for NormalShader {
+    type Vertex = MeshVertex;
+    type Varying = V3;
+
+    fn vertex(&self, uniforms: &SceneUniforms<'a>, vertex: &MeshVertex) -> (V4, Self::Varying) {
+        let (clip, _, normal) = uniforms.transform(&vertex.position, &vertex.normal);
+        (clip, normal)
+    }
+}
+
+
+impl<'a> FragmentShader<SceneUniforms<'a>> for NormalShader {
+    type Varying = V3;
+
+    fn fragment(&self, _uniforms: &SceneUniforms<'a>, varying: &Self::Varying) -> Option<LinearColor> {
+        Some(LinearColor::from_vector(&(varying.normalize() * 0.5 + V3::repeat(0.5))))
+    }
+}
+
+
+/// Test suite for the built in shaders.
+#[cfg(test)]
+mod test_shader {
+    use super::*;
+    use crate::lighting::{Attenuation, Light};
+
+    #[test]
+    fn flat_shading_lights_centroid() {
+        let lighting = Lighting::new(vec![Light::point(P3::new(0.0, 0.0, 1.0), V3::repeat(1.0), 1.0, Attenuation::default())]);
+        let material = Material::new(V3::repeat(1.0), V3::zeros(), 1.0);
+        let eye = P3::new(0.0, 0.0, 10.0);
+        let uniforms = SceneUniforms::new(M4::identity(), M4::identity(), eye, material, &lighting);
+
+        let positions = [P3::new(0.0, 0.0, 0.0), P3::new(3.0, 0.0, 0.0), P3::new(0.0, 3.0, 0.0)];
+        let face_centroid = P3::new(1.0, 1.0, 0.0);
+        let colors = positions.map(|position| {
+            let vertex = MeshVertex { position, normal: V3::z(), face_normal: V3::z(), face_centroid };
+            let (_, Flat(color)) = FlatShader.vertex(&uniforms, &vertex);
+            color
+        });
+
+        let expected = lighting.shade(&material, &face_centroid, &V3::z(), &eye);
+        assert!(colors.iter().all(|color| *color == expected));
+        // Lighting at the provoking vertex, right below the light, would be brighter.
+        assert!(lighting.shade(&material, &positions[0], &V3::z(), &eye).x > expected.x);
+    }
+}
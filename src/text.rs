@@ -0,0 +1,604 @@
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+
+use crate::Pixel;
+use crate::view::RenderTarget;
+use crate::bitmap::Rect;
+
+type Rgb = (u8, u8, u8);
+
+/// Glyphs of the embedded 8x8 font for printable ASCII characters, starting with space.
+///
+/// Every byte is one row with the least significant bit being the leftmost pixel.
+/// Based on public domain font8x8 by Daniel Hepper.
+const EMBEDDED_GLYPHS: [[u8; 8]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],  // space
+    [0x18, 0x3C, 0x3C, 0x18, 0x18, 0x00, 0x18, 0x00],  // !
+    [0x36, 0x36, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],  // "
+    [0x36, 0x36, 0x7F, 0x36, 0x7F, 0x36, 0x36, 0x00],  // #
+    [0x0C, 0x3E, 0x03, 0x1E, 0x30, 0x1F, 0x0C, 0x00],  // $
+    [0x00, 0x63, 0x33, 0x18, 0x0C, 0x66, 0x63, 0x00],  // %
+    [0x1C, 0x36, 0x1C, 0x6E, 0x3B, 0x33, 0x6E, 0x00],  // &
+    [0x06, 0x06, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00],  // '
+    [0x18, 0x0C, 0x06, 0x06, 0x06, 0x0C, 0x18, 0x00],  // (
+    [0x06, 0x0C, 0x18, 0x18, 0x18, 0x0C, 0x06, 0x00],  // )
+    [0x00, 0x66, 0x3C, 0xFF, 0x3C, 0x66, 0x00, 0x00],  // *
+    [0x00, 0x0C, 0x0C, 0x3F, 0x0C, 0x0C, 0x00, 0x00],  // +
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C, 0x06],  // ,
+    [0x00, 0x00, 0x00, 0x3F, 0x00, 0x00, 0x00, 0x00],  // -
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C, 0x00],  // .
+    [0x60, 0x30, 0x18, 0x0C, 0x06, 0x03, 0x01, 0x00],  // /
+    [0x3E, 0x63, 0x73, 0x7B, 0x6F, 0x67, 0x3E, 0x00],  // 0
+    [0x0C, 0x0E, 0x0C, 0x0C, 0x0C, 0x0C, 0x3F, 0x00],  // 1
+    [0x1E, 0x33, 0x30, 0x1C, 0x06, 0x33, 0x3F, 0x00],  // 2
+    [0x1E, 0x33, 0x30, 0x1C, 0x30, 0x33, 0x1E, 0x00],  // 3
+    [0x38, 0x3C, 0x36, 0x33, 0x7F, 0x30, 0x78, 0x00],  // 4
+    [0x3F, 0x03, 0x1F, 0x30, 0x30, 0x33, 0x1E, 0x00],  // 5
+    [0x1C, 0x06, 0x03, 0x1F, 0x33, 0x33, 0x1E, 0x00],  // 6
+    [0x3F, 0x33, 0x30, 0x18, 0x0C, 0x0C, 0x0C, 0x00],  // 7
+    [0x1E, 0x33, 0x33, 0x1E, 0x33, 0x33, 0x1E, 0x00],  // 8
+    [0x1E, 0x33, 0x33, 0x3E, 0x30, 0x18, 0x0E, 0x00],  // 9
+    [0x00, 0x0C, 0x0C, 0x00, 0x00, 0x0C, 0x0C, 0x00],  // :
+    [0x00, 0x0C, 0x0C, 0x00, 0x00, 0x0C, 0x0C, 0x06],  // ;
+    [0x18, 0x0C, 0x06, 0x03, 0x06, 0x0C, 0x18, 0x00],  // <
+    [0x00, 0x00, 0x3F, 0x00, 0x00, 0x3F, 0x00, 0x00],  // =
+    [0x06, 0x0C, 0x18, 0x30, 0x18, 0x0C, 0x06, 0x00],  // >
+    [0x1E, 0x33, 0x30, 0x18, 0x0C, 0x00, 0x0C, 0x00],  // ?
+    [0x3E, 0x63, 0x7B, 0x7B, 0x7B, 0x03, 0x1E, 0x00],  // @
+    [0x0C, 0x1E, 0x33, 0x33, 0x3F, 0x33, 0x33, 0x00],  // A
+    [0x3F, 0x66, 0x66, 0x3E, 0x66, 0x66, 0x3F, 0x00],  // B
+    [0x3C, 0x66, 0x03, 0x03, 0x03, 0x66, 0x3C, 0x00],  // C
+    [0x1F, 0x36, 0x66, 0x66, 0x66, 0x36, 0x1F, 0x00],  // D
+    [0x7F, 0x46, 0x16, 0x1E, 0x16, 0x46, 0x7F, 0x00],  // E
+    [0x7F, 0x46, 0x16, 0x1E, 0x16, 0x06, 0x0F, 0x00],  // F
+    [0x3C, 0x66, 0x03, 0x03, 0x73, 0x66, 0x7C, 0x00],  // G
+    [0x33, 0x33, 0x33, 0x3F, 0x33, 0x33, 0x33, 0x00],  // H
+    [0x1E, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00],  // I
+    [0x78, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1E, 0x00],  // J
+    [0x67, 0x66, 0x36, 0x1E, 0x36, 0x66, 0x67, 0x00],  // K
+    [0x0F, 0x06, 0x06, 0x06, 0x46, 0x66, 0x7F, 0x00],  // L
+    [0x63, 0x77, 0x7F, 0x7F, 0x6B, 0x63, 0x63, 0x00],  // M
+    [0x63, 0x67, 0x6F, 0x7B, 0x73, 0x63, 0x63, 0x00],  // N
+    [0x1C, 0x36, 0x63, 0x63, 0x63, 0x36, 0x1C, 0x00],  // O
+    [0x3F, 0x66, 0x66, 0x3E, 0x06, 0x06, 0x0F, 0x00],  // P
+    [0x1E, 0x33, 0x33, 0x33, 0x3B, 0x1E, 0x38, 0x00],  // Q
+    [0x3F, 0x66, 0x66, 0x3E, 0x36, 0x66, 0x67, 0x00],  // R
+    [0x1E, 0x33, 0x07, 0x0E, 0x38, 0x33, 0x1E, 0x00],  // S
+    [0x3F, 0x2D, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00],  // T
+    [0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x3F, 0x00],  // U
+    [0x33, 0x33, 0x33, 0x33, 0x33, 0x1E, 0x0C, 0x00],  // V
+    [0x63, 0x63, 0x63, 0x6B, 0x7F, 0x77, 0x63, 0x00],  // W
+    [0x63, 0x63, 0x36, 0x1C, 0x1C, 0x36, 0x63, 0x00],  // X
+    [0x33, 0x33, 0x33, 0x1E, 0x0C, 0x0C, 0x1E, 0x00],  // Y
+    [0x7F, 0x63, 0x31, 0x18, 0x4C, 0x66, 0x7F, 0x00],  // Z
+    [0x1E, 0x06, 0x06, 0x06, 0x06, 0x06, 0x1E, 0x00],  // [
+    [0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x40, 0x00],  // backslash
+    [0x1E, 0x18, 0x18, 0x18, 0x18, 0x18, 0x1E, 0x00],  // ]
+    [0x08, 0x1C, 0x36, 0x63, 0x00, 0x00, 0x00, 0x00],  // ^
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF],  // _
+    [0x0C, 0x0C, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00],  // `
+    [0x00, 0x00, 0x1E, 0x30, 0x3E, 0x33, 0x6E, 0x00],  // a
+    [0x07, 0x06, 0x06, 0x3E, 0x66, 0x66, 0x3B, 0x00],  // b
+    [0x00, 0x00, 0x1E, 0x33, 0x03, 0x33, 0x1E, 0x00],  // c
+    [0x38, 0x30, 0x30, 0x3E, 0x33, 0x33, 0x6E, 0x00],  // d
+    [0x00, 0x00, 0x1E, 0x33, 0x3F, 0x03, 0x1E, 0x00],  // e
+    [0x1C, 0x36, 0x06, 0x0F, 0x06, 0x06, 0x0F, 0x00],  // f
+    [0x00, 0x00, 0x6E, 0x33, 0x33, 0x3E, 0x30, 0x1F],  // g
+    [0x07, 0x06, 0x36, 0x6E, 0x66, 0x66, 0x67, 0x00],  // h
+    [0x0C, 0x00, 0x0E, 0x0C, 0x0C, 0x0C, 0x1E, 0x00],  // i
+    [0x30, 0x00, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1E],  // j
+    [0x07, 0x06, 0x66, 0x36, 0x1E, 0x36, 0x67, 0x00],  // k
+    [0x0E, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00],  // l
+    [0x00, 0x00, 0x33, 0x7F, 0x7F, 0x6B, 0x63, 0x00],  // m
+    [0x00, 0x00, 0x1F, 0x33, 0x33, 0x33, 0x33, 0x00],  // n
+    [0x00, 0x00, 0x1E, 0x33, 0x33, 0x33, 0x1E, 0x00],  // o
+    [0x00, 0x00, 0x3B, 0x66, 0x66, 0x3E, 0x06, 0x0F],  // p
+    [0x00, 0x00, 0x6E, 0x33, 0x33, 0x3E, 0x30, 0x78],  // q
+    [0x00, 0x00, 0x3B, 0x6E, 0x66, 0x06, 0x0F, 0x00],  // r
+    [0x00, 0x00, 0x3E, 0x03, 0x1E, 0x30, 0x1F, 0x00],  // s
+    [0x08, 0x0C, 0x3E, 0x0C, 0x0C, 0x2C, 0x18, 0x00],  // t
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x33, 0x6E, 0x00],  // u
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x1E, 0x0C, 0x00],  // v
+    [0x00, 0x00, 0x63, 0x6B, 0x7F, 0x7F, 0x36, 0x00],  // w
+    [0x00, 0x00, 0x63, 0x36, 0x1C, 0x36, 0x63, 0x00],  // x
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x3E, 0x30, 0x1F],  // y
+    [0x00, 0x00, 0x3F, 0x19, 0x0C, 0x26, 0x3F, 0x00],  // z
+    [0x38, 0x0C, 0x0C, 0x07, 0x0C, 0x0C, 0x38, 0x00],  // {
+    [0x18, 0x18, 0x18, 0x00, 0x18, 0x18, 0x18, 0x00],  // |
+    [0x07, 0x0C, 0x0C, 0x38, 0x0C, 0x0C, 0x07, 0x00],  // }
+    [0x6E, 0x3B, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],  // ~
+];
+
+/// Magic numbers at the start of PC Screen Font files.
+const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+const PSF2_MAGIC: [u8; 4] = [0x72, 0xb5, 0x4a, 0x86];
+
+/// Largest glyph width and height accepted from font files, bigger ones are treated as corrupted.
+const MAX_GLYPH_SIZE: usize = 1024;
+
+
+/// Monospaced bitmap font.
+#[derive(Clone, Debug)]
+pub struct Font {
+    width: usize,
+    height: usize,
+    /// Pixels of every glyph row by row, `width * height` values each.
+    glyphs: HashMap<char, Vec<bool>>,
+}
+
+
+impl Font {
+    /// Creates font without any glyphs.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self { width, height, glyphs: HashMap::new() }
+    }
+
+    /// Font built into the renderer, 8x8 pixels with printable ASCII characters.
+    pub fn embedded() -> Self {
+        let mut font = Font::new(8, 8);
+        for (code, rows) in (0x20u8..).zip(EMBEDDED_GLYPHS.iter()) {
+            let pixels = rows.iter().flat_map(|row| (0..8).map(move |bit| row >> bit & 1 == 1)).collect();
+            font.glyphs.insert(code as char, pixels);
+        }
+        font
+    }
+
+    /// Loads PSF or BDF font from a file, format is recognized from the contents.
+    pub fn load(path: &str) -> Result<Self, Error> {
+        let bytes = std::fs::read(path)?;
+        if bytes.starts_with(&PSF1_MAGIC) || bytes.starts_with(&PSF2_MAGIC) {
+            Font::from_psf(&bytes)
+        } else {
+            Font::from_bdf(std::str::from_utf8(&bytes).map_err(|_| invalid("font is neither PSF nor BDF"))?)
+        }
+    }
+
+    /// Parses font in PC Screen Font format, version 1 or 2.
+    ///
+    /// Glyphs are mapped to characters using the unicode table of the font, fonts without one
+    /// have glyphs mapped to characters with code points equal to glyph indices.
+    pub fn from_psf(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.starts_with(&PSF1_MAGIC) {
+            let mode = *bytes.get(2).ok_or_else(|| invalid("truncated PSF header"))?;
+            let height = *bytes.get(3).ok_or_else(|| invalid("truncated PSF header"))? as usize;
+            let count = if mode & 0x01 != 0 { 512 } else { 256 };
+            let table = if mode & 0x06 != 0 { Some(psf1_unicode_table as UnicodeTable) } else { None };
+            Font::from_psf_glyphs(&bytes[4..], 8, height, count, table)
+        } else if bytes.starts_with(&PSF2_MAGIC) {
+            let field = |index: usize| -> Result<usize, Error> {
+                let field = bytes.get(4 + index * 4..8 + index * 4).ok_or_else(|| invalid("truncated PSF header"))?;
+                Ok(u32::from_le_bytes([field[0], field[1], field[2], field[3]]) as usize)
+            };
+            let (header_size, flags, count, glyph_size, height, width) = (field(1)?, field(2)?, field(3)?, field(4)?, field(5)?, field(6)?);
+            if width.div_ceil(8).checked_mul(height) != Some(glyph_size) {
+                return Err(invalid("PSF glyph size does not match its dimensions"));
+            }
+            let glyphs = bytes.get(header_size..).ok_or_else(|| invalid("truncated PSF header"))?;
+            let table = if flags & 0x01 != 0 { Some(psf2_unicode_table as UnicodeTable) } else { None };
+            Font::from_psf_glyphs(glyphs, width, height, count, table)
+        } else {
+            Err(invalid("missing PSF magic number"))
+        }
+    }
+
+    /// Builds font from `count` glyphs followed by the optional unicode table, parsed by `table`.
+    fn from_psf_glyphs(glyphs: &[u8], width: usize, height: usize, count: usize, table: Option<UnicodeTable>) -> Result<Self, Error> {
+        if !(1..=MAX_GLYPH_SIZE).contains(&width) || !(1..=MAX_GLYPH_SIZE).contains(&height) {
+            return Err(invalid("invalid PSF glyph dimensions"));
+        }
+        let row_size = width.div_ceil(8);
+        let glyph_size = row_size * height;
+        let data_size = count
+            .checked_mul(glyph_size)
+            .filter(|&size| size <= glyphs.len())
+            .ok_or_else(|| invalid("truncated PSF glyph data"))?;
+        // Size of the table depends on the glyph count, which is known to be sane only now.
+        let table = table.map(|parse| parse(&glyphs[data_size..], count));
+        let mut font = Font::new(width, height);
+        for (index, data) in glyphs.chunks_exact(glyph_size).take(count).enumerate() {
+            let pixels: Vec<bool> = data
+                .chunks_exact(row_size)
+                .flat_map(|row| (0..width).map(move |x| row[x / 8] >> (7 - x % 8) & 1 == 1))
+                .collect();
+            match &table {
+                Some(table) => for &character in &table[index] {
+                    font.glyphs.insert(character, pixels.clone());
+                },
+                None => if let Some(character) = char::from_u32(index as u32) {
+                    font.glyphs.insert(character, pixels);
+                },
+            }
+        }
+        Ok(font)
+    }
+
+    /// Parses font in Glyph Bitmap Distribution Format.
+    ///
+    /// Glyphs are placed in cells of the font bounding box according to their own bounding boxes,
+    /// glyphs without encoding are skipped.
+    pub fn from_bdf(source: &str) -> Result<Self, Error> {
+        let mut font: Option<Font> = None;
+        let mut origin = (0, 0);
+        let mut encoding = None;
+        let mut bounding_box = (0, 0, 0, 0);
+        let mut lines = source.lines();
+
+        while let Some(line) = lines.next() {
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("FONTBOUNDINGBOX") => {
+                    let [width, height, offset_x, offset_y] = parse_numbers(words)?.map(i64::from);
+                    let size = 1..=MAX_GLYPH_SIZE as i64;
+                    if !size.contains(&width) || !size.contains(&height) {
+                        return Err(invalid("invalid BDF font bounding box"));
+                    }
+                    font = Some(Font::new(width as usize, height as usize));
+                    origin = (offset_x, height + offset_y);
+                },
+                Some("ENCODING") => encoding = words.next().and_then(|code| code.parse::<u32>().ok()).and_then(char::from_u32),
+                Some("BBX") => bounding_box = parse_numbers(words)?.map(i64::from).into(),
+                Some("BITMAP") => {
+                    let font = font.as_mut().ok_or_else(|| invalid("BDF glyph before FONTBOUNDINGBOX"))?;
+                    let (width, height, offset_x, offset_y) = bounding_box;
+                    let mut pixels = vec![false; font.width * font.height];
+                    for row in 0..height {
+                        let line = lines.next().ok_or_else(|| invalid("truncated BDF bitmap"))?.trim();
+                        // Pairs of hex digits are taken from bytes, so characters outside of ASCII are rejected instead of split.
+                        let bytes = line.as_bytes()
+                            .chunks_exact(2)
+                            .map(|pair| std::str::from_utf8(pair).ok().and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+                            .collect::<Option<Vec<u8>>>()
+                            .ok_or_else(|| invalid("invalid BDF bitmap row"))?;
+                        let y = origin.1 - (offset_y + height) + row;
+                        for column in 0..width.min(bytes.len() as i64 * 8) {
+                            let x = offset_x - origin.0 + column;
+                            let set = bytes[column as usize / 8] >> (7 - column % 8) & 1 == 1;
+                            if set && (0..font.width as i64).contains(&x) && (0..font.height as i64).contains(&y) {
+                                pixels[y as usize * font.width + x as usize] = true;
+                            }
+                        }
+                    }
+                    if let Some(character) = encoding.take() {
+                        font.glyphs.insert(character, pixels);
+                    }
+                },
+                _ => {},
+            }
+        }
+        font.ok_or_else(|| invalid("missing BDF FONTBOUNDINGBOX"))
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Returns pixels of the glyph row by row, `None` if the font has no glyph for the character.
+    pub fn glyph(&self, character: char) -> Option<&[bool]> {
+        self.glyphs.get(&character).map(Vec::as_slice)
+    }
+}
+
+
+/// Parses unicode table of a PSF font, returns characters of each of `count` glyphs.
+type UnicodeTable = fn(&[u8], usize) -> Vec<Vec<char>>;
+
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+
+fn parse_numbers<'a, const N: usize>(words: impl Iterator<Item = &'a str>) -> Result<[i32; N], Error> {
+    let numbers = words
+        .take(N)
+        .map(|word| word.parse().map_err(|_| invalid("invalid number in BDF font")))
+        .collect::<Result<Vec<i32>, _>>()?;
+    numbers.try_into().map_err(|_| invalid("too few numbers in BDF font"))
+}
+
+
+/// Characters of every glyph, PSF1 table holds UCS-2 values, each glyph's list ends with 0xFFFF.
+fn psf1_unicode_table(table: &[u8], count: usize) -> Vec<Vec<char>> {
+    let values: Vec<u16> = table.chunks_exact(2).map(|value| u16::from_le_bytes([value[0], value[1]])).collect();
+    let mut entries: Vec<Vec<char>> = values
+        .split(|&value| value == 0xFFFF)
+        .take(count)
+        // Multi character sequences come after 0xFFFE and are not supported.
+        .map(|entry| entry.iter().take_while(|&&value| value != 0xFFFE).filter_map(|&value| char::from_u32(value as u32)).collect())
+        .collect();
+    entries.resize(count, Vec::new());
+    entries
+}
+
+
+/// Characters of every glyph, PSF2 table holds UTF-8 strings, each glyph's list ends with 0xFF.
+fn psf2_unicode_table(table: &[u8], count: usize) -> Vec<Vec<char>> {
+    let mut entries: Vec<Vec<char>> = table
+        .split(|&byte| byte == 0xFF)
+        .take(count)
+        .map(|entry| {
+            let single = entry.split(|&byte| byte == 0xFE).next().unwrap_or(&[]);
+            std::str::from_utf8(single).map(|text| text.chars().collect()).unwrap_or_default()
+        })
+        .collect();
+    entries.resize(count, Vec::new());
+    entries
+}
+
+
+/// Horizontal alignment of lines of text relative to the x coordinate they are drawn at.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TextAlign {
+    Left,
+    Center,
+    Right,
+}
+
+
+/// Layout and color of drawn text.
+#[derive(Copy, Clone, Debug)]
+pub struct TextStyle {
+    /// Every pixel of the font is drawn as a square with this side length.
+    pub scale: usize,
+    pub align: TextAlign,
+    /// Additional space between lines, in pixels of the font.
+    pub line_spacing: usize,
+    pub color: Rgb,
+}
+
+
+impl TextStyle {
+    pub fn new(scale: usize, align: TextAlign, line_spacing: usize, color: Rgb) -> Self {
+        Self { scale, align, line_spacing, color }
+    }
+}
+
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self::new(1, TextAlign::Left, 0, (255, 255, 255))
+    }
+}
+
+
+/// Returns width of the longest line and height of all lines of the text in pixels.
+pub fn measure_text(font: &Font, text: &str, style: &TextStyle) -> (usize, usize) {
+    let lines = text.lines().count().max(1);
+    let longest = text.lines().map(|line| line.chars().count()).max().unwrap_or(0);
+    let (glyph_height, spacing) = (font.height.saturating_mul(style.scale), style.line_spacing.saturating_mul(style.scale));
+    (
+        longest.saturating_mul(font.width).saturating_mul(style.scale),
+        lines.saturating_mul(glyph_height).saturating_add((lines - 1).saturating_mul(spacing)),
+    )
+}
+
+
+/// Calls `visit` with the part of `clip` covered by every set pixel of the font in the laid out text.
+///
+/// Text starts at row `y`, lines are aligned to `x` according to the style.
+/// Characters missing from the font are replaced with '?', or left blank if the font has no '?' either.
+pub fn rasterize_text<F: FnMut(Rect)>(font: &Font, text: &str, x: i32, y: i32, style: &TextStyle, clip: &Rect, mut visit: F) {
+    // Layout is computed in i64 with saturating arithmetic, large scales or long lines may not fit into i32.
+    let scale = i64::try_from(style.scale).unwrap_or(i64::MAX);
+    let (glyph_width, glyph_height) = ((font.width as i64).saturating_mul(scale), (font.height as i64).saturating_mul(scale));
+    let line_height = glyph_height.saturating_add((style.line_spacing as i64).saturating_mul(scale));
+
+    for (row, line) in text.lines().enumerate() {
+        let line_y = (y as i64).saturating_add((row as i64).saturating_mul(line_height));
+        // Lines and cells only move further down and right, nothing past the clip can be visible.
+        if line_y >= clip.bottom() as i64 {
+            break;
+        }
+        let line_width = (line.chars().count() as i64).saturating_mul(glyph_width);
+        let line_x = match style.align {
+            TextAlign::Left => x as i64,
+            TextAlign::Center => (x as i64).saturating_sub(line_width / 2),
+            TextAlign::Right => (x as i64).saturating_sub(line_width),
+        };
+
+        for (column, character) in line.chars().enumerate() {
+            let cell_x = line_x.saturating_add((column as i64).saturating_mul(glyph_width));
+            if cell_x >= clip.right() as i64 {
+                break;
+            }
+            if clipped(cell_x, line_y, glyph_width, glyph_height, clip).is_empty() {
+                continue;
+            }
+            let pixels = match font.glyph(character).or_else(|| font.glyph('?')) {
+                Some(pixels) => pixels,
+                None => continue,
+            };
+            for (index, _) in pixels.iter().enumerate().filter(|(_, &set)| set) {
+                let (glyph_x, glyph_y) = ((index % font.width) as i64, (index / font.width) as i64);
+                let square_x = cell_x.saturating_add(glyph_x.saturating_mul(scale));
+                let square_y = line_y.saturating_add(glyph_y.saturating_mul(scale));
+                let square = clipped(square_x, square_y, scale, scale, clip);
+                if !square.is_empty() {
+                    visit(square);
+                }
+            }
+        }
+    }
+}
+
+
+/// Returns the part of `clip` covered by the rectangle, whose coordinates may lie outside of the i32 range.
+fn clipped(x: i64, y: i64, width: i64, height: i64, clip: &Rect) -> Rect {
+    let (left, top) = (x.max(clip.x as i64), y.max(clip.y as i64));
+    let (right, bottom) = (x.saturating_add(width).min(clip.right() as i64), y.saturating_add(height).min(clip.bottom() as i64));
+    if left >= right || top >= bottom {
+        return Rect::new(clip.x, clip.y, 0, 0);
+    }
+    // The rest lies inside of the clip, so it fits into i32.
+    Rect::new(left as i32, top as i32, (right - left) as i32, (bottom - top) as i32)
+}
+
+
+/// Draws text with its first line starting at row `y`, parts outside of the bitmap are skipped.
+pub fn draw_text<T: RenderTarget>(bitmap: &mut T, font: &Font, text: &str, x: i32, y: i32, style: &TextStyle) {
+    let clip = bitmap.rect();
+    draw_text_clipped(bitmap, font, text, x, y, style, &clip);
+}
+
+
+/// Draws text with its first line starting at row `y`, parts outside of `clip` are skipped.
+pub fn draw_text_clipped<T: RenderTarget>(bitmap: &mut T, font: &Font, text: &str, x: i32, y: i32, style: &TextStyle, clip: &Rect) {
+    let clip = clip.intersection(&bitmap.rect());
+    let pixel = Pixel::from_rgb_tuple(style.color);
+    rasterize_text(font, text, x, y, style, &clip, |square| {
+        for y in square.y..square.bottom() {
+            for x in square.x..square.right() {
+                bitmap.set_pixel(x as usize, y as usize, pixel);
+            }
+        }
+    });
+}
+
+
+/// Test suite for fonts and text layout.
+#[cfg(test)]
+mod test_text {
+    use super::*;
+
+    fn covered(font: &Font, text: &str, x: i32, style: &TextStyle, clip: &Rect) -> Vec<Rect> {
+        let mut squares = Vec::new();
+        rasterize_text(font, text, x, 0, style, clip, |square| squares.push(square));
+        squares
+    }
+
+    #[test]
+    fn embedded_font() {
+        let font = Font::embedded();
+        assert_eq!((font.width(), font.height()), (8, 8));
+        assert!(font.glyph(' ').unwrap().iter().all(|&set| !set));
+        assert!(font.glyph('~').unwrap().iter().any(|&set| set));
+        assert!(font.glyph('ł').is_none());
+    }
+
+    #[test]
+    fn layout() {
+        let font = Font::embedded();
+        let style = TextStyle::new(2, TextAlign::Right, 1, (255, 255, 255));
+        assert_eq!(measure_text(&font, "abc\nde", &style), (48, 34));
+
+        let clip = Rect::new(-100, -100, 200, 200);
+        let squares = covered(&font, "abc\nde", 0, &style, &clip);
+        assert!(squares.iter().all(|square| square.right() <= 0 && square.width == 2));
+        assert!(squares.iter().any(|square| square.x < -32));
+        assert!(squares.iter().any(|square| square.y >= 18));
+    }
+
+    #[test]
+    fn clipping() {
+        let font = Font::embedded();
+        let clip = Rect::new(0, 0, 20, 3);
+        let squares = covered(&font, "WWWWWWWW\nWWWW", -5, &TextStyle::new(3, TextAlign::Left, 0, (255, 255, 255)), &clip);
+        assert!(!squares.is_empty());
+        assert!(squares.iter().all(|square| square.intersection(&clip) == *square));
+    }
+
+    #[test]
+    fn far_and_large_text() {
+        let font = Font::embedded();
+        let clip = Rect::new(0, 0, 20, 20);
+        let inside = |squares: &[Rect]| squares.iter().all(|square| !square.is_empty() && square.intersection(&clip) == *square);
+        for (x, y) in [(i32::MAX, 0), (i32::MIN, i32::MIN), (0, i32::MAX)] {
+            let mut squares = Vec::new();
+            rasterize_text(&font, "W\nW", x, y, &TextStyle::new(1 << 20, TextAlign::Center, 1 << 20, (255, 255, 255)), &clip, |square| squares.push(square));
+            assert!(inside(&squares));
+        }
+
+        // Line far wider than the i32 range, only the start of its middle glyph is visible.
+        let line = "W".repeat(1 << 20);
+        let mut squares = Vec::new();
+        rasterize_text(&font, &line, 10, 0, &TextStyle::new(1 << 12, TextAlign::Center, 0, (255, 255, 255)), &clip, |square| squares.push(square));
+        assert!(!squares.is_empty() && inside(&squares));
+        assert!(squares.iter().all(|square| square.x >= 10));
+
+        let huge = TextStyle::new(usize::MAX, TextAlign::Right, usize::MAX, (255, 255, 255));
+        let mut squares = Vec::new();
+        rasterize_text(&font, "W\nW", 0, 0, &huge, &clip, |square| squares.push(square));
+        assert!(inside(&squares));
+        assert_eq!(measure_text(&font, "W\nW", &huge), (usize::MAX, usize::MAX));
+    }
+
+    #[test]
+    fn bdf() {
+        let source = "STARTFONT 2.1\n\
+            FONTBOUNDINGBOX 4 4 0 -1\n\
+            CHARS 1\n\
+            STARTCHAR period\n\
+            ENCODING 46\n\
+            BBX 2 1 1 0\n\
+            BITMAP\n\
+            C0\n\
+            ENDCHAR\n\
+            ENDFONT\n";
+        let font = Font::from_bdf(source).unwrap();
+        assert_eq!((font.width(), font.height()), (4, 4));
+        let set: Vec<usize> = (0..16).filter(|&index| font.glyph('.').unwrap()[index]).collect();
+        // Baseline is one row above the bottom of the cell.
+        assert_eq!(set, vec![9, 10]);
+        assert!(Font::from_bdf("STARTFONT 2.1\nENDFONT\n").is_err());
+    }
+
+    #[test]
+    fn psf2() {
+        let mut bytes = PSF2_MAGIC.to_vec();
+        for field in [0u32, 32, 1, 2, 4, 2, 9] {
+            bytes.extend_from_slice(&field.to_le_bytes());
+        }
+        // Two glyphs 9 pixels wide and 2 pixels high, two bytes per row.
+        bytes.extend_from_slice(&[0x80, 0x80, 0x00, 0x00]);
+        bytes.extend_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF]);
+        bytes.extend_from_slice("AÄ".as_bytes());
+        bytes.push(0xFF);
+        bytes.extend_from_slice("B".as_bytes());
+        bytes.push(0xFF);
+
+        let font = Font::from_psf(&bytes).unwrap();
+        assert_eq!((font.width(), font.height()), (9, 2));
+        let glyph = font.glyph('Ä').unwrap();
+        assert!(glyph[0] && glyph[8] && !glyph[1] && !glyph[9]);
+        assert!(font.glyph('B').unwrap().iter().all(|&set| set));
+        assert!(font.glyph('C').is_none());
+        assert!(Font::from_psf(&bytes[..20]).is_err());
+    }
+
+    #[test]
+    fn malformed_fonts() {
+        // PSF1 header of zero pixels high glyphs.
+        assert!(Font::from_psf(&[0x36, 0x04, 0x00, 0x00]).is_err());
+
+        let psf2 = |fields: [u32; 7]| {
+            let mut bytes = PSF2_MAGIC.to_vec();
+            for field in fields {
+                bytes.extend_from_slice(&field.to_le_bytes());
+            }
+            bytes.extend_from_slice(&[0xFF; 16]);
+            Font::from_psf(&bytes)
+        };
+        // Zero width, zero height, glyph count overflowing the data size and glyphs too big to be real.
+        assert!(psf2([0, 32, 1, 1, 0, 8, 0]).is_err());
+        assert!(psf2([0, 32, 1, 1, 0, 0, 8]).is_err());
+        assert!(psf2([0, 32, 1, u32::MAX, 1, 1, 8]).is_err());
+        assert!(psf2([0, 32, 0, 1, 1 << 29, u32::MAX, u32::MAX]).is_err());
+        assert!(psf2([0, 32, 0, 1, 2, 2, 8]).is_ok());
+
+        let bdf = |bounding_box: &str, row: &str| {
+            Font::from_bdf(&format!("FONTBOUNDINGBOX {}\nENCODING 65\nBBX 8 1 0 0\nBITMAP\n{}\nENDCHAR\n", bounding_box, row))
+        };
+        assert!(bdf("8 8 0 0", "C3").is_ok());
+        // Multi byte characters in the bitmap, zero sized and huge bounding boxes.
+        assert!(bdf("8 8 0 0", "Cł").is_err());
+        assert!(bdf("8 8 0 0", "łC").is_err());
+        assert!(bdf("0 8 0 0", "C3").is_err());
+        assert!(bdf("8 0 0 0", "C3").is_err());
+        assert!(bdf("2147483647 2147483647 0 0", "C3").is_err());
+        assert!(bdf("8 8 2147483647 2147483647", "C3").is_ok());
+    }
+}
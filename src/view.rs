@@ -0,0 +1,309 @@
+use crate::{Bitmap, Pixel};
+use crate::bitmap::Rect;
+use crate::color::LinearColor;
+use crate::format::{PixelFormat, Surface};
+
+
+/// Surface the drawing functions draw into.
+///
+/// Coordinates are relative to the top left corner of the target,
+/// drawing functions clip to `rect` and never write pixels outside of it.
+/// Pixels are stored in `Format`, colors drawn as 8 bit `Pixel` are converted and blended by it.
+pub trait RenderTarget {
+    type Format: PixelFormat;
+
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+
+    /// Returns pixel in the format of the target, `None` outside of the target.
+    fn get(&self, x: usize, y: usize) -> Option<Self::Format>;
+
+    /// Setter for pixel in the format of the target, coordinates have to lie inside of the target.
+    fn set(&mut self, x: usize, y: usize, pixel: Self::Format);
+
+    /// Rectangle covering the whole target.
+    fn rect(&self) -> Rect {
+        Rect::new(0, 0, self.width() as i32, self.height() as i32)
+    }
+
+    /// Returns pixel at given coordinates, `None` outside of the target.
+    fn get_pixel(&self, x: usize, y: usize) -> Option<Pixel> {
+        self.get(x, y).map(|pixel| pixel.to_pixel())
+    }
+
+    /// Setter for specific pixel, coordinates have to lie inside of the target.
+    fn set_pixel(&mut self, x: usize, y: usize, pixel: Pixel) {
+        self.set(x, y, Self::Format::from_pixel(pixel));
+    }
+
+    /// Blends pixel into the target using its alfa channel as opacity.
+    fn blend_pixel(&mut self, x: usize, y: usize, pixel: Pixel) {
+        if let Some(background) = self.get(x, y) {
+            self.set(x, y, Self::Format::composite(&pixel, &background));
+        }
+    }
+
+    /// Setter for linear color, float formats keep values outside of the displayable range.
+    fn set_color(&mut self, x: usize, y: usize, color: LinearColor) {
+        self.set(x, y, Self::Format::from_rgba(color.to_rgba()));
+    }
+}
+
+
+impl RenderTarget for Bitmap {
+    type Format = Pixel;
+
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn get(&self, x: usize, y: usize) -> Option<Pixel> {
+        Bitmap::get_pixel(self, x, y)
+    }
+
+    fn set(&mut self, x: usize, y: usize, pixel: Pixel) {
+        Bitmap::set_pixel(self, x, y, pixel)
+    }
+
+    fn blend_pixel(&mut self, x: usize, y: usize, pixel: Pixel) {
+        Bitmap::blend_pixel(self, x, y, pixel)
+    }
+}
+
+
+/// Returns range of buffer indices spanning the rectangle, from its top left to its bottom right pixel.
+fn span(rect: &Rect, stride: usize) -> std::ops::Range<usize> {
+    if rect.is_empty() {
+        return 0..0;
+    }
+    let start = rect.y as usize * stride + rect.x as usize;
+    start..start + (rect.height as usize - 1) * stride + rect.width as usize
+}
+
+
+/// Read only rectangular region of a Bitmap or Surface, borrowed without copying.
+#[derive(Copy, Clone, Debug)]
+pub struct BitmapView<'a, F: PixelFormat = Pixel> {
+    /// Pixels from the top left to the bottom right corner of the view, rows start `stride` pixels apart.
+    pixels: &'a [F],
+    stride: usize,
+    width: usize,
+    height: usize,
+}
+
+
+impl<'a> BitmapView<'a> {
+    /// View of the part of the bitmap inside of `rect`, clipped to the bitmap.
+    pub fn new(bitmap: &'a Bitmap, rect: Rect) -> Self {
+        Self::from_buffer(bitmap.pixels(), bitmap.width, bitmap.height, rect)
+    }
+
+    /// Copies the view into a new Bitmap.
+    pub fn to_bitmap(self) -> Bitmap {
+        let mut bitmap = Bitmap::new(self.width, self.height);
+        for (destination, source) in bitmap.rows_mut().zip(self.rows()) {
+            destination.copy_from_slice(source);
+        }
+        bitmap
+    }
+}
+
+
+impl<'a, F: PixelFormat> BitmapView<'a, F> {
+    /// View of the part of `rect` inside of an image of given dimensions stored row by row in `buffer`.
+    pub(crate) fn from_buffer(buffer: &'a [F], width: usize, height: usize, rect: Rect) -> Self {
+        let rect = rect.intersection(&Rect::new(0, 0, width as i32, height as i32));
+        Self { pixels: &buffer[span(&rect, width)], stride: width, width: rect.width as usize, height: rect.height as usize }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Rectangle covering the whole view.
+    pub fn rect(&self) -> Rect {
+        Rect::new(0, 0, self.width as i32, self.height as i32)
+    }
+
+    /// View of the part of this view inside of `rect`, given in coordinates of this view.
+    pub fn view(&self, rect: Rect) -> BitmapView<'a, F> {
+        let rect = rect.intersection(&self.rect());
+        BitmapView { pixels: &self.pixels[span(&rect, self.stride)], stride: self.stride, width: rect.width as usize, height: rect.height as usize }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<F> {
+        (x < self.width && y < self.height).then(|| self.pixels[y * self.stride + x])
+    }
+
+    pub fn get_pixel(&self, x: usize, y: usize) -> Option<Pixel> {
+        self.get(x, y).map(|pixel| pixel.to_pixel())
+    }
+
+    /// Returns pixels of a single row, `None` outside of the view.
+    pub fn row(&self, y: usize) -> Option<&'a [F]> {
+        (y < self.height).then(|| &self.pixels[y * self.stride..y * self.stride + self.width])
+    }
+
+    /// Iterator over rows from top to bottom.
+    pub fn rows(&self) -> impl Iterator<Item = &'a [F]> + '_ {
+        (0..self.height).filter_map(move |y| self.row(y))
+    }
+
+    /// Copies the view into a new Surface.
+    pub fn to_surface(self) -> Surface<F> {
+        let mut surface = Surface::new(self.width, self.height);
+        for (destination, source) in surface.pixels_mut().chunks_exact_mut(self.width.max(1)).zip(self.rows()) {
+            destination.copy_from_slice(source);
+        }
+        surface
+    }
+}
+
+
+/// Mutable rectangular region of a Bitmap or Surface with its own origin and clipping, borrowed without copying.
+///
+/// Everything drawn into the view is clipped to its region, which allows split screen,
+/// picture in picture and tile based rendering into a single Bitmap.
+#[derive(Debug)]
+pub struct BitmapViewMut<'a, F: PixelFormat = Pixel> {
+    /// Pixels from the top left to the bottom right corner of the view, rows start `stride` pixels apart.
+    pixels: &'a mut [F],
+    stride: usize,
+    width: usize,
+    height: usize,
+}
+
+
+impl<'a> BitmapViewMut<'a> {
+    /// View of the part of the bitmap inside of `rect`, clipped to the bitmap.
+    pub fn new(bitmap: &'a mut Bitmap, rect: Rect) -> Self {
+        let (width, height) = (bitmap.width, bitmap.height);
+        Self::from_buffer(bitmap.pixels_mut(), width, height, rect)
+    }
+}
+
+
+impl<'a, F: PixelFormat> BitmapViewMut<'a, F> {
+    /// View of the part of `rect` inside of an image of given dimensions stored row by row in `buffer`.
+    pub(crate) fn from_buffer(buffer: &'a mut [F], width: usize, height: usize, rect: Rect) -> Self {
+        let rect = rect.intersection(&Rect::new(0, 0, width as i32, height as i32));
+        Self { pixels: &mut buffer[span(&rect, width)], stride: width, width: rect.width as usize, height: rect.height as usize }
+    }
+
+    /// Read only view of the same region.
+    pub fn as_view(&self) -> BitmapView<'_, F> {
+        BitmapView { pixels: self.pixels, stride: self.stride, width: self.width, height: self.height }
+    }
+
+    /// View of the part of this view inside of `rect`, given in coordinates of this view.
+    pub fn view_mut(&mut self, rect: Rect) -> BitmapViewMut<'_, F> {
+        let rect = rect.intersection(&RenderTarget::rect(self));
+        BitmapViewMut { pixels: &mut self.pixels[span(&rect, self.stride)], stride: self.stride, width: rect.width as usize, height: rect.height as usize }
+    }
+
+    /// Splits the view into two, the first with rows above `y` and the second with the rest.
+    ///
+    /// Both parts can be drawn into at the same time, for example from different threads.
+    pub fn split_at_row(self, y: usize) -> (BitmapViewMut<'a, F>, BitmapViewMut<'a, F>) {
+        let y = y.min(self.height);
+        let middle = (y * self.stride).min(self.pixels.len());
+        let (top, bottom) = self.pixels.split_at_mut(middle);
+        (
+            BitmapViewMut { pixels: top, stride: self.stride, width: self.width, height: y },
+            BitmapViewMut { pixels: bottom, stride: self.stride, width: self.width, height: self.height - y },
+        )
+    }
+
+    /// Returns pixels of a single row, `None` outside of the view.
+    pub fn row_mut(&mut self, y: usize) -> Option<&mut [F]> {
+        let (stride, width) = (self.stride, self.width);
+        (y < self.height).then(move || &mut self.pixels[y * stride..y * stride + width])
+    }
+
+    /// Sets all pixels of the view.
+    pub fn fill(&mut self, pixel: F) {
+        for y in 0..self.height {
+            self.row_mut(y).unwrap().fill(pixel);
+        }
+    }
+}
+
+
+impl<'a, F: PixelFormat> RenderTarget for BitmapViewMut<'a, F> {
+    type Format = F;
+
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
+
+    fn get(&self, x: usize, y: usize) -> Option<F> {
+        (x < self.width && y < self.height).then(|| self.pixels[y * self.stride + x])
+    }
+
+    fn set(&mut self, x: usize, y: usize, pixel: F) {
+        assert!(x < self.width && y < self.height, "pixel ({}, {}) outside of {}x{} view", x, y, self.width, self.height);
+        self.pixels[y * self.stride + x] = pixel;
+    }
+}
+
+
+/// Test suite for bitmap views.
+#[cfg(test)]
+mod test_view {
+    use super::*;
+    use crate::graphics::draw_line;
+
+    const WHITE: Pixel = Pixel::new(255, 255, 255);
+
+    #[test]
+    fn drawing_is_clipped_to_the_view() {
+        let mut bitmap = Bitmap::new(10, 10);
+        bitmap.fill(&(0, 0, 0));
+        let mut view = bitmap.view_mut(Rect::new(2, 3, 4, 4));
+        assert_eq!((view.width(), view.height()), (4, 4));
+        draw_line(&mut view, -100, 1, 100, 1, &(255, 255, 255));
+
+        let white: Vec<(usize, usize)> = bitmap.enumerate_pixels().filter(|(_, _, &pixel)| pixel == WHITE).map(|(x, y, _)| (x, y)).collect();
+        assert_eq!(white, vec![(2, 4), (3, 4), (4, 4), (5, 4)]);
+    }
+
+    #[test]
+    fn nested_views() {
+        let mut bitmap = Bitmap::new(10, 10);
+        let mut view = bitmap.view_mut(Rect::new(5, 5, 100, 100));
+        assert_eq!(RenderTarget::rect(&view), Rect::new(0, 0, 5, 5));
+        view.view_mut(Rect::new(1, 1, 1, 1)).set_pixel(0, 0, WHITE);
+        assert_eq!(view.get_pixel(1, 1), Some(WHITE));
+        assert_eq!(bitmap.get_pixel(6, 6), Some(WHITE));
+        assert_eq!(bitmap.view(Rect::new(4, 4, 4, 4)).view(Rect::new(2, 2, 9, 9)).get_pixel(0, 0), Some(WHITE));
+    }
+
+    #[test]
+    fn split_views_in_parallel() {
+        let mut bitmap = Bitmap::new(7, 9);
+        let view = bitmap.view_mut(Rect::new(1, 1, 5, 7));
+        let (mut top, mut bottom) = view.split_at_row(3);
+        assert_eq!((top.height(), bottom.height()), (3, 4));
+        std::thread::scope(|scope| {
+            scope.spawn(|| top.fill(Pixel::new(1, 0, 0)));
+            scope.spawn(|| bottom.fill(Pixel::new(2, 0, 0)));
+        });
+
+        assert_eq!(bitmap.enumerate_pixels().filter(|(_, _, pixel)| pixel.red == 1).count(), 15);
+        assert_eq!(bitmap.enumerate_pixels().filter(|(_, _, pixel)| pixel.red == 2).count(), 20);
+        assert_eq!(bitmap.get_pixel(0, 1).map(|pixel| pixel.red), Some(Pixel::default().red));
+        assert_eq!(bitmap.view(Rect::new(1, 4, 5, 4)).to_bitmap().pixels(), vec![Pixel::new(2, 0, 0); 20]);
+    }
+}